@@ -15,6 +15,11 @@ use dodrio::{
 use std::cell::RefCell;
 use std::convert::TryInto;
 
+/// A container element in the in-memory DOM to mount `Vdom`s in.
+fn container() -> dodrio::Element {
+    dodrio::native_dom::Node::create_element("div")
+}
+
 /// The simplest thing we can render: `<div/>`.
 struct Empty;
 impl<'a> Render<'a> for Empty {
@@ -84,7 +89,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         ParameterizedBenchmark::new(
             "same-list",
             |b, &n| {
-                let vdom = Vdom::new(&(), SimpleList(n));
+                let vdom = Vdom::new(&container(), SimpleList(n));
                 b.iter(|| {
                     vdom.immediately_render_and_diff(SimpleList(n));
                     black_box(&vdom);
@@ -94,7 +99,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         )
         .with_function("empty-to-full-list-to-empty", |b, &n| {
             b.iter(|| {
-                let vdom = Vdom::new(&(), Empty);
+                let vdom = Vdom::new(&container(), Empty);
                 vdom.immediately_render_and_diff(SimpleList(n));
                 black_box(&vdom);
                 vdom.immediately_render_and_diff(Empty);
@@ -102,7 +107,7 @@ fn criterion_benchmark(c: &mut Criterion) {
             });
         })
         .with_function("append-one-and-remove-one", |b, &n| {
-            let vdom = Vdom::new(&(), SimpleList(n));
+            let vdom = Vdom::new(&container(), SimpleList(n));
             b.iter(|| {
                 vdom.immediately_render_and_diff(SimpleList(n + 1));
                 black_box(&vdom);
@@ -1,6 +1,5 @@
 use dodrio::{bumpalo, Node, Render, RenderContext};
 use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
 
 /// Say hello to someone.
 struct SayHelloTo {
@@ -35,18 +34,8 @@ impl<'a> Render<'a> for SayHelloTo {
                         "value",
                         bumpalo::format!(in cx.bump, "{}", self.who).into_bump_str(),
                     )
-                    .on("input", |root, vdom, event| {
-                        // If the event's target is our input...
-                        let input = match event
-                            .target()
-                            .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok())
-                        {
-                            None => return,
-                            Some(input) => input,
-                        };
-
-                        // ...then get its value and update who we are greeting.
-                        let value = input.value();
+                    .on_input(|root, vdom, _event, value| {
+                        // Update who we are greeting with the input's new value.
                         let hello = root.unwrap_mut::<SayHelloTo>();
                         hello.set_who(value);
 
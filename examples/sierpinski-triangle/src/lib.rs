@@ -15,44 +15,38 @@ impl Container {
         Container { size: 25.0 }
     }
 
-    // Generate the container style to fluctuate triangle width.
+    // Generate the container transform to fluctuate triangle width.
     fn container_transform<'a>(&self, cx: &mut RenderContext<'a>, elapsed: f64) -> &'a str {
         let t = elapsed % 10.0;
         let scale = 0.45 + (if t > 5.0 { 10.0 - t } else { t }) / 40.0;
         let transform = bumpalo::format!(
             in cx.bump,
-            "transform: scaleX({}) scaleY(0.7) translateZ(0.1px)",
+            "scaleX({}) scaleY(0.7) translateZ(0.1px)",
             scale
         );
         transform.into_bump_str()
     }
 
-    // Generate the dot's position on the grid.
-    fn dot_style<'a>(&self, cx: &mut RenderContext<'a>, x: f64, y: f64) -> &'a str {
-        let s = self.size * 1.3;
-        let radius = s / 2.0;
-        let styles = bumpalo::format!(
-            in cx.bump,
-            r#"
-                width: {}px;
-                height: {}px;
-                left: {}px;
-                top: {}px;
-                border-radius: {}px;
-                line-height: {}px;
-            "#,
-            s, s, x, y, radius, s
-        );
-        styles.into_bump_str()
+    // Format a length in pixels.
+    fn px<'a>(cx: &mut RenderContext<'a>, length: f64) -> &'a str {
+        bumpalo::format!(in cx.bump, "{}px", length).into_bump_str()
     }
 
-    // Create a dot node.
+    // Create a dot node at the given position on the grid.
     fn dot<'a>(&self, cx: &mut RenderContext<'a>, x: f64, y: f64, content: u32) -> Node<'a> {
         use dodrio::builder::{div, text};
 
+        let s = Container::px(cx, self.size * 1.3);
+        let radius = Container::px(cx, self.size * 1.3 / 2.0);
+
         div(&cx)
             .attr("class", "dot")
-            .attr("style", self.dot_style(cx, x, y))
+            .style("width", s)
+            .style("height", s)
+            .style("left", Container::px(cx, x))
+            .style("top", Container::px(cx, y))
+            .style("border-radius", radius)
+            .style("line-height", s)
             .child(text(
                 bumpalo::format!(in cx.bump, "{}", content).into_bump_str(),
             ))
@@ -103,7 +97,7 @@ impl<'a> Render<'a> for Container {
 
         div(&cx)
             .attr("class", "container")
-            .attr("style", self.container_transform(cx, elapsed))
+            .style("transform", self.container_transform(cx, elapsed))
             .children(children)
             .finish()
     }
@@ -9,6 +9,24 @@ function string(mem, pointer, length) {
   return decoder.decode(buf);
 }
 
+// Decode the options of an event listener from their bits.
+function listenerOptions(bits) {
+  return {
+    capture: (bits & 1) !== 0,
+    passive: (bits & 2) !== 0,
+    once: (bits & 4) !== 0
+  };
+}
+
+// Scratch space for reassembling an `f64` from its two `u32` halves.
+const f64Scratch = new DataView(new ArrayBuffer(8));
+
+function f64(low, high) {
+  f64Scratch.setUint32(0, low, true);
+  f64Scratch.setUint32(4, high, true);
+  return f64Scratch.getFloat64(0, true);
+}
+
 const OP_TABLE = [
   // 0
   function setText(interpreter, mem8, mem32, i) {
@@ -23,7 +41,9 @@ const OP_TABLE = [
   function removeSelfAndNextSiblings(interpreter, mem8, mem32, i) {
     const node = interpreter.stack.pop();
     let sibling = node.nextSibling;
-    while (sibling) {
+    // The last child of a portal is followed by the portal's end marker, and
+    // maybe other contents of the portal's target, which must stay.
+    while (sibling && !sibling["dodrio-portal-end"]) {
       const temp = sibling.nextSibling;
       sibling.remove();
       sibling = temp;
@@ -142,8 +162,15 @@ const OP_TABLE = [
     const eventType = interpreter.getCachedString(eventId);
     const a = mem32[i++];
     const b = mem32[i++];
+    const bits = mem32[i++];
     const el = top(interpreter.stack);
-    el.addEventListener(eventType, interpreter.eventHandler);
+    if (interpreter.isDelegated(bits)) {
+      interpreter.delegate(eventType);
+      el[`dodrio-delegated-a-${eventType}`] = a;
+      el[`dodrio-delegated-b-${eventType}`] = b;
+      return i;
+    }
+    el.addEventListener(eventType, interpreter.eventHandler, listenerOptions(bits));
     el[`dodrio-a-${eventType}`] = a;
     el[`dodrio-b-${eventType}`] = b;
     return i;
@@ -153,9 +180,14 @@ const OP_TABLE = [
   function updateEventListener(interpreter, mem8, mem32, i) {
     const eventId = mem32[i++];
     const eventType = interpreter.getCachedString(eventId);
+    const a = mem32[i++];
+    const b = mem32[i++];
+    // The options can't change without re-adding the listener, but they tell
+    // us whether the listener is delegated.
+    const prefix = interpreter.isDelegated(mem32[i++]) ? "dodrio-delegated" : "dodrio";
     const el = top(interpreter.stack);
-    el[`dodrio-a-${eventType}`] = mem32[i++];
-    el[`dodrio-b-${eventType}`] = mem32[i++];
+    el[`${prefix}-a-${eventType}`] = a;
+    el[`${prefix}-b-${eventType}`] = b;
     return i;
   },
 
@@ -163,8 +195,14 @@ const OP_TABLE = [
   function removeEventListener(interpreter, mem8, mem32, i) {
     const eventId = mem32[i++];
     const eventType = interpreter.getCachedString(eventId);
+    const bits = mem32[i++];
     const el = top(interpreter.stack);
-    el.removeEventListener(eventType, interpreter.eventHandler);
+    if (interpreter.isDelegated(bits)) {
+      delete el[`dodrio-delegated-a-${eventType}`];
+      delete el[`dodrio-delegated-b-${eventType}`];
+      return i;
+    }
+    el.removeEventListener(eventType, interpreter.eventHandler, listenerOptions(bits));
     return i;
   },
 
@@ -275,32 +313,236 @@ const OP_TABLE = [
     const template = interpreter.getTemplate(id);
     interpreter.stack.push(template.cloneNode(true));
     return i;
+  },
+
+  // 26
+  function createComment(interpreter, mem8, mem32, i) {
+    interpreter.stack.push(document.createComment(""));
+    return i;
+  },
+
+  // 27
+  function createRawHtml(interpreter, mem8, mem32, i) {
+    const pointer = mem32[i++];
+    const length = mem32[i++];
+    const html = string(mem8, pointer, length);
+    // The markup may have any number of top-level nodes, so it is kept in a
+    // wrapper that the diff can treat as a single node.
+    const wrapper = document.createElement("div");
+    wrapper.innerHTML = html;
+    interpreter.stack.push(wrapper);
+    return i;
+  },
+
+  // 28
+  function setPropertyBool(interpreter, mem8, mem32, i) {
+    const nameId = mem32[i++];
+    const value = mem32[i++];
+    const name = interpreter.getCachedString(nameId);
+    top(interpreter.stack)[name] = value !== 0;
+    return i;
+  },
+
+  // 29
+  function setPropertyNumber(interpreter, mem8, mem32, i) {
+    const nameId = mem32[i++];
+    const low = mem32[i++];
+    const high = mem32[i++];
+    const name = interpreter.getCachedString(nameId);
+    top(interpreter.stack)[name] = f64(low, high);
+    return i;
+  },
+
+  // 30
+  function setPropertyString(interpreter, mem8, mem32, i) {
+    const nameId = mem32[i++];
+    const valueId = mem32[i++];
+    const name = interpreter.getCachedString(nameId);
+    const value = interpreter.getCachedString(valueId);
+    top(interpreter.stack)[name] = value;
+    return i;
+  },
+
+  // 31
+  function removeProperty(interpreter, mem8, mem32, i) {
+    const nameId = mem32[i++];
+    const name = interpreter.getCachedString(nameId);
+    const element = top(interpreter.stack);
+    if (Object.prototype.hasOwnProperty.call(element, name)) {
+      // An expando, such as a custom element's property that was set before
+      // it was upgraded.
+      delete element[name];
+    } else if (name in element) {
+      // A property that the element's prototype defines, which can't be
+      // deleted, so its default value is restored instead.
+      element[name] = interpreter.defaultPropertyValue(element, name);
+    }
+    return i;
+  },
+
+  // 32
+  function setStyle(interpreter, mem8, mem32, i) {
+    const propertyId = mem32[i++];
+    const valueId = mem32[i++];
+    const property = interpreter.getCachedString(propertyId);
+    const value = interpreter.getCachedString(valueId);
+    top(interpreter.stack).style.setProperty(property, value);
+    return i;
+  },
+
+  // 33
+  function removeStyle(interpreter, mem8, mem32, i) {
+    const propertyId = mem32[i++];
+    const property = interpreter.getCachedString(propertyId);
+    top(interpreter.stack).style.removeProperty(property);
+    return i;
+  },
+
+  // 34
+  function addClass(interpreter, mem8, mem32, i) {
+    const classId = mem32[i++];
+    const className = interpreter.getCachedString(classId);
+    top(interpreter.stack).classList.add(className);
+    return i;
+  },
+
+  // 35
+  function removeClass(interpreter, mem8, mem32, i) {
+    const classId = mem32[i++];
+    const className = interpreter.getCachedString(classId);
+    top(interpreter.stack).classList.remove(className);
+    return i;
+  },
+
+  // 36
+  function setNodeRef(interpreter, mem8, mem32, i) {
+    interpreter.savedNodes.push(top(interpreter.stack));
+    return i;
+  },
+
+  // 37
+  function trackLifecycle(interpreter, mem8, mem32, i) {
+    interpreter.savedNodes.push(top(interpreter.stack));
+    return i;
+  },
+
+  // 38
+  function createPortal(interpreter, mem8, mem32, i) {
+    const id = mem32[i++];
+    const key = mem32[i++];
+    const start = document.createComment("");
+    const end = document.createComment("");
+    interpreter.portalTargets.get(id).append(start, end);
+    const portal = new PortalRange(start, end);
+    interpreter.portals.set(key, portal);
+    interpreter.stack.push(portal);
+    return i;
+  },
+
+  // 39
+  function pushPortal(interpreter, mem8, mem32, i) {
+    const key = mem32[i++];
+    interpreter.stack.push(interpreter.portals.get(key));
+    return i;
+  },
+
+  // 40
+  function removePortal(interpreter, mem8, mem32, i) {
+    const key = mem32[i++];
+    interpreter.portals.get(key).remove();
+    interpreter.portals.delete(key);
+    return i;
   }
 ];
 
+// The nodes between a rendered portal's start and end markers in its target,
+// which stand in for the parent of the portal's children. This implements just
+// the parts of `Node` that the ops use on a parent.
+class PortalRange {
+  constructor(start, end) {
+    this.start = start;
+    this.end = end;
+    end["dodrio-portal-end"] = true;
+  }
+
+  get childNodes() {
+    const children = [];
+    for (let node = this.start.nextSibling; node !== this.end; node = node.nextSibling) {
+      children.push(node);
+    }
+    return children;
+  }
+
+  set textContent(text) {
+    for (const child of this.childNodes) {
+      child.remove();
+    }
+    if (text !== "") {
+      this.appendChild(document.createTextNode(text));
+    }
+  }
+
+  appendChild(child) {
+    this.end.before(child);
+  }
+
+  // Remove the portal's children along with the markers.
+  remove() {
+    for (const child of this.childNodes) {
+      child.remove();
+    }
+    this.start.remove();
+    this.end.remove();
+  }
+}
+
 export class ChangeListInterpreter {
-  constructor(container) {
+  constructor(container, delegateEvents) {
     this.trampoline = null;
     this.container = container;
+    this.delegateEvents = delegateEvents;
+    this.delegatedTypes = new Set();
     this.ranges = [];
     this.stack = [];
     this.strings = new Map();
     this.temporaries = [];
     this.templates = new Map();
+    this.savedNodes = [];
+    this.portalTargets = new Map();
+    this.portals = new Map();
+    this.pristineElements = new Map();
   }
 
   unmount() {
     this.trampoline.mounted = false;
 
+    for (const root of this.roots()) {
+      for (const type of this.delegatedTypes) {
+        root.removeEventListener(type, this.delegatedHandler);
+        root.removeEventListener(type, this.delegatedCaptureHandler, true);
+      }
+    }
+
+    // Portals' contents are removed along with the rest of the VDOM. Their
+    // targets may have other contents, which are left alone.
+    for (const portal of this.portals.values()) {
+      portal.remove();
+    }
+
     // Null out all of our properties just to ensure that if we mistakenly ever
     // call a method on this instance again, it will throw.
     this.trampoline = null;
     this.container = null;
+    this.delegatedTypes = null;
     this.ranges = null;
     this.stack = null;
     this.strings = null;
     this.temporaries = null;
     this.templates = null;
+    this.savedNodes = null;
+    this.portalTargets = null;
+    this.portals = null;
+    this.pristineElements = null;
   }
 
   addChangeListRange(start, len) {
@@ -313,7 +555,7 @@ export class ChangeListInterpreter {
       return;
     }
 
-    this.stack.push(this.container.firstChild);
+    this.stack.push(this.container);
     const mem8 = new Uint8Array(memory.buffer);
     const mem32 = new Uint32Array(memory.buffer);
 
@@ -348,6 +590,12 @@ export class ChangeListInterpreter {
     return this.strings.get(id);
   }
 
+  takeSavedNodes() {
+    const savedNodes = this.savedNodes;
+    this.savedNodes = [];
+    return savedNodes;
+  }
+
   saveTemplate(id, template) {
     this.templates.set(id, template);
   }
@@ -356,6 +604,62 @@ export class ChangeListInterpreter {
     return this.templates.get(id);
   }
 
+  // The value that a property defined by the element's prototype has when
+  // nothing has set it.
+  defaultPropertyValue(element, name) {
+    // Form controls' state defaults to what their markup says.
+    switch (name) {
+      case "value":
+        if ("defaultValue" in element) {
+          return element.defaultValue;
+        }
+        break;
+      case "checked":
+        if ("defaultChecked" in element) {
+          return element.defaultChecked;
+        }
+        break;
+      case "selected":
+        if ("defaultSelected" in element) {
+          return element.defaultSelected;
+        }
+        break;
+    }
+
+    // Otherwise, it is whatever a newly created element of the same kind has.
+    const key = `${element.namespaceURI} ${element.localName}`;
+    let pristine = this.pristineElements.get(key);
+    if (pristine === undefined) {
+      pristine = document.createElementNS(element.namespaceURI, element.localName);
+      this.pristineElements.set(key, pristine);
+    }
+    return pristine[name];
+  }
+
+  addPortalTarget(id, target) {
+    this.portalTargets.set(id, target);
+    for (const type of this.delegatedTypes) {
+      target.addEventListener(type, this.delegatedHandler);
+      target.addEventListener(type, this.delegatedCaptureHandler, true);
+    }
+  }
+
+  removePortalTarget(id) {
+    const target = this.portalTargets.get(id);
+    this.portalTargets.delete(id);
+    for (const type of this.delegatedTypes) {
+      target.removeEventListener(type, this.delegatedHandler);
+      target.removeEventListener(type, this.delegatedCaptureHandler, true);
+    }
+  }
+
+  // The elements that delegated listeners are added to: the container, and
+  // every portal target.
+  *roots() {
+    yield this.container;
+    yield* this.portalTargets.values();
+  }
+
   initEventsTrampoline(trampoline) {
     this.trampoline = trampoline;
     trampoline.mounted = true;
@@ -372,6 +676,83 @@ export class ChangeListInterpreter {
       const a = this[`dodrio-a-${type}`];
       const b = this[`dodrio-b-${type}`];
       trampoline(event, a, b);
+    };
+
+    // Invoke the delegated listener registered on `el` for this event, if any.
+    function dispatchDelegated(event, el) {
+      const type = event.type;
+      const a = el[`dodrio-delegated-a-${type}`];
+      if (a === undefined) {
+        return;
+      }
+      const b = el[`dodrio-delegated-b-${type}`];
+
+      // The listener was registered on `el`, not on the container that the
+      // physical listener was added to, so make `currentTarget` say so.
+      Object.defineProperty(event, "currentTarget", { configurable: true, value: el });
+      try {
+        trampoline(event, a, b);
+      } finally {
+        delete event.currentTarget;
+      }
+    }
+
+    // Walk from the event's target up to the container or portal target that
+    // this handler was added to, like the event would bubble, until a listener
+    // stops its propagation. When a portal target is inside the container, the
+    // container's handler picks up the walk where the portal target's left off.
+    this.delegatedHandler = function(event) {
+      if (!trampoline.mounted) {
+        throw new Error("invocation of listener after VDOM has been unmounted");
+      }
+
+      const root = this;
+      let el = event["dodrio-delegated-resume"] || event.target;
+      while (el && el !== root && !event.cancelBubble) {
+        dispatchDelegated(event, el);
+        el = el.parentNode;
+      }
+      event["dodrio-delegated-resume"] = root;
+    };
+
+    // Events that don't bubble never reach the bubbling listeners, so catch
+    // them on their way down instead and only dispatch them to their target.
+    // The event is caught by both the container and a portal target inside
+    // it, but must only be dispatched once.
+    this.delegatedCaptureHandler = function(event) {
+      if (!trampoline.mounted) {
+        throw new Error("invocation of listener after VDOM has been unmounted");
+      }
+
+      if (
+        !event.bubbles &&
+        event.target !== this &&
+        event["dodrio-delegated-dispatched"] !== trampoline
+      ) {
+        event["dodrio-delegated-dispatched"] = trampoline;
+        dispatchDelegated(event, event.target);
+      }
+    };
+  }
+
+  // Whether a listener with the given options bits is delegated to the
+  // container. Listeners with any options are always added to their element,
+  // which changes their order relative to delegated listeners; see
+  // `VdomBuilder::delegate_events`.
+  isDelegated(bits) {
+    return this.delegateEvents && bits === 0;
+  }
+
+  // Make sure the container and portal targets are listening for events of
+  // the given type.
+  delegate(type) {
+    if (this.delegatedTypes.has(type)) {
+      return;
+    }
+    this.delegatedTypes.add(type);
+    for (const root of this.roots()) {
+      root.addEventListener(type, this.delegatedHandler);
+      root.addEventListener(type, this.delegatedCaptureHandler, true);
     }
   }
 }
@@ -1,7 +1,15 @@
 //! Helpers for building virtual DOM nodes.
 
-use crate::{node::NodeKey, Attribute, Listener, Node, RootRender, VdomWeak};
+use crate::{
+    node::{ElementNode, Lifecycle, ListenerCallback, NodeKey, NodeKind},
+    node_ref::NodeRefId,
+    Attribute, Listener, ListenerOptions, Node, NodeRef, Property, PropertyValue, RenderContext,
+    RootRender, VdomWeak,
+};
 use bumpalo::Bump;
+use std::future::Future;
+use std::hash::Hash;
+use wasm_bindgen::{JsCast, JsValue};
 
 /// A virtual DOM element builder.
 ///
@@ -20,8 +28,13 @@ where
     tag_name: &'a str,
     listeners: Listeners,
     attributes: Attributes,
+    properties: bumpalo::collections::Vec<'a, Property<'a>>,
+    styles: bumpalo::collections::Vec<'a, Attribute<'a>>,
+    classes: bumpalo::collections::Vec<'a, &'a str>,
     children: Children,
     namespace: Option<&'a str>,
+    node_ref: Option<NodeRefId>,
+    lifecycle: Option<Lifecycle<'a>>,
 }
 
 impl<'a>
@@ -66,8 +79,13 @@ impl<'a>
             tag_name,
             listeners: bumpalo::collections::Vec::new_in(bump),
             attributes: bumpalo::collections::Vec::new_in(bump),
+            properties: bumpalo::collections::Vec::new_in(bump),
+            styles: bumpalo::collections::Vec::new_in(bump),
+            classes: bumpalo::collections::Vec::new_in(bump),
             children: bumpalo::collections::Vec::new_in(bump),
             namespace: None,
+            node_ref: None,
+            lifecycle: None,
         }
     }
 }
@@ -116,8 +134,13 @@ where
             tag_name: self.tag_name,
             listeners,
             attributes: self.attributes,
+            properties: self.properties,
+            styles: self.styles,
+            classes: self.classes,
             children: self.children,
             namespace: self.namespace,
+            node_ref: self.node_ref,
+            lifecycle: self.lifecycle,
         }
     }
 
@@ -155,8 +178,13 @@ where
             tag_name: self.tag_name,
             listeners: self.listeners,
             attributes,
+            properties: self.properties,
+            styles: self.styles,
+            classes: self.classes,
             children: self.children,
             namespace: self.namespace,
+            node_ref: self.node_ref,
+            lifecycle: self.lifecycle,
         }
     }
 
@@ -194,8 +222,13 @@ where
             tag_name: self.tag_name,
             listeners: self.listeners,
             attributes: self.attributes,
+            properties: self.properties,
+            styles: self.styles,
+            classes: self.classes,
             children,
             namespace: self.namespace,
+            node_ref: self.node_ref,
+            lifecycle: self.lifecycle,
         }
     }
 
@@ -221,8 +254,13 @@ where
             tag_name: self.tag_name,
             listeners: self.listeners,
             attributes: self.attributes,
+            properties: self.properties,
+            styles: self.styles,
+            classes: self.classes,
             children: self.children,
             namespace,
+            node_ref: self.node_ref,
+            lifecycle: self.lifecycle,
         }
     }
 
@@ -259,12 +297,248 @@ where
     /// ```
     #[inline]
     pub fn key(mut self, key: u32) -> Self {
-        use std::u32;
-        debug_assert!(key != u32::MAX);
-        self.key = NodeKey(key);
+        self.key = NodeKey::new(key);
         self
     }
 
+    /// Set this element's key to the hash of `key`, for keyed diffing.
+    ///
+    /// This is like `key`, but for lists whose items are identified by
+    /// something other than a `u32`, such as a UUID or a slug. See
+    /// `NodeKey::hashed` for details.
+    ///
+    /// The same invariants as for `key` apply: keys must be unique among
+    /// siblings, and either all siblings are keyed or none are.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump};
+    ///
+    /// let b = Bump::new();
+    ///
+    /// let my_li = li(&b)
+    ///     .hashed_key("getting-started")
+    ///     .finish();
+    /// ```
+    #[inline]
+    pub fn hashed_key<K>(mut self, key: &K) -> Self
+    where
+        K: ?Sized + Hash,
+    {
+        self.key = NodeKey::hashed(key);
+        self
+    }
+
+    /// Attach a `NodeRef` to this element, so that it holds the physical DOM
+    /// element once this render's changes are applied.
+    ///
+    /// The physical elements of a `Vdom` created with `Vdom::with_transport`
+    /// are out of reach, so its `NodeRef`s are never filled in.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump, NodeRef};
+    ///
+    /// let b = Bump::new();
+    /// let canvas_ref = NodeRef::new();
+    ///
+    /// let my_canvas = canvas(&b).node_ref(&canvas_ref).finish();
+    /// ```
+    #[inline]
+    pub fn node_ref(mut self, node_ref: &NodeRef) -> Self {
+        self.node_ref = Some(node_ref.id());
+        self
+    }
+
+    /// Set the callback to invoke once this element has been inserted into
+    /// the physical DOM.
+    ///
+    /// The callback is invoked with the physical element after all of the
+    /// render's changes are applied, so the element is already in place among
+    /// its new siblings and can be measured or handed off to other libraries.
+    /// It is invoked once when the element is created, and not again when the
+    /// element is updated or moved by later renders.
+    ///
+    /// The physical elements of a `Vdom` created with `Vdom::with_transport`
+    /// are out of reach, so it never invokes lifecycle callbacks.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump};
+    ///
+    /// let b = Bump::new();
+    ///
+    /// let my_chart = div(&b)
+    ///     .on_mount(|root, vdom, element| {
+    ///         // Draw a chart inside `element`...
+    ///     })
+    ///     .finish();
+    /// ```
+    #[inline]
+    pub fn on_mount<F>(mut self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut dyn RootRender, VdomWeak, crate::Element),
+    {
+        let callback = self.bump.alloc(callback);
+        self.lifecycle.get_or_insert_with(Lifecycle::new).on_mount = Some(callback);
+        self
+    }
+
+    /// Set the callback to invoke once this element has been removed from the
+    /// physical DOM.
+    ///
+    /// The callback is invoked with the physical element after all of the
+    /// render's changes are applied. That includes when an ancestor of this
+    /// element was removed or replaced, and when the whole virtual DOM is
+    /// dropped or unmounted. In that last case, the elements' callbacks are
+    /// invoked in no particular order, and the `VdomWeak` they are given can
+    /// no longer schedule renders.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump};
+    ///
+    /// let b = Bump::new();
+    ///
+    /// let my_chart = div(&b)
+    ///     .on_unmount(|root, vdom, element| {
+    ///         // Tear down the chart that was drawn inside `element`...
+    ///     })
+    ///     .finish();
+    /// ```
+    #[inline]
+    pub fn on_unmount<F>(mut self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut dyn RootRender, VdomWeak, crate::Element),
+    {
+        let callback = self.bump.alloc(callback);
+        self.lifecycle.get_or_insert_with(Lifecycle::new).on_unmount = Some(callback);
+        self
+    }
+
+    /// Set a DOM property on this element.
+    ///
+    /// Properties are set directly on the physical DOM node, rather than with
+    /// `setAttribute`. This is necessary for state that has no attribute, such
+    /// as a checkbox's `indeterminate` or a scrolling container's `scrollTop`,
+    /// and for the properties of custom elements.
+    ///
+    /// Properties are diffed against the previous render's properties, not
+    /// against the physical DOM node, and they are not included in
+    /// server-side rendered markup. A property that is no longer rendered is
+    /// removed: an expando property is deleted, and a property that the
+    /// element defines, such as an input's `value`, gets its default back.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump};
+    ///
+    /// let b = Bump::new();
+    ///
+    /// let my_checkbox = input(&b)
+    ///     .attr("type", "checkbox")
+    ///     .prop("indeterminate", true)
+    ///     .finish();
+    /// ```
+    #[inline]
+    pub fn prop<V>(mut self, name: &'a str, value: V) -> Self
+    where
+        V: Into<PropertyValue<'a>>,
+    {
+        self.properties.push(Property {
+            name,
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Set an inline style property on this element.
+    ///
+    /// Each style property is diffed on its own, so changing one of them
+    /// doesn't rewrite the whole `style` attribute. This can be mixed with
+    /// setting the `style` attribute via `attr`, in which case the attribute's
+    /// declarations come first and these styles are applied on top of them.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump};
+    ///
+    /// let b = Bump::new();
+    ///
+    /// let my_red_div = div(&b)
+    ///     .style("color", "red")
+    ///     .style("margin-left", "1em")
+    ///     .finish();
+    /// ```
+    #[inline]
+    pub fn style(mut self, property: &'a str, value: &'a str) -> Self {
+        self.styles.push(Attribute {
+            name: property,
+            value,
+        });
+        self
+    }
+
+    /// Add a class name to this element's class list.
+    ///
+    /// Unlike setting the `class` attribute, which replaces the whole class
+    /// list, each class name is added and removed on its own when diffing,
+    /// and class names that were added by something other than this element's
+    /// builder are left alone. Setting the `class` attribute via `attr` is the
+    /// same as adding each of its class names with this method.
+    ///
+    /// The class name must be a single token, without any whitespace.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump};
+    ///
+    /// let b = Bump::new();
+    ///
+    /// let my_button = button(&b)
+    ///     .class("btn")
+    ///     .class("btn-primary")
+    ///     .finish();
+    /// ```
+    #[inline]
+    pub fn class(mut self, name: &'a str) -> Self {
+        self.classes.push(name);
+        self
+    }
+
+    /// Add a class name to this element's class list if `condition` is true.
+    ///
+    /// See `class` for details.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump};
+    ///
+    /// let b = Bump::new();
+    /// let is_done = true;
+    ///
+    /// let my_todo = li(&b)
+    ///     .class("todo")
+    ///     .class_if("completed", is_done)
+    ///     .finish();
+    /// ```
+    #[inline]
+    pub fn class_if(self, name: &'a str, condition: bool) -> Self {
+        if condition {
+            self.class(name)
+        } else {
+            self
+        }
+    }
+
     /// Create the virtual DOM node described by this builder.
     ///
     /// # Example
@@ -289,17 +563,51 @@ where
         let listeners: &'a [Listener<'a>] = listeners.as_ref();
 
         let attributes: &'a Attributes = self.bump.alloc(self.attributes);
-        let attributes: &'a [Attribute<'a>] = attributes.as_ref();
+        let mut attributes: &'a [Attribute<'a>] = attributes.as_ref();
 
-        Node::element(
-            self.bump,
-            self.key,
-            self.tag_name,
+        // A `class` attribute is split into class list tokens, which come
+        // before the class names added with `class`, so that setting it
+        // doesn't wipe those out.
+        let mut classes = self.classes;
+        if attributes.iter().any(|attr| attr.name == "class") {
+            let mut rest = bumpalo::collections::Vec::with_capacity_in(attributes.len(), self.bump);
+            let mut tokens = bumpalo::collections::Vec::new_in(self.bump);
+            for attr in attributes {
+                if attr.name == "class" {
+                    tokens.extend(attr.value.split_whitespace());
+                } else {
+                    rest.push(attr.clone());
+                }
+            }
+            tokens.extend(classes);
+            attributes = rest.into_bump_slice();
+            classes = tokens;
+        }
+
+        let properties: &'a [Property<'a>] = self.properties.into_bump_slice();
+        let styles: &'a [Attribute<'a>] = self.styles.into_bump_slice();
+        let classes: &'a [&'a str] = classes.into_bump_slice();
+
+        let bump = self.bump;
+        let lifecycle: Option<&'a Lifecycle<'a>> = self.lifecycle.map(|l| &*bump.alloc(l));
+
+        let element = self.bump.alloc(ElementNode {
+            key: self.key,
+            tag_name: self.tag_name,
             listeners,
             attributes,
+            properties,
+            styles,
+            classes,
             children,
-            self.namespace,
-        )
+            namespace: self.namespace,
+            node_ref: self.node_ref,
+            lifecycle,
+        });
+
+        Node {
+            kind: NodeKind::Element(element),
+        }
     }
 }
 
@@ -330,16 +638,291 @@ where
     ///     .finish();
     /// ```
     #[inline]
-    pub fn on<F>(mut self, event: &'a str, callback: F) -> Self
+    pub fn on<F>(self, event: &'a str, callback: F) -> Self
     where
         F: 'static + Fn(&mut dyn RootRender, VdomWeak, web_sys::Event),
     {
-        self.listeners.push(Listener {
-            event,
-            callback: self.bump.alloc(callback),
-        });
+        self.on_with_options(event, ListenerOptions::default(), callback)
+    }
+
+    /// Add a new event listener to this element, with the given options.
+    ///
+    /// This is like `on`, but the listener is added with options such as
+    /// `passive`, which touch and scroll listeners should usually set.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump, ListenerOptions};
+    ///
+    /// let b = Bump::new();
+    ///
+    /// let options = ListenerOptions {
+    ///     passive: true,
+    ///     ..Default::default()
+    /// };
+    /// let my_list = ul(&b)
+    ///     .on_with_options("touchstart", options, |root, vdom, event| {
+    ///         // ...
+    ///     })
+    ///     .finish();
+    /// ```
+    #[inline]
+    pub fn on_with_options<F>(
+        mut self,
+        event: &'a str,
+        options: ListenerOptions,
+        callback: F,
+    ) -> Self
+    where
+        F: 'static + Fn(&mut dyn RootRender, VdomWeak, web_sys::Event),
+    {
+        self.listeners
+            .push(on_with_options(self.bump, event, options, callback));
+        self
+    }
+
+    /// Add a new event listener to this element that invokes a plain function
+    /// with the given data.
+    ///
+    /// Unlike with `on`, the callback doesn't need to capture anything, so the
+    /// same function can be shared by every element in a large list, with
+    /// each element passing its own small `Copy` payload. The payload may also
+    /// be a reference to data in the render's bump arena, which lives as long
+    /// as this element does.
+    ///
+    /// No closure is allocated for the listener. A payload that fits in a
+    /// pointer is stored in the listener itself, and a bigger one is copied
+    /// into the bump arena.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump, RootRender, VdomWeak};
+    ///
+    /// fn remove_row(root: &mut dyn RootRender, vdom: VdomWeak, _event: web_sys::Event, i: usize) {
+    ///     // Remove the `i`th row...
+    /// }
+    ///
+    /// let b = Bump::new();
+    ///
+    /// let rows = (0..1000).map(|i| {
+    ///     tr(&b)
+    ///         .on_with("click", i, remove_row)
+    ///         .finish()
+    /// });
+    /// ```
+    #[inline]
+    pub fn on_with<T>(
+        mut self,
+        event: &'a str,
+        data: T,
+        callback: fn(&mut dyn RootRender, VdomWeak, web_sys::Event, T),
+    ) -> Self
+    where
+        T: 'a + Copy,
+    {
+        self.listeners
+            .push(on_with(self.bump, event, data, callback));
         self
     }
+
+    /// Add a new event listener to this element whose callback returns a
+    /// future.
+    ///
+    /// The callback is invoked like an `on` callback, and the future it
+    /// returns is then driven by the virtual DOM. Between `await`s, the future
+    /// can get at the root rendering component again with
+    /// `VdomWeak::with_component`. Once the future completes, a re-render is
+    /// scheduled. If the virtual DOM is dropped before then, the future is
+    /// dropped without being polled again.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump, Node, Render, RenderContext};
+    ///
+    /// # async fn fetch_greeting() -> String { unimplemented!() }
+    /// struct Greeting(String);
+    ///
+    /// impl<'a> Render<'a> for Greeting {
+    ///     // ...
+    /// #   fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> { unimplemented!() }
+    /// }
+    ///
+    /// let b = Bump::new();
+    ///
+    /// let my_button = button(&b)
+    ///     .on_async("click", |_root, vdom, _event| async move {
+    ///         let greeting = fetch_greeting().await;
+    ///         let _ = vdom
+    ///             .with_component(move |root| {
+    ///                 root.unwrap_mut::<Greeting>().0 = greeting;
+    ///             })
+    ///             .await;
+    ///     })
+    ///     .finish();
+    /// ```
+    #[inline]
+    pub fn on_async<F, Fut>(self, event: &'a str, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut dyn RootRender, VdomWeak, web_sys::Event) -> Fut,
+        Fut: 'static + Future<Output = ()>,
+    {
+        self.on(event, move |root, vdom, event| {
+            let future = callback(root, vdom.clone(), event);
+            vdom.spawn_listener_future(future);
+        })
+    }
+
+    /// Add a `click` event listener to this element, whose callback is given
+    /// the `MouseEvent`.
+    ///
+    /// `click` events that aren't `MouseEvent`s, such as a plain `Event`
+    /// dispatched by a script, are skipped. The same goes for the other typed
+    /// listeners, like `on_keydown`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump};
+    ///
+    /// let b = Bump::new();
+    ///
+    /// let my_button = button(&b)
+    ///     .on_click(|root, vdom, event| {
+    ///         let (x, y) = (event.client_x(), event.client_y());
+    ///         // ...
+    ///     })
+    ///     .finish();
+    /// ```
+    #[inline]
+    pub fn on_click<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut dyn RootRender, VdomWeak, web_sys::MouseEvent),
+    {
+        self.on("click", move |root, vdom, event| {
+            if let Some(event) = cast_event(event) {
+                callback(root, vdom, event)
+            }
+        })
+    }
+
+    /// Add a `pointermove` event listener to this element, whose callback is
+    /// given the `PointerEvent`.
+    #[inline]
+    pub fn on_pointermove<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut dyn RootRender, VdomWeak, web_sys::PointerEvent),
+    {
+        self.on("pointermove", move |root, vdom, event| {
+            if let Some(event) = cast_event(event) {
+                callback(root, vdom, event)
+            }
+        })
+    }
+
+    /// Add a `keydown` event listener to this element, whose callback is given
+    /// the `KeyboardEvent`.
+    #[inline]
+    pub fn on_keydown<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut dyn RootRender, VdomWeak, web_sys::KeyboardEvent),
+    {
+        self.on("keydown", move |root, vdom, event| {
+            if let Some(event) = cast_event(event) {
+                callback(root, vdom, event)
+            }
+        })
+    }
+
+    /// Add an `input` event listener to this element, whose callback is given
+    /// the event and the current value of the event's target.
+    ///
+    /// The callback is given a plain `Event`, rather than an `InputEvent`,
+    /// because some browsers fire plain `Event`s for `input` on some elements,
+    /// such as `<select>`s and checkboxes.
+    ///
+    /// The value is the target's `value` property, which `<input>`,
+    /// `<textarea>`, and `<select>` elements have. For other targets, it is the
+    /// empty string.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, bumpalo::Bump};
+    ///
+    /// let b = Bump::new();
+    ///
+    /// let my_input = input(&b)
+    ///     .on_input(|root, vdom, event, value| {
+    ///         // Do something with the new `value`...
+    ///     })
+    ///     .finish();
+    /// ```
+    #[inline]
+    pub fn on_input<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut dyn RootRender, VdomWeak, web_sys::Event, String),
+    {
+        self.on("input", move |root, vdom, event| {
+            let value = target_value(&event);
+            callback(root, vdom, event, value)
+        })
+    }
+
+    /// Add a `change` event listener to this element, whose callback is given
+    /// the event and the current value of the event's target, like `on_input`.
+    #[inline]
+    pub fn on_change<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut dyn RootRender, VdomWeak, web_sys::Event, String),
+    {
+        self.on("change", move |root, vdom, event| {
+            let value = target_value(&event);
+            callback(root, vdom, event, value)
+        })
+    }
+
+    /// Add a `submit` event listener to this element.
+    ///
+    /// The callback is given a plain `Event`, rather than a `SubmitEvent`,
+    /// because not every browser that Dodrio supports fires `SubmitEvent`s.
+    /// Remember to call `prevent_default` on it to keep the browser from
+    /// navigating away.
+    #[inline]
+    pub fn on_submit<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut dyn RootRender, VdomWeak, web_sys::Event),
+    {
+        self.on("submit", callback)
+    }
+}
+
+/// Cast an event to the type that a typed listener expects, or warn and return
+/// `None` if it isn't one. Scripts can dispatch a plain `Event` of any type, so
+/// a `click` event isn't necessarily a `MouseEvent`.
+fn cast_event<T: JsCast>(event: web_sys::Event) -> Option<T> {
+    match event.dyn_into() {
+        Ok(event) => Some(event),
+        Err(event) => {
+            warn!(
+                "skipping a typed `{}` listener for an event of the wrong type",
+                event.type_()
+            );
+            None
+        }
+    }
+}
+
+/// Get the `value` property of an event's target, or the empty string if it
+/// doesn't have one.
+fn target_value(event: &web_sys::Event) -> String {
+    event
+        .target()
+        .and_then(|target| js_sys::Reflect::get(&target, &JsValue::from_str("value")).ok())
+        .and_then(|value| value.as_string())
+        .unwrap_or_default()
 }
 
 impl<'a, Listeners, Children>
@@ -1032,6 +1615,151 @@ pub fn text<'a>(contents: &'a str) -> Node<'a> {
     Node::text(contents)
 }
 
+/// Construct a node from a string of trusted HTML markup.
+///
+/// The markup is parsed by the browser as-is, without any escaping, so it must
+/// never contain untrusted input. It may have any number of top-level nodes,
+/// which are rendered inside a `<div>` wrapper element, also when rendering to
+/// a string with `dodrio::ssr`. The markup is not diffed: whenever it changes,
+/// the old wrapper is replaced with a new one.
+///
+/// # Example
+///
+/// ```no_run
+/// use dodrio::{builder::*, bumpalo::Bump};
+///
+/// let b = Bump::new();
+///
+/// // Markup that was rendered from markdown ahead of time.
+/// let post = div(&b)
+///     .children([raw_html("<p>Hello, <em>world</em>!</p>")])
+///     .finish();
+/// ```
+#[inline]
+pub fn raw_html<'a>(html: &'a str) -> Node<'a> {
+    Node::raw_html(html)
+}
+
+/// Construct an empty placeholder node.
+///
+/// Rendering `empty()` in place of a conditionally shown node keeps the
+/// positions of its siblings stable, so that toggling the node doesn't cause its
+/// siblings to be diffed against each other. It is rendered as an empty comment
+/// node in the physical DOM.
+///
+/// An `Option<Node>` can also be converted into a `Node` with `into()`, which
+/// uses an empty placeholder for `None`.
+///
+/// # Example
+///
+/// ```no_run
+/// use dodrio::{builder::*, bumpalo::Bump};
+///
+/// let b = Bump::new();
+/// let show_warning = false;
+///
+/// let form = div(&b)
+///     .children([
+///         if show_warning {
+///             p(&b).children([text("warning!")]).finish()
+///         } else {
+///             empty()
+///         },
+///         input(&b).finish(),
+///     ])
+///     .finish();
+/// ```
+#[inline]
+pub fn empty<'a>() -> Node<'a> {
+    Node::empty()
+}
+
+/// Construct a fragment of sibling nodes that are rendered without a wrapper
+/// element.
+///
+/// This is useful for components that render more than one root, such as
+/// several table rows or list items.
+///
+/// # Example
+///
+/// ```no_run
+/// use dodrio::{builder::*, bumpalo::Bump};
+///
+/// let b = Bump::new();
+///
+/// // Create `<td>a</td><td>b</td>` without a wrapper element.
+/// let cells = fragment(&b, [
+///     td(&b).children([text("a")]).finish(),
+///     td(&b).children([text("b")]).finish(),
+/// ]);
+/// ```
+#[inline]
+pub fn fragment<'a, B, Children>(bump: B, children: Children) -> Node<'a>
+where
+    B: Into<&'a Bump>,
+    Children: 'a + AsRef<[Node<'a>]>,
+{
+    let bump = bump.into();
+    let children: &'a Children = bump.alloc(children);
+    Node::fragment(children.as_ref())
+}
+
+/// Construct a portal, which renders its children into the `target` element
+/// instead of into the portal's parent.
+///
+/// This is useful for modals, tooltips, and toasts, which need to escape
+/// ancestors with `overflow: hidden` or their own stacking context. The
+/// portal's children are appended to the `target` between a pair of empty
+/// comment nodes that mark where they start and end, and are removed from it,
+/// along with the markers, when the portal is no longer rendered or the virtual
+/// DOM is dropped. In its parent, the portal is an empty placeholder comment
+/// node, like `empty()`.
+///
+/// The `target` may have other contents, like `document.body`, and several
+/// portals may render into the same target. The virtual DOM holds on to a
+/// target for as long as some portal renders into it.
+///
+/// The listeners of the portal's children are invoked with the root rendering
+/// component, just like any other listeners. Events bubble up through the
+/// physical DOM, though, so events in the portal's children don't bubble
+/// through the portal's virtual ancestors.
+///
+/// A `Vdom` whose changes are applied remotely only renders the portal's
+/// placeholder, since the `target` element isn't in the remote DOM.
+///
+/// # Example
+///
+/// ```no_run
+/// use dodrio::{builder::*, Node, Render, RenderContext};
+///
+/// struct Modal {
+///     root: dodrio::Element,
+/// }
+///
+/// impl<'a> Render<'a> for Modal {
+///     fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+///         div(&cx)
+///             .children([
+///                 text("The modal's contents are not in here."),
+///                 portal(cx, &self.root, [text("Are you sure?")]),
+///             ])
+///             .finish()
+///     }
+/// }
+/// ```
+pub fn portal<'a, Children>(
+    cx: &RenderContext<'a>,
+    target: &crate::Element,
+    children: Children,
+) -> Node<'a>
+where
+    Children: 'a + AsRef<[Node<'a>]>,
+{
+    let target = cx.portal_target(target);
+    let children: &'a Children = cx.bump.alloc(children);
+    Node::portal(target, children.as_ref())
+}
+
 /// Construct an attribute for an element.
 ///
 /// # Example
@@ -1068,8 +1796,68 @@ pub fn on<'a, F>(bump: &'a Bump, event: &'a str, callback: F) -> Listener<'a>
 where
     F: Fn(&mut dyn RootRender, VdomWeak, web_sys::Event) + 'static,
 {
-    Listener {
+    on_with_options(bump, event, ListenerOptions::default(), callback)
+}
+
+/// Create an event listener with the given options.
+///
+/// # Example
+///
+/// ```no_run
+/// use dodrio::{builder::*, bumpalo::Bump, ListenerOptions};
+///
+/// let b = Bump::new();
+///
+/// let options = ListenerOptions {
+///     capture: true,
+///     ..Default::default()
+/// };
+/// let listener = on_with_options(&b, "focus", options, |root, vdom, event| {
+///     // do something when a descendant is focused...
+/// });
+/// ```
+pub fn on_with_options<'a, F>(
+    bump: &'a Bump,
+    event: &'a str,
+    options: ListenerOptions,
+    callback: F,
+) -> Listener<'a>
+where
+    F: Fn(&mut dyn RootRender, VdomWeak, web_sys::Event) + 'static,
+{
+    Listener::new(
         event,
-        callback: bump.alloc(callback),
-    }
+        ListenerCallback::Closure(bump.alloc(callback)),
+        options,
+    )
+}
+
+/// Create an event listener that invokes a plain function with the given data.
+///
+/// See `ElementBuilder::on_with` for details.
+///
+/// # Example
+///
+/// ```no_run
+/// use dodrio::{builder::*, bumpalo::Bump, RootRender, VdomWeak};
+///
+/// fn select(root: &mut dyn RootRender, vdom: VdomWeak, _event: web_sys::Event, id: u32) {
+///     // Select the item with this `id`...
+/// }
+///
+/// let b = Bump::new();
+///
+/// let listener = on_with(&b, "click", 42, select);
+/// ```
+pub fn on_with<'a, T>(
+    bump: &'a Bump,
+    event: &'a str,
+    data: T,
+    callback: fn(&mut dyn RootRender, VdomWeak, web_sys::Event, T),
+) -> Listener<'a>
+where
+    T: 'a + Copy,
+{
+    let callback = ListenerCallback::with_data(bump, data, callback);
+    Listener::new(event, callback, ListenerOptions::default())
 }
@@ -1,10 +1,12 @@
 use crate::{
+    change_list::ChangeListPersistentState,
     events::EventsRegistry,
-    node::{Node, NodeKind},
+    node::{Node, NodeKind, PortalTarget},
     render_context::RenderContext,
 };
 use bumpalo::Bump;
 use fxhash::{FxHashMap, FxHashSet};
+use std::mem;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::u32;
 use wasm_bindgen::prelude::*;
@@ -15,6 +17,19 @@ pub_unstable_internal! {
     #[derive(Debug, Default)]
     pub(crate) struct CachedSet {
         items: FxHashMap<CacheId, CacheEntry>,
+
+        // The entries that the last render garbage collected. A listener
+        // callback that was copied out of the events registry just before that
+        // render finished may live in one of them, so they are only dropped
+        // once the next render starts.
+        collected: Vec<CacheEntry>,
+
+        // The elements that portals are rendered into, by id. Virtual portal
+        // nodes reference these, so they are boxed to keep their addresses
+        // stable. A target is removed by `gc` once neither the current render
+        // nor any cached render that is kept renders into it.
+        portal_targets: FxHashMap<u32, Box<PortalTarget>>,
+        next_portal_target_id: u32,
     }
 }
 
@@ -50,6 +65,10 @@ pub(crate) struct CacheEntry {
     // Whether this entry should never be garbage collected. Typically only
     // templates are pinned.
     pinned: bool,
+
+    // The ids of the portal targets that `node` renders into, not counting
+    // those of the other cached entries in `edges`.
+    portal_targets: FxHashSet<u32>,
 }
 
 impl From<CacheId> for u32 {
@@ -66,7 +85,16 @@ impl CachedSet {
         roots
     }
 
-    pub(crate) fn gc(&mut self, registry: &mut EventsRegistry, roots: FxHashSet<CacheId>) {
+    /// Drop the cached renders that aren't reachable from `roots`, and the
+    /// portal targets that neither the kept renders nor the current render's
+    /// `portal_targets` use. Returns the ids of the removed portal targets.
+    pub(crate) fn gc(
+        &mut self,
+        registry: &mut EventsRegistry,
+        change_list: &ChangeListPersistentState,
+        roots: FxHashSet<CacheId>,
+        mut portal_targets: FxHashSet<u32>,
+    ) -> Vec<u32> {
         let mut marked = FxHashSet::default();
         marked.reserve(self.items.len());
 
@@ -83,14 +111,41 @@ impl CachedSet {
             }
         }
 
-        self.items.retain(|id, entry| {
-            let keep = entry.pinned || marked.contains(id);
-            if !keep {
-                let node: &Node = unsafe { &*entry.node };
-                registry.remove_subtree(node);
-            }
-            keep
-        });
+        let garbage: Vec<CacheId> = self
+            .items
+            .iter()
+            .filter(|(id, entry)| !entry.pinned && !marked.contains(id))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in garbage {
+            let mut entry = self.items.remove(&id).unwrap_throw();
+            let node: &Node = unsafe { &*entry.node };
+            registry.purge_subtree(node);
+            debug_assert!(
+                !change_list.tracks_lifecycles_in(&mut entry.bump),
+                "a cached render's lifecycles should all be untracked before it is dropped"
+            );
+            self.collected.push(entry);
+        }
+
+        for entry in self.items.values() {
+            portal_targets.extend(entry.portal_targets.iter().cloned());
+        }
+        let unused: Vec<u32> = self
+            .portal_targets
+            .keys()
+            .filter(|id| !portal_targets.contains(id))
+            .cloned()
+            .collect();
+        for id in &unused {
+            self.portal_targets.remove(id);
+        }
+        unused
+    }
+
+    /// Drop the entries that the last render garbage collected.
+    pub(crate) fn drop_collected(&mut self) {
+        self.collected.clear();
     }
 
     // Trace all the transitive edges to other cached entries that the given
@@ -103,7 +158,7 @@ impl CachedSet {
 
     fn trace_recursive(&self, edges: &mut FxHashSet<CacheId>, node: &Node) {
         match &node.kind {
-            NodeKind::Text(_) => return,
+            NodeKind::Text(_) | NodeKind::Empty | NodeKind::RawHtml(_) => return,
             NodeKind::Cached(c) => {
                 debug_assert!(self.items.contains_key(&c.id));
                 edges.insert(c.id);
@@ -121,6 +176,16 @@ impl CachedSet {
                     self.trace_recursive(edges, child);
                 }
             }
+            NodeKind::Fragment(f) => {
+                for child in f.children {
+                    self.trace_recursive(edges, child);
+                }
+            }
+            NodeKind::Portal(p) => {
+                for child in p.children {
+                    self.trace_recursive(edges, child);
+                }
+            }
         }
     }
 
@@ -141,9 +206,11 @@ impl CachedSet {
     {
         let set = cx.cached_set;
         let bump = Bump::new();
-        let (node, edges) = {
+        let (node, edges, after_render, portal_targets) = {
             let mut nested_cx = RenderContext::new(&bump, cx.cached_set, cx.templates);
             let node = f(&mut nested_cx);
+            let after_render = mem::take(&mut nested_cx.after_render);
+            let portal_targets = nested_cx.take_portal_targets();
             let node = bump.alloc(node);
             let edges = {
                 let set = set.borrow();
@@ -152,8 +219,11 @@ impl CachedSet {
             (
                 node as *mut Node<'_> as usize as *const Node<'static>,
                 edges,
+                after_render,
+                portal_targets,
             )
         };
+        cx.after_render.extend(after_render);
 
         let entry = CacheEntry {
             bump,
@@ -161,6 +231,7 @@ impl CachedSet {
             edges,
             template,
             pinned,
+            portal_targets,
         };
 
         let mut set = set.borrow_mut();
@@ -169,6 +240,27 @@ impl CachedSet {
         id
     }
 
+    /// Get the portal target for the given element, adding it if it isn't
+    /// rendered into already.
+    pub(crate) fn portal_target(&mut self, element: &crate::Element) -> &PortalTarget {
+        // Elements can't be hashed, so look for it among the targets in use,
+        // of which there are usually only a few.
+        let id = match self.portal_targets.values().find(|t| t.element == *element) {
+            Some(target) => target.id,
+            None => {
+                let id = self.next_portal_target_id;
+                self.next_portal_target_id = id
+                    .checked_add(1)
+                    .expect_throw("portal target ids overflowed");
+                let element = element.clone();
+                self.portal_targets
+                    .insert(id, Box::new(PortalTarget { id, element }));
+                id
+            }
+        };
+        &self.portal_targets[&id]
+    }
+
     /// Does the cached set contain a cached node with the given id?
     pub fn contains(&self, id: CacheId) -> bool {
         self.items.contains_key(&id)
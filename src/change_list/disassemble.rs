@@ -0,0 +1,300 @@
+//! A disassembler for change lists.
+//!
+//! The `InstructionEmitter` encodes change lists as raw `u32` opcodes and
+//! immediates. This module decodes them back into `Instruction`s and renders
+//! them as a human-readable listing, with cached string keys and text
+//! immediates resolved to the strings they refer to. This is useful for
+//! asserting on the exact change list that a diff produced, and for pasting
+//! into bug reports.
+
+pub use super::emitter::{DecodeError, Instruction};
+use crate::ListenerOptions;
+use fxhash::FxHashMap;
+use std::fmt;
+
+/// Decode a sequence of change list words into instructions.
+pub fn disassemble(mut words: &[u32]) -> Result<Vec<Instruction>, DecodeError> {
+    let mut instructions = vec![];
+    while !words.is_empty() {
+        let (instruction, len) = Instruction::decode(words)?;
+        instructions.push(instruction);
+        words = &words[len..];
+    }
+    Ok(instructions)
+}
+
+impl fmt::Display for Instruction {
+    /// Display this instruction's name followed by its raw immediates.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut words = vec![];
+        self.encode(&mut words);
+        write!(f, "{}", self.name())?;
+        for immediate in &words[1..] {
+            write!(f, " {}", immediate)?;
+        }
+        Ok(())
+    }
+}
+
+/// A disassembled change list.
+///
+/// Its `Display` implementation is a listing with one instruction per line,
+/// where cached strings and text are shown as quoted strings rather than keys
+/// and pointers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disassembly {
+    instructions: Vec<Instruction>,
+    lines: Vec<String>,
+}
+
+impl Disassembly {
+    /// Disassemble `words`.
+    ///
+    /// `strings` maps the keys of strings that were already cached before this
+    /// change list to their contents, and `read_text` reads the string that a
+    /// pair of text immediates refers to.
+    pub(crate) fn new(
+        words: &[u32],
+        mut strings: FxHashMap<u32, String>,
+        read_text: &dyn Fn(u32, u32) -> String,
+    ) -> Result<Disassembly, DecodeError> {
+        let instructions = disassemble(words)?;
+        let lines = instructions
+            .iter()
+            .map(|instruction| describe(instruction, &mut strings, read_text))
+            .collect();
+        Ok(Disassembly {
+            instructions,
+            lines,
+        })
+    }
+
+    /// The decoded instructions.
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// The listing's lines, one per instruction.
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+fn describe(
+    instruction: &Instruction,
+    strings: &mut FxHashMap<u32, String>,
+    read_text: &dyn Fn(u32, u32) -> String,
+) -> String {
+    let string = |key: &u32| match strings.get(key) {
+        Some(s) => format!("{:?}", s),
+        None => format!("<unknown string {}>", key),
+    };
+
+    let name = instruction.name();
+    match instruction {
+        Instruction::SetText { pointer, length }
+        | Instruction::CreateTextNode { pointer, length }
+        | Instruction::CreateRawHtml { pointer, length } => {
+            format!("{} {:?}", name, read_text(*pointer, *length))
+        }
+        Instruction::SetAttribute {
+            attribute_key,
+            value_key,
+        } => format!("{} {} {}", name, string(attribute_key), string(value_key)),
+        Instruction::RemoveAttribute { attribute_key } => {
+            format!("{} {}", name, string(attribute_key))
+        }
+        Instruction::SetPropertyBool { name_key, value } => {
+            format!("{} {} {}", name, string(name_key), *value != 0)
+        }
+        Instruction::SetPropertyNumber {
+            name_key,
+            low,
+            high,
+        } => {
+            let value = f64::from_bits(u64::from(*high) << 32 | u64::from(*low));
+            format!("{} {} {}", name, string(name_key), value)
+        }
+        Instruction::SetPropertyString {
+            name_key,
+            value_key,
+        } => format!("{} {} {}", name, string(name_key), string(value_key)),
+        Instruction::SetStyle {
+            property_key,
+            value_key,
+        } => format!("{} {} {}", name, string(property_key), string(value_key)),
+        Instruction::RemoveStyle { property_key } => {
+            format!("{} {}", name, string(property_key))
+        }
+        Instruction::RemoveProperty { name_key } => format!("{} {}", name, string(name_key)),
+        Instruction::CreateElement { tag_name_key } => format!("{} {}", name, string(tag_name_key)),
+        Instruction::CreateElementNs {
+            tag_name_key,
+            namespace_key,
+        } => format!(
+            "{} {} {}",
+            name,
+            string(tag_name_key),
+            string(namespace_key)
+        ),
+        // Listener ids change with every render, and aren't meaningful to print.
+        Instruction::NewEventListener {
+            event_key, options, ..
+        }
+        | Instruction::UpdateEventListener {
+            event_key, options, ..
+        }
+        | Instruction::RemoveEventListener { event_key, options } => {
+            let options = ListenerOptions::from_bits(*options);
+            let mut s = format!("{} {}", name, string(event_key));
+            for (flag, set) in [
+                ("capture", options.capture),
+                ("passive", options.passive),
+                ("once", options.once),
+            ] {
+                if set {
+                    s.push(' ');
+                    s.push_str(flag);
+                }
+            }
+            s
+        }
+        Instruction::SetClass { class } => format!("{} {}", name, string(class)),
+        Instruction::AddClass { class_key } | Instruction::RemoveClass { class_key } => {
+            format!("{} {}", name, string(class_key))
+        }
+        Instruction::AddCachedString {
+            pointer,
+            length,
+            key,
+        } => {
+            let s = read_text(*pointer, *length);
+            let line = format!("{} {} {:?}", name, key, s);
+            strings.insert(*key, s);
+            line
+        }
+        Instruction::DropCachedString { key } => {
+            let line = format!("{} {}", name, key);
+            strings.remove(key);
+            line
+        }
+        Instruction::SaveChildrenToTemporaries {
+            temp_base,
+            start,
+            end,
+        } => format!(
+            "{} t{}..t{} = children {}..{}",
+            name,
+            temp_base,
+            temp_base + (end - start),
+            start,
+            end
+        ),
+        Instruction::PushTemporary { temp } => format!("{} t{}", name, temp),
+        _ => instruction.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_round_trip() {
+        let instructions = vec![
+            Instruction::AddCachedString {
+                pointer: 0,
+                length: 3,
+                key: 0,
+            },
+            Instruction::CreateElement { tag_name_key: 0 },
+            Instruction::AppendChild {},
+            Instruction::SaveChildrenToTemporaries {
+                temp_base: 2,
+                start: 1,
+                end: 3,
+            },
+            Instruction::PushTemporary { temp: 3 },
+        ];
+        let mut words = vec![];
+        for instruction in &instructions {
+            instruction.encode(&mut words);
+        }
+        assert_eq!(disassemble(&words), Ok(instructions));
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(Instruction::decode(&[]), Err(DecodeError::Empty));
+        assert_eq!(
+            Instruction::decode(&[1000]),
+            Err(DecodeError::UnknownOpcode(1000))
+        );
+        assert_eq!(
+            disassemble(&[7, 3, 1]),
+            Err(DecodeError::Truncated { opcode: 3 })
+        );
+    }
+
+    #[test]
+    fn listing() {
+        let text = "divhello";
+        let read_text = |pointer: u32, length: u32| {
+            text[pointer as usize..(pointer + length) as usize].to_string()
+        };
+        let mut strings = FxHashMap::default();
+        strings.insert(7, "id".to_string());
+
+        let mut words = vec![];
+        for instruction in &[
+            Instruction::AddCachedString {
+                pointer: 0,
+                length: 3,
+                key: 8,
+            },
+            Instruction::CreateElement { tag_name_key: 8 },
+            Instruction::SetAttribute {
+                attribute_key: 7,
+                value_key: 8,
+            },
+            Instruction::CreateTextNode {
+                pointer: 3,
+                length: 5,
+            },
+            Instruction::AppendChild {},
+            Instruction::SaveChildrenToTemporaries {
+                temp_base: 0,
+                start: 1,
+                end: 3,
+            },
+            Instruction::PushTemporary { temp: 1 },
+            Instruction::DropCachedString { key: 7 },
+            Instruction::RemoveAttribute { attribute_key: 7 },
+        ] {
+            instruction.encode(&mut words);
+        }
+
+        let disassembly = Disassembly::new(&words, strings, &read_text).unwrap();
+        assert_eq!(
+            disassembly.to_string(),
+            "add_cached_string 8 \"div\"\n\
+             create_element \"div\"\n\
+             set_attribute \"id\" \"div\"\n\
+             create_text_node \"hello\"\n\
+             append_child\n\
+             save_children_to_temporaries t0..t2 = children 1..3\n\
+             push_temporary t1\n\
+             drop_cached_string 7\n\
+             remove_attribute <unknown string 7>\n"
+        );
+    }
+}
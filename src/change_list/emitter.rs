@@ -13,24 +13,91 @@
 //! inside this bump, and that the instructions themselves do not contain any
 //! padding or uninitialized memory. See the documentation for the the
 //! `Bump::each_allocated_chunk` method for details.
+//!
+//! Strings that aren't cached are encoded as a pointer and length into wasm's
+//! linear memory, which the interpreter reads from directly. On native targets
+//! pointers don't fit in a `u32`, so the strings are copied into a side buffer
+//! instead, and the "pointer" is an offset into that buffer.
 
+use crate::remote::Frame;
 use bumpalo::Bump;
+use std::fmt;
 
 #[derive(Debug)]
 pub(crate) struct InstructionEmitter {
     bump: Bump,
+    #[cfg(not(target_arch = "wasm32"))]
+    text: crate::RefCell<Vec<u8>>,
 }
 
 impl InstructionEmitter {
     /// Construct a new `InstructionEmitter` with its own bump arena.
     pub fn new() -> InstructionEmitter {
         let bump = Bump::new();
-        InstructionEmitter { bump }
+        InstructionEmitter {
+            bump,
+            #[cfg(not(target_arch = "wasm32"))]
+            text: Default::default(),
+        }
+    }
+
+    /// Get the `(pointer, length)` immediates that the interpreter uses to read
+    /// the given string.
+    #[cfg(target_arch = "wasm32")]
+    #[inline]
+    pub fn string_immediates(&self, string: &str) -> (u32, u32) {
+        (string.as_ptr() as u32, string.len() as u32)
+    }
+
+    /// Get the `(pointer, length)` immediates that the interpreter uses to read
+    /// the given string.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn string_immediates(&self, string: &str) -> (u32, u32) {
+        let mut text = self.text.borrow_mut();
+        let offset = text.len() as u32;
+        text.extend_from_slice(string.as_bytes());
+        (offset, string.len() as u32)
+    }
+
+    /// Copy the instructions emitted so far, and the text that they refer to,
+    /// into a self-contained frame.
+    pub fn frame(&mut self) -> Frame {
+        let mut words = vec![];
+        self.each_instruction_sequence(|seq| {
+            words.extend(
+                seq.chunks_exact(4)
+                    .map(|word| u32::from_ne_bytes([word[0], word[1], word[2], word[3]])),
+            );
+        });
+        self.frame_text(words)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn frame_text(&self, words: Vec<u32>) -> Frame {
+        // String immediates are already offsets into the side buffer.
+        Frame::new(words, self.text.borrow().clone())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn frame_text(&self, mut words: Vec<u32>) -> Frame {
+        // Copy each string that is pointed to into the frame, and replace the
+        // pointer with its offset in the frame.
+        let mut text = vec![];
+        crate::remote::relocate_text(&mut words, |pointer, length| {
+            let offset = text.len() as u32;
+            // Safety: the strings that we emitted pointers to are kept alive
+            // until the change list is finished.
+            let bytes =
+                unsafe { std::slice::from_raw_parts(pointer as *const u8, length as usize) };
+            text.extend_from_slice(bytes);
+            Ok(offset)
+        })
+        .expect("emitted change lists should always decode");
+        Frame::new(words, text)
     }
 
     /// Invoke the given function with each of the allocated instruction
     /// sequences that this emitter has built up.
-    #[cfg_attr(feature = "xxx-unstable-internal-use-only", allow(dead_code))]
     pub fn each_instruction_sequence<F>(&mut self, f: F)
     where
         F: FnMut(&[u8]),
@@ -47,6 +114,8 @@ impl InstructionEmitter {
     /// Reset to an empty sequence of instructions.
     pub fn reset(&mut self) {
         self.bump.reset();
+        #[cfg(not(target_arch = "wasm32"))]
+        self.text.borrow_mut().clear();
     }
 }
 
@@ -55,7 +124,7 @@ macro_rules! define_change_list_instructions {
         $( #[$attr:meta] )*
         $name:ident (
             $($immediate:ident),*
-        ) = $discriminant:expr,
+        ) = $discriminant:literal => $variant:ident,
     )* ) => {
         impl InstructionEmitter {
             $(
@@ -66,16 +135,128 @@ macro_rules! define_change_list_instructions {
                 }
             )*
         }
+
+        /// A decoded change list instruction and its immediates.
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub enum Instruction {
+            $(
+                $( #[$attr] )*
+                $variant { $( $immediate: u32 ),* },
+            )*
+        }
+
+        #[cfg_attr(not(feature = "xxx-unstable-internal-use-only"), allow(dead_code))]
+        impl Instruction {
+            /// Decode the instruction at the start of `words`.
+            ///
+            /// Returns the instruction and the number of words it was encoded
+            /// in.
+            pub fn decode(words: &[u32]) -> Result<(Instruction, usize), DecodeError> {
+                let (&opcode, immediates) = words.split_first().ok_or(DecodeError::Empty)?;
+                let mut immediates = immediates.iter().copied();
+                let mut len = 1;
+                match opcode {
+                    $(
+                        $discriminant => {
+                            $(
+                                let $immediate = immediates
+                                    .next()
+                                    .ok_or(DecodeError::Truncated { opcode })?;
+                                len += 1;
+                            )*
+                            Ok((Instruction::$variant { $( $immediate ),* }, len))
+                        }
+                    )*
+                    _ => Err(DecodeError::UnknownOpcode(opcode)),
+                }
+            }
+
+            /// Encode this instruction, appending its opcode and immediates to
+            /// `words`.
+            pub fn encode(&self, words: &mut Vec<u32>) {
+                match *self {
+                    $(
+                        Instruction::$variant { $( $immediate ),* } => {
+                            words.extend_from_slice(&[$discriminant $(, $immediate )*]);
+                        }
+                    )*
+                }
+            }
+
+            /// This instruction's opcode.
+            pub fn opcode(&self) -> u32 {
+                match self {
+                    $( Instruction::$variant { .. } => $discriminant, )*
+                }
+            }
+
+            /// This instruction's name, which is also the name of the
+            /// `InstructionEmitter` method that emits it.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $( Instruction::$variant { .. } => stringify!($name), )*
+                }
+            }
+        }
+    }
+}
+
+impl Instruction {
+    /// Get mutable references to this instruction's `(pointer, length)`
+    /// immediates for a string that isn't cached, if it has them.
+    pub(crate) fn text_immediates_mut(&mut self) -> Option<(&mut u32, &mut u32)> {
+        match self {
+            Instruction::SetText { pointer, length }
+            | Instruction::CreateTextNode { pointer, length }
+            | Instruction::CreateRawHtml { pointer, length }
+            | Instruction::AddCachedString {
+                pointer, length, ..
+            } => Some((pointer, length)),
+            _ => None,
+        }
+    }
+}
+
+/// An error that occurred while decoding a change list instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "xxx-unstable-internal-use-only"), allow(dead_code))]
+pub enum DecodeError {
+    /// There were no words left to decode.
+    Empty,
+    /// The opcode is not any known instruction's opcode.
+    UnknownOpcode(u32),
+    /// The words ended before all of the instruction's immediates.
+    Truncated {
+        /// The opcode of the truncated instruction.
+        opcode: u32,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Empty => write!(f, "no change list instruction to decode"),
+            DecodeError::UnknownOpcode(opcode) => {
+                write!(f, "unknown change list opcode: {}", opcode)
+            }
+            DecodeError::Truncated { opcode } => write!(
+                f,
+                "change list instruction with opcode {} is missing immediates",
+                opcode
+            ),
+        }
     }
 }
 
+impl std::error::Error for DecodeError {}
+
 define_change_list_instructions! {
     /// Stack: `[... TextNode] -> [... TextNode]`
     ///
     /// ```text
     /// stack.top().textContent = readString(pointer, length)
     /// ```
-    set_text(pointer, length) = 0,
+    set_text(pointer, length) = 0 => SetText,
 
     /// Stack: `[... Node] -> [...]`
     ///
@@ -86,7 +267,7 @@ define_change_list_instructions! {
     /// }
     /// node.remove()
     /// ```
-    remove_self_and_next_siblings() = 1,
+    remove_self_and_next_siblings() = 1 => RemoveSelfAndNextSiblings,
 
     /// Stack: `[... Node Node] -> [... Node]`
     ///
@@ -96,21 +277,21 @@ define_change_list_instructions! {
     /// old.replaceWith(new)
     /// stack.push(new)
     /// ```
-    replace_with() = 2,
+    replace_with() = 2 => ReplaceWith,
 
     /// Stack: `[... Node] -> [... Node]`
     ///
     /// ```text
     /// stack.top().setAttribute(getCachedString(attribute_key), getCachedString(value_key))
     /// ```
-    set_attribute(attribute_key, value_key) = 3,
+    set_attribute(attribute_key, value_key) = 3 => SetAttribute,
 
     /// Stack: `[... Node] -> [... Node]`
     ///
     /// ```text
     /// stack.top().removeAttribute(getCachedString(attribute_key))
     /// ```
-    remove_attribute(attribute_key) = 4,
+    remove_attribute(attribute_key) = 4 => RemoveAttribute,
 
     /// Stack: `[... Node] -> [... Node Node]`
     ///
@@ -119,7 +300,7 @@ define_change_list_instructions! {
     /// child = parent.childNodes[parent.childNodes.length - n - 1]
     /// stack.push(child)
     /// ```
-    push_reverse_child(n) = 5,
+    push_reverse_child(n) = 5 => PushReverseChild,
 
     /// Stack: `[... Node Node] -> [... Node Node]`
     ///
@@ -129,14 +310,14 @@ define_change_list_instructions! {
     /// child = parent.childNodes[n]
     /// stack.push(child)
     /// ```
-    pop_push_child(n) = 6,
+    pop_push_child(n) = 6 => PopPushChild,
 
     /// Stack: `[... T] -> [...]`
     ///
     /// ```text
     /// stack.pop()
     /// ```
-    pop() = 7,
+    pop() = 7 => Pop,
 
     /// Stack: `[... Node Node] -> [... Node]`
     ///
@@ -144,30 +325,30 @@ define_change_list_instructions! {
     /// child = stack.pop()
     /// stack.top().appendChild(child)
     /// ```
-    append_child() = 8,
+    append_child() = 8 => AppendChild,
 
     /// Stack: `[...] -> [... Node]`
     ///
     /// ```text
     /// stack.push(document.createTextNode(readString(pointer, length)))
     /// ```
-    create_text_node(pointer, length) = 9,
+    create_text_node(pointer, length) = 9 => CreateTextNode,
 
     /// Stack: `[...] -> [... Node]`
     ///
     /// ```text
     /// stack.push(document.createElement(getCachedString(tag_name_key))
     /// ```
-    create_element(tag_name_key) = 10,
+    create_element(tag_name_key) = 10 => CreateElement,
 
     /// Stack: `[... Node] -> [... Node]`
     ///
     /// ```text
     /// event = getCachedString(event_key)
     /// callback = createProxyToRustCallback(a, b)
-    /// stack.top().addEventListener(event, callback)
+    /// stack.top().addEventListener(event, callback, decodeOptions(options))
     /// ```
-    new_event_listener(event_key, a, b) = 11,
+    new_event_listener(event_key, a, b, options) = 11 => NewEventListener,
 
     /// Stack: `[... Node] -> [... Node]`
     ///
@@ -176,28 +357,30 @@ define_change_list_instructions! {
     /// new_callback = createProxyToRustCallback(a, b);
     /// stack.top().updateEventlistener(new_callback)
     /// ```
-    update_event_listener(event_key, a, b) = 12,
+    ///
+    /// The options are always the same as the listener's current options.
+    update_event_listener(event_key, a, b, options) = 12 => UpdateEventListener,
 
     /// Stack: `[... Node] -> [... Node]`
     ///
     /// ```text
-    /// stack.top().removeEventListener(getCachedString(event_key));
+    /// stack.top().removeEventListener(getCachedString(event_key), decodeOptions(options));
     /// ```
-    remove_event_listener(event_key) = 13,
+    remove_event_listener(event_key, options) = 13 => RemoveEventListener,
 
     /// Stack: `[...] -> [...]`
     ///
     /// ```text
     /// addCachedString(readString(pointer, length), key);
     /// ```
-    add_cached_string(pointer, length, key) = 14,
+    add_cached_string(pointer, length, key) = 14 => AddCachedString,
 
     /// Stack: `[...] -> [...]`
     ///
     /// ```text
     /// dropCachedString(key);
     /// ```
-    drop_cached_string(key) = 15,
+    drop_cached_string(key) = 15 => DropCachedString,
 
     /// Stack: `[...] -> [... Node]`
     ///
@@ -206,7 +389,7 @@ define_change_list_instructions! {
     /// namespace = getCachedString(tag_name_key)
     /// stack.push(document.createElementNS(tag_name, namespace))
     /// ```
-    create_element_ns(tag_name_key, namespace_key) = 16,
+    create_element_ns(tag_name_key, namespace_key) = 16 => CreateElementNs,
 
     /// Stack: `[...] -> [...]`
     ///
@@ -218,7 +401,7 @@ define_change_list_instructions! {
     ///     temporaries[temp] = children[i]
     ///     temp += 1
     /// ```
-    save_children_to_temporaries(temp_base, start, end) = 17,
+    save_children_to_temporaries(temp_base, start, end) = 17 => SaveChildrenToTemporaries,
 
     /// Stack: `[... Node] -> [... Node Node]`
     ///
@@ -227,14 +410,14 @@ define_change_list_instructions! {
     /// child = parent.childNodes[n]
     /// stack.push(child)
     /// ```
-    push_child(n) = 18,
+    push_child(n) = 18 => PushChild,
 
     /// Stack: `[...] -> [... Node]`
     ///
     /// ```text
     /// stack.push(temporaries[temp])
     /// ```
-    push_temporary(temp) = 19,
+    push_temporary(temp) = 19 => PushTemporary,
 
     /// Stack: `[... Node Node] -> [... Node]`
     ///
@@ -244,7 +427,7 @@ define_change_list_instructions! {
     /// after.insertBefore(before)
     /// stack.push(before)
     /// ```
-    insert_before() = 20,
+    insert_before() = 20 => InsertBefore,
 
     /// Stack: `[... Node Node] -> [... Node Node]`
     ///
@@ -254,7 +437,7 @@ define_change_list_instructions! {
     /// child = parent.childNodes[parent.childNodes.length - n - 1]
     /// stack.push(child)
     /// ```
-    pop_push_reverse_child(n) = 21,
+    pop_push_reverse_child(n) = 21 => PopPushReverseChild,
 
     /// Stack: `[... Node] -> [... Node]`
     ///
@@ -263,7 +446,7 @@ define_change_list_instructions! {
     /// child = parent.childNodes[n]
     /// child.remove()
     /// ```
-    remove_child(n) = 22,
+    remove_child(n) = 22 => RemoveChild,
 
     /// Stack: `[... Node] -> [... Node]`
     ///
@@ -272,7 +455,7 @@ define_change_list_instructions! {
     /// node = stack.top()
     /// node.className = class
     /// ```
-    set_class(class) = 23,
+    set_class(class) = 23 => SetClass,
 
     /// Stack: `[... Node] -> [... Node]`
     ///
@@ -280,7 +463,7 @@ define_change_list_instructions! {
     /// template = stack.top()
     /// saveTemplate(id, template)
     /// ```
-    save_template(id) = 24,
+    save_template(id) = 24 => SaveTemplate,
 
     /// Stack: `[...] -> [... Node]`
     ///
@@ -288,5 +471,125 @@ define_change_list_instructions! {
     /// template = getTemplate(id)
     /// stack.push(template.cloneNode(true))
     /// ```
-    push_template(id) = 25,
+    push_template(id) = 25 => PushTemplate,
+
+    /// Stack: `[...] -> [... Node]`
+    ///
+    /// ```text
+    /// stack.push(document.createComment(""))
+    /// ```
+    create_comment() = 26 => CreateComment,
+
+    /// Stack: `[...] -> [... Node]`
+    ///
+    /// ```text
+    /// template = document.createElement("template")
+    /// template.innerHTML = readString(pointer, length)
+    /// stack.push(template.content.firstChild)
+    /// ```
+    create_raw_html(pointer, length) = 27 => CreateRawHtml,
+
+    /// Stack: `[... Node] -> [... Node]`
+    ///
+    /// ```text
+    /// node = stack.top()
+    /// node[getCachedString(name_key)] = value != 0
+    /// ```
+    set_property_bool(name_key, value) = 28 => SetPropertyBool,
+
+    /// Stack: `[... Node] -> [... Node]`
+    ///
+    /// ```text
+    /// node = stack.top()
+    /// node[getCachedString(name_key)] = f64::from_bits(high << 32 | low)
+    /// ```
+    set_property_number(name_key, low, high) = 29 => SetPropertyNumber,
+
+    /// Stack: `[... Node] -> [... Node]`
+    ///
+    /// ```text
+    /// node = stack.top()
+    /// node[getCachedString(name_key)] = getCachedString(value_key)
+    /// ```
+    set_property_string(name_key, value_key) = 30 => SetPropertyString,
+
+    /// Stack: `[... Node] -> [... Node]`
+    ///
+    /// ```text
+    /// node = stack.top()
+    /// node[getCachedString(name_key)] = null
+    /// ```
+    remove_property(name_key) = 31 => RemoveProperty,
+
+    /// Stack: `[... HTMLElement] -> [... HTMLElement]`
+    ///
+    /// ```text
+    /// element = stack.top()
+    /// element.style.setProperty(getCachedString(property_key), getCachedString(value_key))
+    /// ```
+    set_style(property_key, value_key) = 32 => SetStyle,
+
+    /// Stack: `[... HTMLElement] -> [... HTMLElement]`
+    ///
+    /// ```text
+    /// element = stack.top()
+    /// element.style.removeProperty(getCachedString(property_key))
+    /// ```
+    remove_style(property_key) = 33 => RemoveStyle,
+
+    /// Stack: `[... Element] -> [... Element]`
+    ///
+    /// ```text
+    /// element = stack.top()
+    /// element.classList.add(getCachedString(class_key))
+    /// ```
+    add_class(class_key) = 34 => AddClass,
+
+    /// Stack: `[... Element] -> [... Element]`
+    ///
+    /// ```text
+    /// element = stack.top()
+    /// element.classList.remove(getCachedString(class_key))
+    /// ```
+    remove_class(class_key) = 35 => RemoveClass,
+
+    /// Stack: `[... Element] -> [... Element]`
+    ///
+    /// ```text
+    /// savedNodes.push(stack.top())
+    /// ```
+    set_node_ref() = 36 => SetNodeRef,
+
+    /// Stack: `[... Element] -> [... Element]`
+    ///
+    /// ```text
+    /// savedNodes.push(stack.top())
+    /// ```
+    track_lifecycle() = 37 => TrackLifecycle,
+
+    /// Stack: `[...] -> [... Portal]`
+    ///
+    /// ```text
+    /// const start = document.createComment("")
+    /// const end = document.createComment("")
+    /// getPortalTarget(id).append(start, end)
+    /// portals.set(key, new PortalRange(start, end))
+    /// stack.push(portals.get(key))
+    /// ```
+    create_portal(id, key) = 38 => CreatePortal,
+
+    /// Stack: `[...] -> [... Portal]`
+    ///
+    /// ```text
+    /// stack.push(portals.get(key))
+    /// ```
+    push_portal(key) = 39 => PushPortal,
+
+    /// Stack: `[...] -> [...]`
+    ///
+    /// ```text
+    /// portals.get(key).remove()
+    /// portals.delete(key)
+    /// ```
+    remove_portal(key) = 40 => RemovePortal,
 }
@@ -2,54 +2,232 @@ pub(crate) mod emitter;
 pub(crate) mod strings;
 pub(crate) mod traversal;
 
-// Note: has to be `pub` because of `wasm-bindgen` visibility restrictions.
-pub mod js;
+#[cfg(feature = "xxx-unstable-internal-use-only")]
+pub mod disassemble;
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "xxx-unstable-internal-use-only", not(target_arch = "wasm32")))] {
+        pub mod native;
+        pub(crate) use self::native as interpreter;
+    } else {
+        // Note: has to be `pub` because of `wasm-bindgen` visibility restrictions.
+        pub mod js;
+        pub(crate) use self::js as interpreter;
+    }
+}
 
 use self::emitter::InstructionEmitter;
 use self::strings::{StringKey, StringsCache};
 use self::traversal::{MoveTo, Traversal};
-use crate::{cached_set::CacheId, Listener};
-use fxhash::FxHashSet;
+use crate::remote::Transport;
+use crate::{
+    cached_set::CacheId,
+    node::{Lifecycle, PortalNode},
+    node_ref::NodeRefId,
+    Listener, ListenerOptions, PropertyValue, RootRender, VdomWeak,
+};
+use bumpalo::Bump;
+use fxhash::{FxHashMap, FxHashSet};
+use std::fmt;
+use std::mem;
 
 #[derive(Debug)]
 pub(crate) struct ChangeListPersistentState {
     strings: StringsCache,
     emitter: InstructionEmitter,
     traversal: Traversal,
-    interpreter: js::ChangeListInterpreter,
+    target: ChangeListTarget,
     templates: FxHashSet<CacheId>,
+    // What to do with each of the elements that the interpreter saves with
+    // `set_node_ref` and `track_lifecycle` instructions, in order.
+    saved_nodes: Vec<SavedNode>,
+    // The physical elements of mounted elements with lifecycle callbacks, by
+    // lifecycle id.
+    mounted: FxHashMap<u64, Mounted>,
+    // The lifecycle callbacks to invoke once the changes are applied.
+    lifecycle_calls: Vec<LifecycleCall>,
+    // The ids of the portal targets that the interpreter has been given.
+    portal_targets: FxHashSet<u32>,
+    // The keys of the ranges in the portal targets that hold each rendered
+    // portal's children, by portal id. There is more than one when the portal is part
+    // of a cached subtree that is rendered in multiple places.
+    portals: FxHashMap<u64, Vec<u32>>,
+    next_portal_key: u32,
+    #[cfg(feature = "xxx-unstable-internal-use-only")]
+    recording: bool,
+    #[cfg(feature = "xxx-unstable-internal-use-only")]
+    disassembly: Option<disassemble::Disassembly>,
 }
 
 pub(crate) struct ChangeListBuilder<'a> {
     state: &'a mut ChangeListPersistentState,
     next_temporary: u32,
     forcing_new_listeners: bool,
+    building_template: bool,
+    diffing_template: bool,
+}
+
+/// An element saved by the interpreter, and what it is for.
+#[derive(Debug)]
+enum SavedNode {
+    /// Fill in this node ref with the element.
+    NodeRef(NodeRefId),
+    /// Track the element for this lifecycle, and invoke its `on_mount` callback
+    /// if the element was `mounted` by this change list.
+    Lifecycle {
+        lifecycle: &'static Lifecycle<'static>,
+        mounted: bool,
+    },
+}
+
+/// The physical elements that a lifecycle is tracking. There is more than one
+/// when the lifecycle is part of a cached subtree that is rendered in multiple
+/// places.
+#[derive(Debug)]
+struct Mounted {
+    lifecycle: &'static Lifecycle<'static>,
+    elements: Vec<crate::Element>,
+}
+
+/// A lifecycle callback that is waiting for its change list to be applied.
+#[derive(Debug)]
+pub(crate) enum LifecycleCall {
+    Mount(&'static Lifecycle<'static>, crate::Element),
+    Unmount(&'static Lifecycle<'static>, crate::Element),
+}
+
+impl LifecycleCall {
+    pub(crate) fn invoke(self, component: &mut dyn RootRender, vdom: VdomWeak) {
+        let (callback, element) = match self {
+            LifecycleCall::Mount(lifecycle, element) => (lifecycle.on_mount, element),
+            LifecycleCall::Unmount(lifecycle, element) => (lifecycle.on_unmount, element),
+        };
+        if let Some(callback) = callback {
+            callback(component, vdom, element);
+        }
+    }
+}
+
+// Extend a lifecycle's lifetime to `'static`, so that it can wait in the
+// persistent state until its change list is applied, and then until its element
+// is unmounted. `ChangeListPersistentState::tracks_lifecycles_in` checks that
+// its bump arena isn't reset or dropped in the meantime.
+unsafe fn extend_lifecycle_lifetime<'a>(
+    lifecycle: &'a Lifecycle<'a>,
+) -> &'static Lifecycle<'static> {
+    mem::transmute(lifecycle)
+}
+
+// Track another mounted element for the given lifecycle.
+fn track(
+    mounted: &mut FxHashMap<u64, Mounted>,
+    lifecycle: &'static Lifecycle<'static>,
+    element: crate::Element,
+) {
+    mounted
+        .entry(lifecycle.id)
+        .or_insert_with(|| Mounted {
+            lifecycle,
+            elements: vec![],
+        })
+        .elements
+        .push(element);
+}
+
+// Stop tracking one of the given lifecycle's mounted elements, and return it.
+// When the same cached subtree is rendered in several places, their elements
+// are indistinguishable here, so any one of them is returned.
+fn untrack(mounted: &mut FxHashMap<u64, Mounted>, lifecycle: &Lifecycle) -> Option<crate::Element> {
+    let entry = mounted.get_mut(&lifecycle.id)?;
+    let element = entry.elements.pop();
+    if entry.elements.is_empty() {
+        mounted.remove(&lifecycle.id);
+    }
+    element
+}
+
+// Stop tracking one of the given portal's ranges, and return its key. Like
+// with `untrack`, a cached portal's ranges are indistinguishable here.
+fn untrack_portal(portals: &mut FxHashMap<u64, Vec<u32>>, id: u64) -> Option<u32> {
+    let keys = portals.get_mut(&id)?;
+    let key = keys.pop();
+    if keys.is_empty() {
+        portals.remove(&id);
+    }
+    key
+}
+
+/// Where finished change lists go.
+// There is only one of these per virtual DOM, so its size doesn't matter.
+#[allow(clippy::large_enum_variant)]
+enum ChangeListTarget {
+    /// Apply them to the container with the local interpreter.
+    Interpreter(interpreter::ChangeListInterpreter),
+    /// Send them as frames to a remote `Applier`.
+    Transport(Box<dyn Transport>),
+}
+
+impl fmt::Debug for ChangeListTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChangeListTarget::Interpreter(interpreter) => {
+                f.debug_tuple("Interpreter").field(interpreter).finish()
+            }
+            ChangeListTarget::Transport(_) => f.debug_tuple("Transport").field(&"..").finish(),
+        }
+    }
 }
 
 impl Drop for ChangeListPersistentState {
     fn drop(&mut self) {
-        self.interpreter.unmount();
+        if let ChangeListTarget::Interpreter(ref mut interpreter) = self.target {
+            interpreter.unmount();
+        }
     }
 }
 
 impl ChangeListPersistentState {
-    pub(crate) fn new(container: &crate::Element) -> ChangeListPersistentState {
+    pub(crate) fn new(
+        container: &crate::Element,
+        delegate_events: bool,
+    ) -> ChangeListPersistentState {
+        let interpreter = interpreter::ChangeListInterpreter::new(container, delegate_events);
+        Self::with_target(ChangeListTarget::Interpreter(interpreter))
+    }
+
+    pub(crate) fn with_transport(transport: Box<dyn Transport>) -> ChangeListPersistentState {
+        Self::with_target(ChangeListTarget::Transport(transport))
+    }
+
+    fn with_target(target: ChangeListTarget) -> ChangeListPersistentState {
         let strings = StringsCache::new();
         let emitter = InstructionEmitter::new();
         let traversal = Traversal::new();
-        let interpreter = js::ChangeListInterpreter::new(container);
         let templates = Default::default();
         ChangeListPersistentState {
             strings,
             emitter,
             traversal,
-            interpreter,
+            target,
             templates,
+            saved_nodes: vec![],
+            mounted: Default::default(),
+            lifecycle_calls: vec![],
+            portal_targets: Default::default(),
+            portals: Default::default(),
+            next_portal_key: 0,
+            #[cfg(feature = "xxx-unstable-internal-use-only")]
+            recording: false,
+            #[cfg(feature = "xxx-unstable-internal-use-only")]
+            disassembly: None,
         }
     }
 
     pub(crate) fn init_events_trampoline(&mut self, trampoline: &crate::EventsTrampoline) {
-        self.interpreter.init_events_trampoline(trampoline);
+        // Remote appliers have their own trampolines.
+        if let ChangeListTarget::Interpreter(ref mut interpreter) = self.target {
+            interpreter.init_events_trampoline(trampoline);
+        }
     }
 
     pub(crate) fn builder<'a>(&'a mut self) -> ChangeListBuilder<'a> {
@@ -57,37 +235,198 @@ impl ChangeListPersistentState {
             state: self,
             next_temporary: 0,
             forcing_new_listeners: false,
+            building_template: false,
+            diffing_template: false,
         }
     }
+
+    /// Take the lifecycle callbacks that are waiting to be invoked now that
+    /// the last change list is applied.
+    pub(crate) fn take_lifecycle_calls(&mut self) -> Vec<LifecycleCall> {
+        mem::take(&mut self.lifecycle_calls)
+    }
+
+    /// Stop tracking every mounted element, and take the `on_unmount`
+    /// callbacks to invoke for them, in no particular order. This is for when
+    /// the whole virtual DOM is going away.
+    ///
+    /// The last change list must already be applied.
+    pub(crate) fn unmount_all(&mut self) -> Vec<LifecycleCall> {
+        debug_assert!(self.saved_nodes.is_empty());
+        debug_assert!(self.lifecycle_calls.is_empty());
+        let mut calls = vec![];
+        for (_, mounted) in self.mounted.drain() {
+            let lifecycle = mounted.lifecycle;
+            if lifecycle.on_unmount.is_some() {
+                calls.extend(
+                    mounted
+                        .elements
+                        .into_iter()
+                        .map(|element| LifecycleCall::Unmount(lifecycle, element)),
+                );
+            }
+        }
+        calls
+    }
+
+    /// Forget a portal target that is no longer rendered into, and remove the
+    /// delegated event listeners that were added to it.
+    pub(crate) fn remove_portal_target(&mut self, id: u32) {
+        if let ChangeListTarget::Interpreter(ref mut interpreter) = self.target {
+            if self.portal_targets.remove(&id) {
+                interpreter.remove_portal_target(id);
+            }
+        }
+    }
+
+    /// Is any lifecycle that is tracked, or waiting to be, allocated in
+    /// `bump`?
+    ///
+    /// Tracked lifecycles' lifetimes are extended to `'static`, so this is for
+    /// asserting that a bump arena isn't reset or dropped while it still holds
+    /// one of them. Their callbacks are allocated alongside them.
+    pub(crate) fn tracks_lifecycles_in(&self, bump: &mut Bump) -> bool {
+        let mut chunks = vec![];
+        // Only the chunks' addresses are used, not their contents.
+        unsafe {
+            bump.each_allocated_chunk(|chunk| chunks.push(chunk.as_ptr_range()));
+        }
+        let in_bump = |lifecycle: &Lifecycle| {
+            let address = lifecycle as *const Lifecycle as *const u8;
+            chunks.iter().any(|chunk| chunk.contains(&address))
+        };
+
+        self.mounted.values().any(|m| in_bump(m.lifecycle))
+            || self.lifecycle_calls.iter().any(|call| match call {
+                LifecycleCall::Mount(lifecycle, _) | LifecycleCall::Unmount(lifecycle, _) => {
+                    in_bump(lifecycle)
+                }
+            })
+            || self.saved_nodes.iter().any(|saved| match saved {
+                SavedNode::Lifecycle { lifecycle, .. } => in_bump(lifecycle),
+                SavedNode::NodeRef(_) => false,
+            })
+    }
+
+    /// Record a disassembly of the next change list that is finished.
+    #[cfg(feature = "xxx-unstable-internal-use-only")]
+    pub(crate) fn record_disassembly(&mut self) {
+        self.recording = true;
+    }
+
+    /// Take the recorded disassembly, if any, and stop recording.
+    #[cfg(feature = "xxx-unstable-internal-use-only")]
+    pub(crate) fn take_disassembly(&mut self) -> Option<disassemble::Disassembly> {
+        self.recording = false;
+        self.disassembly.take()
+    }
 }
 
-cfg_if::cfg_if! {
-    if #[cfg(all(feature = "xxx-unstable-internal-use-only", not(target_arch = "wasm32")))] {
-        impl ChangeListBuilder<'_> {
-            pub(crate) fn finish(self) {
-                self.state.strings.drop_unused_strings(&self.state.emitter);
+impl ChangeListBuilder<'_> {
+    /// Emit the final instructions for this change list, and disassemble it if
+    /// we are recording.
+    fn finish_instructions(&mut self) {
+        #[cfg(feature = "xxx-unstable-internal-use-only")]
+        let strings = if self.state.recording {
+            Some(self.state.strings.cached_strings())
+        } else {
+            None
+        };
+
+        self.state.strings.drop_unused_strings(&self.state.emitter);
 
-                // Nothing to actually apply the changes to.
+        #[cfg(feature = "xxx-unstable-internal-use-only")]
+        {
+            if let Some(strings) = strings {
+                self.state.disassembly = Some(self.disassemble(strings));
+            }
+        }
+    }
 
-                self.state.emitter.reset();
-                self.state.traversal.reset();
+    #[cfg(feature = "xxx-unstable-internal-use-only")]
+    fn disassemble(&mut self, strings: fxhash::FxHashMap<u32, String>) -> disassemble::Disassembly {
+        let frame = self.state.emitter.frame();
+        let read_text = |offset, length| {
+            frame
+                .string(offset, length)
+                .expect("emitted change lists should only refer to valid text")
+                .to_string()
+        };
+        disassemble::Disassembly::new(frame.words(), strings, &read_text)
+            .expect("emitted change lists should always decode")
+    }
+
+    pub(crate) fn finish(mut self) {
+        self.finish_instructions();
+
+        let state = &mut *self.state;
+        match state.target {
+            ChangeListTarget::Interpreter(ref mut interpreter) => {
+                let elements =
+                    apply_changes(interpreter, &mut state.emitter, state.saved_nodes.len());
+                for (saved, element) in state.saved_nodes.drain(..).zip(elements) {
+                    match saved {
+                        SavedNode::NodeRef(node_ref) => node_ref.set(Some(element)),
+                        SavedNode::Lifecycle { lifecycle, mounted } => {
+                            track(&mut state.mounted, lifecycle, element.clone());
+                            if mounted && lifecycle.on_mount.is_some() {
+                                state
+                                    .lifecycle_calls
+                                    .push(LifecycleCall::Mount(lifecycle, element));
+                            }
+                        }
+                    }
+                }
+            }
+            ChangeListTarget::Transport(ref mut transport) => {
+                let frame = state.emitter.frame();
+                if !frame.is_empty() {
+                    transport.send_frame(frame);
+                }
             }
         }
+
+        state.emitter.reset();
+        state.traversal.reset();
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "xxx-unstable-internal-use-only", not(target_arch = "wasm32")))] {
+        // Apply the changes, and return the `saved` elements.
+        fn apply_changes(
+            interpreter: &mut interpreter::ChangeListInterpreter,
+            emitter: &mut InstructionEmitter,
+            saved: usize,
+        ) -> Vec<crate::Element> {
+            // Apply the changes to the in-memory DOM.
+            interpreter.apply_frame(&emitter.frame());
+
+            let elements = interpreter.take_saved_nodes();
+            debug_assert_eq!(elements.len(), saved);
+            elements
+        }
     } else {
-        impl ChangeListBuilder<'_> {
-            pub(crate) fn finish(self) {
-                self.state.strings.drop_unused_strings(&self.state.emitter);
-
-                // Apply the changes.
-                let interpreter = &self.state.interpreter;
-                self.state.emitter.each_instruction_sequence(|seq| {
-                    interpreter.add_change_list_range(seq.as_ptr() as usize, seq.len());
-                });
-                interpreter.apply_changes(wasm_bindgen::memory());
-
-                self.state.emitter.reset();
-                self.state.traversal.reset();
+        // Apply the changes, and return the `saved` elements.
+        fn apply_changes(
+            interpreter: &mut interpreter::ChangeListInterpreter,
+            emitter: &mut InstructionEmitter,
+            saved: usize,
+        ) -> Vec<crate::Element> {
+            use wasm_bindgen::JsCast;
+
+            // Apply the changes.
+            emitter.each_instruction_sequence(|seq| {
+                interpreter.add_change_list_range(seq.as_ptr() as usize, seq.len());
+            });
+            interpreter.apply_changes(wasm_bindgen::memory());
+
+            if saved == 0 {
+                return vec![];
             }
+            let elements = interpreter.take_saved_nodes();
+            debug_assert_eq!(elements.length() as usize, saved);
+            elements.iter().map(|e| e.unchecked_into()).collect()
         }
     }
 }
@@ -195,6 +534,83 @@ impl ChangeListBuilder<'_> {
         self.state.emitter.push_temporary(temp);
     }
 
+    /// Append a pair of markers to the target of a newly rendered portal, and
+    /// push the range between them, for the portal's children to be appended
+    /// to.
+    ///
+    /// Stack: `[...] -> [... range]`
+    ///
+    /// Remote appliers have no way to get at the target element, so if the
+    /// changes are applied remotely, nothing is emitted and this returns
+    /// `false`. Only the portal's placeholder is rendered then.
+    pub fn push_new_portal(&mut self, portal: &PortalNode) -> bool {
+        debug_assert!(self.traversal_is_committed());
+        let target = portal.target;
+        match self.state.target {
+            ChangeListTarget::Interpreter(ref mut interpreter) => {
+                if self.state.portal_targets.insert(target.id) {
+                    interpreter.add_portal_target(target.id, &target.element);
+                }
+            }
+            ChangeListTarget::Transport(_) => {
+                warn!("portals aren't rendered by a `Vdom` whose changes are applied remotely");
+                return false;
+            }
+        }
+
+        let key = self.state.next_portal_key;
+        self.state.next_portal_key = key.wrapping_add(1);
+        self.state.portals.entry(portal.id).or_default().push(key);
+
+        // The markers let the target have other contents, and other portals,
+        // without affecting the indices of this portal's children.
+        debug!("emit: create_portal({}, {})", target.id, key);
+        self.state.emitter.create_portal(target.id, key);
+        true
+    }
+
+    /// Push the range of a rendered portal's children in its target, which is
+    /// now rendered as `new`.
+    ///
+    /// Stack: `[...] -> [... range]`
+    ///
+    /// Returns `false`, without emitting anything, if `old` has no range
+    /// because it wasn't rendered by `push_new_portal`.
+    pub fn push_portal(&mut self, old: &PortalNode, new: &PortalNode) -> bool {
+        debug_assert!(self.traversal_is_committed());
+        let key = if old.id == new.id {
+            match self.state.portals.get(&old.id).and_then(|keys| keys.last()) {
+                Some(key) => *key,
+                None => return false,
+            }
+        } else {
+            let key = match untrack_portal(&mut self.state.portals, old.id) {
+                Some(key) => key,
+                None => return false,
+            };
+            self.state.portals.entry(new.id).or_default().push(key);
+            key
+        };
+        debug!("emit: push_portal({})", key);
+        self.state.emitter.push_portal(key);
+        true
+    }
+
+    /// Remove the children of a rendered portal, along with their markers,
+    /// from the portal's target. This doesn't use the stack.
+    pub fn remove_portal(&mut self, portal: &PortalNode) {
+        if let Some(key) = untrack_portal(&mut self.state.portals, portal.id) {
+            debug!("emit: remove_portal({})", key);
+            self.state.emitter.remove_portal(key);
+        }
+    }
+
+    pub fn pop(&self) {
+        debug_assert!(self.traversal_is_committed());
+        debug!("emit: pop()");
+        self.state.emitter.pop();
+    }
+
     pub fn remove_child(&self, child: usize) {
         debug_assert!(self.traversal_is_committed());
         debug!("emit: remove_child({})", child);
@@ -216,9 +632,8 @@ impl ChangeListBuilder<'_> {
     pub fn set_text(&self, text: &str) {
         debug_assert!(self.traversal_is_committed());
         debug!("emit: set_text({:?})", text);
-        self.state
-            .emitter
-            .set_text(text.as_ptr() as u32, text.len() as u32);
+        let (pointer, length) = self.state.emitter.string_immediates(text);
+        self.state.emitter.set_text(pointer, length);
     }
 
     pub fn remove_self_and_next_siblings(&self) {
@@ -256,6 +671,168 @@ impl ChangeListBuilder<'_> {
         self.state.emitter.remove_attribute(name_id.into());
     }
 
+    pub fn add_class(&mut self, class: &str) {
+        debug_assert!(self.traversal_is_committed());
+        debug!("emit: add_class({:?})", class);
+        let class_id = self.ensure_string(class);
+        self.state.emitter.add_class(class_id.into());
+    }
+
+    pub fn remove_class(&mut self, class: &str) {
+        debug_assert!(self.traversal_is_committed());
+        debug!("emit: remove_class({:?})", class);
+        let class_id = self.ensure_string(class);
+        self.state.emitter.remove_class(class_id.into());
+    }
+
+    pub fn set_node_ref(&mut self, node_ref: NodeRefId) {
+        debug_assert!(self.traversal_is_committed());
+        // Remote appliers have no elements to hand back, so node refs stay
+        // empty. This is documented on `Vdom::with_transport`.
+        if let ChangeListTarget::Transport(_) = self.state.target {
+            return;
+        }
+        debug!("emit: set_node_ref({:?})", node_ref);
+        self.state.saved_nodes.push(SavedNode::NodeRef(node_ref));
+        self.state.emitter.set_node_ref();
+    }
+
+    /// Track the element on top of the stack for the given lifecycle, and
+    /// invoke its `on_mount` callback if the element was just `mounted`.
+    ///
+    /// # Unsafety
+    ///
+    /// The lifecycle's lifetime is extended to `'static`, and it is the
+    /// caller's responsibility to invoke the resulting lifecycle calls before
+    /// the lifecycle is dropped.
+    pub unsafe fn track_lifecycle(&mut self, lifecycle: &Lifecycle, mounted: bool) {
+        debug_assert!(self.traversal_is_committed());
+        // Remote appliers have no elements to hand back, so lifecycles are
+        // never tracked. This is documented on `Vdom::with_transport`.
+        if let ChangeListTarget::Transport(_) = self.state.target {
+            return;
+        }
+        debug!("emit: track_lifecycle({:?})", lifecycle);
+        let lifecycle = extend_lifecycle_lifetime(lifecycle);
+        self.state
+            .saved_nodes
+            .push(SavedNode::Lifecycle { lifecycle, mounted });
+        self.state.emitter.track_lifecycle();
+    }
+
+    /// Like `track_lifecycle`, but for a mounted element that is already at
+    /// hand.
+    ///
+    /// # Unsafety
+    ///
+    /// See `track_lifecycle`.
+    // Only hydration has elements at hand, and the in-memory DOM is never
+    // hydrated.
+    #[cfg_attr(
+        all(
+            feature = "xxx-unstable-internal-use-only",
+            not(target_arch = "wasm32")
+        ),
+        allow(dead_code)
+    )]
+    pub unsafe fn track_lifecycle_element(
+        &mut self,
+        lifecycle: &Lifecycle,
+        element: crate::Element,
+    ) {
+        let lifecycle = extend_lifecycle_lifetime(lifecycle);
+        track(&mut self.state.mounted, lifecycle, element.clone());
+        if lifecycle.on_mount.is_some() {
+            self.state
+                .lifecycle_calls
+                .push(LifecycleCall::Mount(lifecycle, element));
+        }
+    }
+
+    /// The element tracked for the `old` lifecycle is now rendered with the
+    /// `new` lifecycle instead.
+    pub fn retrack_lifecycle(&mut self, old: &Lifecycle, new: &Lifecycle) {
+        if old.id == new.id {
+            return;
+        }
+        if let Some(element) = untrack(&mut self.state.mounted, old) {
+            // The new lifecycle outlives this change list, just like in
+            // `track_lifecycle`.
+            let new = unsafe { extend_lifecycle_lifetime(new) };
+            track(&mut self.state.mounted, new, element);
+        }
+    }
+
+    /// Stop tracking the element for the given lifecycle, and invoke its
+    /// `on_unmount` callback if the element is being `unmounted`.
+    ///
+    /// # Unsafety
+    ///
+    /// See `track_lifecycle`.
+    pub unsafe fn untrack_lifecycle(&mut self, lifecycle: &Lifecycle, unmounted: bool) {
+        let element = match untrack(&mut self.state.mounted, lifecycle) {
+            Some(element) => element,
+            None => return,
+        };
+        if unmounted && lifecycle.on_unmount.is_some() {
+            let lifecycle = extend_lifecycle_lifetime(lifecycle);
+            self.state
+                .lifecycle_calls
+                .push(LifecycleCall::Unmount(lifecycle, element));
+        }
+    }
+
+    pub fn set_style(&mut self, property: &str, value: &str) {
+        debug_assert!(self.traversal_is_committed());
+        debug!("emit: set_style({:?}, {:?})", property, value);
+        let property_id = self.ensure_string(property);
+        let value_id = self.ensure_string(value);
+        self.state
+            .emitter
+            .set_style(property_id.into(), value_id.into());
+    }
+
+    pub fn remove_style(&mut self, property: &str) {
+        debug_assert!(self.traversal_is_committed());
+        debug!("emit: remove_style({:?})", property);
+        let property_id = self.ensure_string(property);
+        self.state.emitter.remove_style(property_id.into());
+    }
+
+    pub fn set_property(&mut self, name: &str, value: PropertyValue) {
+        debug_assert!(self.traversal_is_committed());
+        debug!("emit: set_property({:?}, {:?})", name, value);
+        let name_id = self.ensure_string(name);
+        match value {
+            PropertyValue::Bool(b) => {
+                self.state
+                    .emitter
+                    .set_property_bool(name_id.into(), b as u32);
+            }
+            PropertyValue::Number(n) => {
+                let bits = n.to_bits();
+                self.state.emitter.set_property_number(
+                    name_id.into(),
+                    bits as u32,
+                    (bits >> 32) as u32,
+                );
+            }
+            PropertyValue::String(s) => {
+                let value_id = self.ensure_string(s);
+                self.state
+                    .emitter
+                    .set_property_string(name_id.into(), value_id.into());
+            }
+        }
+    }
+
+    pub fn remove_property(&mut self, name: &str) {
+        debug_assert!(self.traversal_is_committed());
+        debug!("emit: remove_property({:?})", name);
+        let name_id = self.ensure_string(name);
+        self.state.emitter.remove_property(name_id.into());
+    }
+
     pub fn append_child(&self) {
         debug_assert!(self.traversal_is_committed());
         debug!("emit: append_child()");
@@ -265,9 +842,21 @@ impl ChangeListBuilder<'_> {
     pub fn create_text_node(&self, text: &str) {
         debug_assert!(self.traversal_is_committed());
         debug!("emit: create_text_node({:?})", text);
-        self.state
-            .emitter
-            .create_text_node(text.as_ptr() as u32, text.len() as u32);
+        let (pointer, length) = self.state.emitter.string_immediates(text);
+        self.state.emitter.create_text_node(pointer, length);
+    }
+
+    pub fn create_raw_html(&self, html: &str) {
+        debug_assert!(self.traversal_is_committed());
+        debug!("emit: create_raw_html({:?})", html);
+        let (pointer, length) = self.state.emitter.string_immediates(html);
+        self.state.emitter.create_raw_html(pointer, length);
+    }
+
+    pub fn create_comment(&self) {
+        debug_assert!(self.traversal_is_committed());
+        debug!("emit: create_comment()");
+        self.state.emitter.create_comment();
     }
 
     pub fn create_element(&mut self, tag_name: &str) {
@@ -298,13 +887,53 @@ impl ChangeListBuilder<'_> {
         self.forcing_new_listeners = previous;
     }
 
+    /// Are we diffing against a node that was cloned from a template? Such
+    /// nodes have neither the template's listeners nor its properties.
+    #[inline]
+    pub fn is_forcing_new_listeners(&self) -> bool {
+        self.forcing_new_listeners
+    }
+
+    pub fn push_building_template(&mut self) -> bool {
+        mem::replace(&mut self.building_template, true)
+    }
+
+    pub fn pop_building_template(&mut self, previous: bool) {
+        debug_assert!(self.building_template);
+        self.building_template = previous;
+    }
+
+    /// Are we creating the physical DOM for a template? Its node refs and
+    /// lifecycles belong to no rendered element, and are ignored.
+    #[inline]
+    pub fn is_building_template(&self) -> bool {
+        self.building_template
+    }
+
+    pub fn push_diffing_template(&mut self) -> bool {
+        mem::replace(&mut self.diffing_template, true)
+    }
+
+    pub fn pop_diffing_template(&mut self, previous: bool) {
+        debug_assert!(self.diffing_template);
+        self.diffing_template = previous;
+    }
+
+    /// Are we diffing a template against the node it was created for? That
+    /// node is new, even though the template's physical DOM is reused for it.
+    #[inline]
+    pub fn is_diffing_template(&self) -> bool {
+        self.diffing_template
+    }
+
     pub fn new_event_listener(&mut self, listener: &Listener) {
         debug_assert!(self.traversal_is_committed());
         debug!("emit: new_event_listener({:?})", listener);
-        let (a, b) = listener.get_callback_parts();
-        debug_assert!(a != 0);
+        let (a, b) = listener.id_parts();
         let event_id = self.ensure_string(listener.event);
-        self.state.emitter.new_event_listener(event_id.into(), a, b);
+        self.state
+            .emitter
+            .new_event_listener(event_id.into(), a, b, listener.options.to_bits());
     }
 
     pub fn update_event_listener(&mut self, listener: &Listener) {
@@ -316,19 +945,20 @@ impl ChangeListBuilder<'_> {
         }
 
         debug!("emit: update_event_listener({:?})", listener);
-        let (a, b) = listener.get_callback_parts();
-        debug_assert!(a != 0);
+        let (a, b) = listener.id_parts();
         let event_id = self.ensure_string(listener.event);
         self.state
             .emitter
-            .update_event_listener(event_id.into(), a, b);
+            .update_event_listener(event_id.into(), a, b, listener.options.to_bits());
     }
 
-    pub fn remove_event_listener(&mut self, event: &str) {
+    pub fn remove_event_listener(&mut self, event: &str, options: ListenerOptions) {
         debug_assert!(self.traversal_is_committed());
-        debug!("emit: remove_event_listener({:?})", event);
+        debug!("emit: remove_event_listener({:?}, {:?})", event, options);
         let event_id = self.ensure_string(event);
-        self.state.emitter.remove_event_listener(event_id.into());
+        self.state
+            .emitter
+            .remove_event_listener(event_id.into(), options.to_bits());
     }
 
     #[inline]
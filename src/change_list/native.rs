@@ -0,0 +1,543 @@
+//! A port of `js/change-list-interpreter.js` that applies change lists to the
+//! in-memory DOM from `crate::native_dom` instead of the Web's DOM.
+//!
+//! This should be kept in sync with the JS interpreter: the whole point is that
+//! applying a change list here has the same effect as applying it in a Web
+//! browser.
+
+use super::emitter::Instruction;
+use crate::native_dom::{Node, PropertyValue};
+use crate::remote::Frame;
+use crate::ListenerOptions;
+use fxhash::FxHashMap;
+
+#[derive(Debug)]
+pub struct ChangeListInterpreter {
+    container: Node,
+    delegate_events: bool,
+    // The event types that the container has a delegating listener for.
+    delegated_types: Vec<String>,
+    stack: Vec<Node>,
+    strings: FxHashMap<u32, String>,
+    temporaries: FxHashMap<u32, Node>,
+    templates: FxHashMap<u32, Node>,
+    saved_nodes: Vec<Node>,
+    portal_targets: FxHashMap<u32, Node>,
+    // The start and end markers of each rendered portal. A portal is on the
+    // stack as its start marker, which stands in for the parent of the
+    // portal's children like the JS interpreter's `PortalRange`.
+    portals: FxHashMap<u32, (Node, Node)>,
+}
+
+// The nodes between a portal's start and end markers.
+fn portal_children(start: &Node, end: &Node) -> Vec<Node> {
+    let siblings = start
+        .parent()
+        .expect("portal markers should be in their target")
+        .children();
+    siblings
+        .into_iter()
+        .skip_while(|node| node != start)
+        .skip(1)
+        .take_while(|node| node != end)
+        .collect()
+}
+
+// Remove a portal's children along with the markers.
+fn remove_portal(start: &Node, end: &Node) {
+    for child in portal_children(start, end) {
+        child.remove();
+    }
+    start.remove();
+    end.remove();
+}
+
+fn string(frame: &Frame, offset: u32, length: u32) -> &str {
+    frame
+        .string(offset, length)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+impl ChangeListInterpreter {
+    pub fn new(container: &crate::Element, delegate_events: bool) -> ChangeListInterpreter {
+        ChangeListInterpreter {
+            container: container.clone(),
+            delegate_events,
+            delegated_types: vec![],
+            stack: vec![],
+            strings: Default::default(),
+            temporaries: Default::default(),
+            templates: Default::default(),
+            saved_nodes: vec![],
+            portal_targets: Default::default(),
+            portals: Default::default(),
+        }
+    }
+
+    pub fn unmount(&mut self) {
+        for event in self.delegated_types.drain(..) {
+            for root in std::iter::once(&self.container).chain(self.portal_targets.values()) {
+                root.remove_listener(&event, ListenerOptions::default());
+            }
+        }
+        // Portals' contents are removed along with the rest of the VDOM. Their
+        // targets may have other contents, which are left alone.
+        for (_, (start, end)) in self.portals.drain() {
+            remove_portal(&start, &end);
+        }
+        self.portal_targets.clear();
+        self.stack.clear();
+        self.strings.clear();
+        self.temporaries.clear();
+        self.templates.clear();
+        self.saved_nodes.clear();
+    }
+
+    pub fn add_portal_target(&mut self, id: u32, target: &Node) {
+        for event in &self.delegated_types {
+            target.set_listener(event, (0, 0), ListenerOptions::default());
+        }
+        self.portal_targets.insert(id, target.clone());
+    }
+
+    pub fn remove_portal_target(&mut self, id: u32) {
+        if let Some(target) = self.portal_targets.remove(&id) {
+            for event in &self.delegated_types {
+                target.remove_listener(event, ListenerOptions::default());
+            }
+        }
+    }
+
+    pub fn init_events_trampoline(&self, _trampoline: &crate::EventsTrampoline) {}
+
+    /// Take the elements saved by `setNodeRef` and `trackLifecycle`
+    /// instructions since the last time this was called.
+    pub fn take_saved_nodes(&mut self) -> Vec<Node> {
+        std::mem::take(&mut self.saved_nodes)
+    }
+
+    /// Apply a frame of changes.
+    pub fn apply_frame(&mut self, frame: &Frame) {
+        if frame.is_empty() {
+            return;
+        }
+
+        self.stack.push(self.container.clone());
+
+        let mut words = frame.words();
+        while !words.is_empty() {
+            let (instruction, len) =
+                Instruction::decode(words).unwrap_or_else(|e| panic!("invalid change list: {}", e));
+            words = &words[len..];
+            self.apply(instruction, frame);
+        }
+
+        self.stack.clear();
+        self.temporaries.clear();
+    }
+
+    fn is_delegated(&self, options: ListenerOptions) -> bool {
+        self.delegate_events && options == ListenerOptions::default()
+    }
+
+    fn delegate(&mut self, event: &str) {
+        if self.delegated_types.iter().any(|t| t == event) {
+            return;
+        }
+        // Stands in for both the bubbling and the capturing listener that the
+        // JS interpreter adds to the container and portal targets.
+        for root in std::iter::once(&self.container).chain(self.portal_targets.values()) {
+            root.set_listener(event, (0, 0), ListenerOptions::default());
+        }
+        self.delegated_types.push(event.to_string());
+    }
+
+    fn top(&self) -> &Node {
+        self.stack
+            .last()
+            .expect("change list stack should not be empty")
+    }
+
+    fn pop(&mut self) -> Node {
+        self.stack
+            .pop()
+            .expect("change list stack should not be empty")
+    }
+
+    // The end marker of the portal that `node` is the start marker of, if any.
+    fn portal_end(&self, node: &Node) -> Option<&Node> {
+        self.portals
+            .values()
+            .find(|(start, _)| start == node)
+            .map(|(_, end)| end)
+    }
+
+    fn is_portal_end(&self, node: &Node) -> bool {
+        self.portals.values().any(|(_, end)| end == node)
+    }
+
+    // The children of the parent on top of the stack.
+    fn top_children(&self) -> Vec<Node> {
+        let top = self.top();
+        match self.portal_end(top) {
+            Some(end) => portal_children(top, end),
+            None => top.children(),
+        }
+    }
+
+    fn top_child(&self, n: usize) -> Node {
+        self.top_children()
+            .get(n)
+            .cloned()
+            .unwrap_or_else(|| panic!("{:?} should have a child at index {}", self.top(), n))
+    }
+
+    fn top_reverse_child(&self, n: usize) -> Node {
+        let len = self.top_children().len();
+        self.top_child(len - n - 1)
+    }
+
+    fn append_to_top(&self, child: &Node) {
+        let top = self.top();
+        match self.portal_end(top) {
+            Some(end) => end
+                .parent()
+                .expect("portal markers should be in their target")
+                .insert_before(child, end),
+            None => top.append_child(child),
+        }
+    }
+
+    fn cached_string(&self, id: u32) -> &str {
+        self.strings
+            .get(&id)
+            .unwrap_or_else(|| panic!("should have cached string {}", id))
+    }
+
+    fn apply(&mut self, instruction: Instruction, frame: &Frame) {
+        match instruction {
+            // setText
+            Instruction::SetText { pointer, length } => {
+                let text = string(frame, pointer, length);
+                if self.portal_end(self.top()).is_some() {
+                    for child in self.top_children() {
+                        child.remove();
+                    }
+                    if !text.is_empty() {
+                        self.append_to_top(&Node::create_text_node(text));
+                    }
+                } else {
+                    self.top().set_text_content(text);
+                }
+            }
+
+            // removeSelfAndNextSiblings
+            Instruction::RemoveSelfAndNextSiblings {} => {
+                let node = self.pop();
+                // The last child of a portal is followed by the portal's end
+                // marker, and maybe other contents of the portal's target,
+                // which must stay.
+                let siblings = node.parent().expect("should have a parent").children();
+                for sibling in siblings
+                    .iter()
+                    .skip_while(|sibling| **sibling != node)
+                    .take_while(|sibling| !self.is_portal_end(sibling))
+                {
+                    sibling.remove();
+                }
+            }
+
+            // replaceWith
+            Instruction::ReplaceWith {} => {
+                let new_node = self.pop();
+                let old_node = self.pop();
+                old_node.replace_with(&new_node);
+                self.stack.push(new_node);
+            }
+
+            // setAttribute
+            Instruction::SetAttribute {
+                attribute_key,
+                value_key,
+            } => {
+                let name = self.cached_string(attribute_key);
+                let value = self.cached_string(value_key);
+                self.top().set_attribute(name, value);
+            }
+
+            // removeAttribute
+            Instruction::RemoveAttribute { attribute_key } => {
+                let name = self.cached_string(attribute_key);
+                self.top().remove_attribute(name);
+            }
+
+            // pushReverseChild
+            Instruction::PushReverseChild { n } => {
+                let child = self.top_reverse_child(n as usize);
+                self.stack.push(child);
+            }
+
+            // popPushChild
+            Instruction::PopPushChild { n } => {
+                self.pop();
+                let child = self.top_child(n as usize);
+                self.stack.push(child);
+            }
+
+            // pop
+            Instruction::Pop {} => {
+                self.pop();
+            }
+
+            // appendChild
+            Instruction::AppendChild {} => {
+                let child = self.pop();
+                self.append_to_top(&child);
+            }
+
+            // createTextNode
+            Instruction::CreateTextNode { pointer, length } => {
+                let node = Node::create_text_node(string(frame, pointer, length));
+                self.stack.push(node);
+            }
+
+            // createElement
+            Instruction::CreateElement { tag_name_key } => {
+                let tag_name = self.cached_string(tag_name_key);
+                let node = Node::create_element(tag_name);
+                self.stack.push(node);
+            }
+
+            // newEventListener and updateEventListener
+            Instruction::NewEventListener {
+                event_key,
+                a,
+                b,
+                options,
+            }
+            | Instruction::UpdateEventListener {
+                event_key,
+                a,
+                b,
+                options,
+            } => {
+                let event = self.cached_string(event_key).to_string();
+                let options = ListenerOptions::from_bits(options);
+                // Delegated listeners are still recorded on their element, in
+                // place of the JS interpreter's expandos.
+                if self.is_delegated(options) {
+                    self.delegate(&event);
+                }
+                self.top().set_listener(&event, (a, b), options);
+            }
+
+            // removeEventListener
+            Instruction::RemoveEventListener { event_key, options } => {
+                let event = self.cached_string(event_key);
+                let options = ListenerOptions::from_bits(options);
+                self.top().remove_listener(event, options);
+            }
+
+            // addCachedString
+            Instruction::AddCachedString {
+                pointer,
+                length,
+                key,
+            } => {
+                let s = string(frame, pointer, length).to_string();
+                self.strings.insert(key, s);
+            }
+
+            // dropCachedString
+            Instruction::DropCachedString { key } => {
+                self.strings.remove(&key);
+            }
+
+            // createElementNS
+            Instruction::CreateElementNs {
+                tag_name_key,
+                namespace_key,
+            } => {
+                let tag_name = self.cached_string(tag_name_key);
+                let namespace = self.cached_string(namespace_key);
+                let node = Node::create_element_ns(tag_name, namespace);
+                self.stack.push(node);
+            }
+
+            // saveChildrenToTemporaries
+            Instruction::SaveChildrenToTemporaries {
+                temp_base,
+                start,
+                end,
+            } => {
+                let children = self.top_children();
+                for (temp, child) in (temp_base..).zip(&children[start as usize..end as usize]) {
+                    self.temporaries.insert(temp, child.clone());
+                }
+            }
+
+            // pushChild
+            Instruction::PushChild { n } => {
+                let child = self.top_child(n as usize);
+                self.stack.push(child);
+            }
+
+            // pushTemporary
+            Instruction::PushTemporary { temp } => {
+                let node = self.temporaries[&temp].clone();
+                self.stack.push(node);
+            }
+
+            // insertBefore
+            Instruction::InsertBefore {} => {
+                let before = self.pop();
+                let after = self.pop();
+                let parent = after.parent().expect("should have a parent");
+                parent.insert_before(&before, &after);
+                self.stack.push(before);
+            }
+
+            // popPushReverseChild
+            Instruction::PopPushReverseChild { n } => {
+                self.pop();
+                let child = self.top_reverse_child(n as usize);
+                self.stack.push(child);
+            }
+
+            // removeChild
+            Instruction::RemoveChild { n } => {
+                self.top_child(n as usize).remove();
+            }
+
+            // setClass
+            Instruction::SetClass { class } => {
+                let class = self.cached_string(class);
+                self.top().set_attribute("class", class);
+            }
+
+            // saveTemplate
+            Instruction::SaveTemplate { id } => {
+                let template = self.top().clone_node();
+                self.templates.insert(id, template);
+            }
+
+            // pushTemplate
+            Instruction::PushTemplate { id } => {
+                let node = self.templates[&id].clone_node();
+                self.stack.push(node);
+            }
+
+            // createComment
+            Instruction::CreateComment {} => {
+                self.stack.push(Node::create_comment());
+            }
+
+            // createRawHtml
+            Instruction::CreateRawHtml { pointer, length } => {
+                let node = Node::create_element("div");
+                node.append_child(&Node::create_raw_html(string(frame, pointer, length)));
+                self.stack.push(node);
+            }
+
+            // setPropertyBool
+            Instruction::SetPropertyBool { name_key, value } => {
+                let name = self.cached_string(name_key);
+                self.top()
+                    .set_property(name, PropertyValue::Bool(value != 0));
+            }
+
+            // setPropertyNumber
+            Instruction::SetPropertyNumber {
+                name_key,
+                low,
+                high,
+            } => {
+                let name = self.cached_string(name_key);
+                let value = f64::from_bits(u64::from(high) << 32 | u64::from(low));
+                self.top().set_property(name, PropertyValue::Number(value));
+            }
+
+            // setPropertyString
+            Instruction::SetPropertyString {
+                name_key,
+                value_key,
+            } => {
+                let name = self.cached_string(name_key);
+                let value = self.cached_string(value_key).to_string();
+                self.top().set_property(name, PropertyValue::String(value));
+            }
+
+            // removeProperty
+            Instruction::RemoveProperty { name_key } => {
+                let name = self.cached_string(name_key);
+                self.top().remove_property(name);
+            }
+
+            // setStyle
+            Instruction::SetStyle {
+                property_key,
+                value_key,
+            } => {
+                let property = self.cached_string(property_key);
+                let value = self.cached_string(value_key);
+                self.top().set_style(property, value);
+            }
+
+            // addClass
+            Instruction::AddClass { class_key } => {
+                let class = self.cached_string(class_key);
+                self.top().add_class(class);
+            }
+
+            // removeClass
+            Instruction::RemoveClass { class_key } => {
+                let class = self.cached_string(class_key);
+                self.top().remove_class(class);
+            }
+
+            // removeStyle
+            Instruction::RemoveStyle { property_key } => {
+                let property = self.cached_string(property_key);
+                self.top().remove_style(property);
+            }
+
+            // setNodeRef
+            Instruction::SetNodeRef {} => {
+                let node = self.top().clone();
+                self.saved_nodes.push(node);
+            }
+
+            // trackLifecycle
+            Instruction::TrackLifecycle {} => {
+                let node = self.top().clone();
+                self.saved_nodes.push(node);
+            }
+
+            // createPortal
+            Instruction::CreatePortal { id, key } => {
+                let target = &self.portal_targets[&id];
+                let start = Node::create_comment();
+                let end = Node::create_comment();
+                target.append_child(&start);
+                target.append_child(&end);
+                self.portals.insert(key, (start.clone(), end));
+                self.stack.push(start);
+            }
+
+            // pushPortal
+            Instruction::PushPortal { key } => {
+                let (start, _) = self.portals[&key].clone();
+                self.stack.push(start);
+            }
+
+            // removePortal
+            Instruction::RemovePortal { key } => {
+                let (start, end) = self
+                    .portals
+                    .remove(&key)
+                    .expect("removePortal: should have the portal");
+                remove_portal(&start, &end);
+            }
+        }
+    }
+}
@@ -39,11 +39,21 @@ impl StringsCache {
             self.next_string_key += 1;
             let entry = StringsCacheEntry { key, used: true };
             self.entries.insert(string.to_string(), entry);
-            emitter.add_cached_string(string.as_ptr() as u32, string.len() as u32, key.into());
+            let (pointer, length) = emitter.string_immediates(string);
+            emitter.add_cached_string(pointer, length, key.into());
             key
         }
     }
 
+    /// Get a map from each cached string's key to its contents.
+    #[cfg(feature = "xxx-unstable-internal-use-only")]
+    pub fn cached_strings(&self) -> FxHashMap<u32, String> {
+        self.entries
+            .iter()
+            .map(|(string, entry)| (entry.key.into(), string.clone()))
+            .collect()
+    }
+
     pub fn drop_unused_strings(&mut self, emitter: &InstructionEmitter) {
         self.entries.retain(|string, entry| {
             if entry.used {
@@ -2,13 +2,195 @@ use crate::{
     cached_set::{CacheId, CachedSet},
     change_list::ChangeListBuilder,
     events::EventsRegistry,
-    node::{Attribute, ElementNode, Listener, Node, NodeKind, TextNode},
+    node::{
+        Attribute, ElementNode, FragmentNode, Lifecycle, Listener, Node, NodeKind, PortalNode,
+        Property, RawHtmlNode, TextNode,
+    },
+    node_ref::NodeRefId,
 };
 use fxhash::{FxHashMap, FxHashSet};
+use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::slice;
 use std::u32;
 use wasm_bindgen::UnwrapThrowExt;
 
+/// A diff that is done a step at a time, so that it can be spread out over
+/// several slices of time.
+///
+/// Each step diffs a pair of nodes without their children, or creates one new
+/// child, and leaves the rest of the work on a stack. Between steps, the change
+/// list stack is in the same state that it would be in at that point of a diff
+/// that was done all at once.
+#[derive(Default)]
+pub(crate) struct Diff<'a> {
+    work: Vec<Work<'a>>,
+}
+
+// Work that is left to do in a `Diff`, and the shape of the change list stack
+// that it expects.
+enum Work<'a> {
+    // Diff the `old` children with the `new` children.
+    //
+    //     [... parent]
+    Children(&'a [Node<'a>], &'a [Node<'a>]),
+
+    // Diff the non-keyed children from index `i` onwards.
+    //
+    //     [... parent prev_child]
+    NonKeyedChildren {
+        old: Cow<'a, [Node<'a>]>,
+        new: Cow<'a, [Node<'a>]>,
+        i: usize,
+    },
+
+    // Diff the prefix of keyed children that share the same keys from index
+    // `i` onwards, and then the rest of the keyed children.
+    //
+    //     [... parent prev_child]
+    KeyedPrefix {
+        old: Cow<'a, [Node<'a>]>,
+        new: Cow<'a, [Node<'a>]>,
+        i: usize,
+    },
+
+    // Diff the suffix of keyed children that share the same keys, which starts
+    // at `old_start` and `new_start`, from its index `i` onwards.
+    //
+    //     [... parent prev_child]
+    KeyedSuffix {
+        old: Cow<'a, [Node<'a>]>,
+        new: Cow<'a, [Node<'a>]>,
+        old_start: usize,
+        new_start: usize,
+        i: usize,
+    },
+
+    // Create and append the `new` children from index `i` onwards.
+    //
+    //     [... parent]
+    AppendChildren {
+        new: Cow<'a, [Node<'a>]>,
+        i: usize,
+    },
+
+    // Restore the next temporary once keyed children are diffed.
+    SetNextTemporary(u32),
+
+    // Pop the range of a portal's children in its target once they are diffed.
+    //
+    //     [... portal range]
+    PopPortal,
+}
+
+impl<'a> Diff<'a> {
+    /// Start diffing the `old` root node with the `new` root node. The
+    /// container must be on top of the change list stack for every step.
+    pub(crate) fn root(old: &'a Node<'a>, new: &'a Node<'a>) -> Diff<'a> {
+        // The roots are the container's only children, and may be fragments.
+        Diff {
+            work: vec![Work::Children(slice::from_ref(old), slice::from_ref(new))],
+        }
+    }
+
+    /// Has all of the work been done?
+    #[inline]
+    pub(crate) fn is_finished(&self) -> bool {
+        self.work.is_empty()
+    }
+
+    /// Do the next step of work, if there is any left.
+    pub(crate) fn step(
+        &mut self,
+        cached_set: &'a CachedSet,
+        change_list: &mut ChangeListBuilder,
+        registry: &mut EventsRegistry,
+        cached_roots: &mut FxHashSet<CacheId>,
+    ) {
+        let work = &mut self.work;
+        match work.pop() {
+            None => {}
+            Some(Work::Children(old, new)) => diff_children(
+                cached_set,
+                change_list,
+                registry,
+                old,
+                new,
+                cached_roots,
+                work,
+            ),
+            Some(Work::NonKeyedChildren { old, new, i }) => diff_non_keyed_children(
+                cached_set,
+                change_list,
+                registry,
+                old,
+                new,
+                i,
+                cached_roots,
+                work,
+            ),
+            Some(Work::KeyedPrefix { old, new, i }) => diff_keyed_prefix(
+                cached_set,
+                change_list,
+                registry,
+                old,
+                new,
+                i,
+                cached_roots,
+                work,
+            ),
+            Some(Work::KeyedSuffix {
+                old,
+                new,
+                old_start,
+                new_start,
+                i,
+            }) => diff_keyed_suffix(
+                cached_set,
+                change_list,
+                registry,
+                old,
+                new,
+                old_start,
+                new_start,
+                i,
+                cached_roots,
+                work,
+            ),
+            Some(Work::AppendChildren { new, i }) => {
+                // [... parent]
+                create(cached_set, change_list, registry, &new[i], cached_roots);
+                // [... parent child]
+                change_list.append_child();
+                // [... parent]
+                if i + 1 < new.len() {
+                    work.push(Work::AppendChildren { new, i: i + 1 });
+                }
+            }
+            Some(Work::SetNextTemporary(t)) => change_list.set_next_temporary(t),
+            Some(Work::PopPortal) => {
+                // [... portal range]
+                change_list.commit_traversal();
+                change_list.pop();
+                // [... portal]
+            }
+        }
+    }
+
+    /// Do all of the work that is left.
+    pub(crate) fn finish(
+        &mut self,
+        cached_set: &'a CachedSet,
+        change_list: &mut ChangeListBuilder,
+        registry: &mut EventsRegistry,
+        cached_roots: &mut FxHashSet<CacheId>,
+    ) {
+        while !self.is_finished() {
+            self.step(cached_set, change_list, registry, cached_roots);
+        }
+    }
+}
+
 // Diff the `old` node with the `new` node. Emits instructions to modify a
 // physical DOM node that reflects `old` into something that reflects `new`.
 //
@@ -18,15 +200,51 @@ use wasm_bindgen::UnwrapThrowExt;
 //     [... node]
 //
 // The change list stack is in the same state when this function exits.
-pub(crate) fn diff(
-    cached_set: &CachedSet,
+pub(crate) fn diff<'a>(
+    cached_set: &'a CachedSet,
     change_list: &mut ChangeListBuilder,
     registry: &mut EventsRegistry,
-    old: &Node,
-    new: &Node,
+    old: &'a Node<'a>,
+    new: &'a Node<'a>,
     cached_roots: &mut FxHashSet<CacheId>,
+) {
+    let mut diff = Diff::default();
+    diff_node(
+        cached_set,
+        change_list,
+        registry,
+        old,
+        new,
+        cached_roots,
+        &mut diff.work,
+    );
+    diff.finish(cached_set, change_list, registry, cached_roots);
+}
+
+// Diff the `old` node with the `new` node, like `diff`, but push diffing their
+// children onto the `work` stack rather than doing it right away.
+//
+// Upon entry to this function, the physical DOM node must be on the top of the
+// change list stack:
+//
+//     [... node]
+//
+// The change list stack is in the same state when this function exits, and
+// once the work it pushed is done.
+fn diff_node<'a>(
+    cached_set: &'a CachedSet,
+    change_list: &mut ChangeListBuilder,
+    registry: &mut EventsRegistry,
+    old: &Node<'a>,
+    new: &Node<'a>,
+    cached_roots: &mut FxHashSet<CacheId>,
+    work: &mut Vec<Work<'a>>,
 ) {
     match (&new.kind, &old.kind) {
+        (&NodeKind::Fragment(_), _) | (_, &NodeKind::Fragment(_)) => {
+            unreachable!("fragments are flattened into their parent's children")
+        }
+
         (
             &NodeKind::Text(TextNode { text: new_text }),
             &NodeKind::Text(TextNode { text: old_text }),
@@ -40,7 +258,7 @@ pub(crate) fn diff(
         (&NodeKind::Text(_), &NodeKind::Element(_)) => {
             change_list.commit_traversal();
             create(cached_set, change_list, registry, new, cached_roots);
-            registry.remove_subtree(&old);
+            remove_subtree(cached_set, change_list, registry, old);
             change_list.replace_with();
         }
 
@@ -52,41 +270,102 @@ pub(crate) fn diff(
             change_list.replace_with();
         }
 
+        // Placeholders are all alike, so there is nothing to do.
+        (&NodeKind::Empty, &NodeKind::Empty) => {}
+
+        // Raw HTML is opaque to us, so it is either unchanged, or replaced
+        // wholesale.
+        (
+            &NodeKind::RawHtml(RawHtmlNode { html: new_html }),
+            &NodeKind::RawHtml(RawHtmlNode { html: old_html }),
+        ) => {
+            if new_html != old_html {
+                change_list.commit_traversal();
+                create(cached_set, change_list, registry, new, cached_roots);
+                change_list.replace_with();
+            }
+        }
+
         (
             &NodeKind::Element(ElementNode {
                 key: _,
                 tag_name: new_tag_name,
                 listeners: new_listeners,
                 attributes: new_attributes,
+                properties: new_properties,
+                styles: new_styles,
+                classes: new_classes,
                 children: new_children,
                 namespace: new_namespace,
+                node_ref: new_node_ref,
+                lifecycle: new_lifecycle,
             }),
             &NodeKind::Element(ElementNode {
                 key: _,
                 tag_name: old_tag_name,
                 listeners: old_listeners,
                 attributes: old_attributes,
+                properties: old_properties,
+                styles: old_styles,
+                classes: old_classes,
                 children: old_children,
                 namespace: old_namespace,
+                node_ref: old_node_ref,
+                lifecycle: old_lifecycle,
             }),
         ) => {
             if new_tag_name != old_tag_name || new_namespace != old_namespace {
                 change_list.commit_traversal();
                 create(cached_set, change_list, registry, new, cached_roots);
-                registry.remove_subtree(&old);
+                remove_subtree(cached_set, change_list, registry, old);
                 change_list.replace_with();
                 return;
             }
             diff_listeners(change_list, registry, old_listeners, new_listeners);
-            diff_attributes(change_list, old_attributes, new_attributes, new_namespace.is_some());
-            diff_children(
-                cached_set,
+            diff_attributes(
                 change_list,
-                registry,
-                old_children,
-                new_children,
-                cached_roots,
+                old_attributes,
+                new_attributes,
+                new_namespace.is_some(),
             );
+            diff_properties(change_list, old_properties, new_properties);
+            // Changing the `style` attribute replaces all of the inline styles,
+            // so every style has to be set again.
+            if style_attribute(old_attributes) == style_attribute(new_attributes) {
+                diff_styles(change_list, old_styles, new_styles);
+            } else {
+                diff_styles(change_list, &[], new_styles);
+            }
+            diff_classes(change_list, old_classes, new_classes);
+            diff_node_refs(change_list, *old_node_ref, *new_node_ref);
+            diff_lifecycles(change_list, *old_lifecycle, *new_lifecycle);
+            work.push(Work::Children(old_children, new_children));
+        }
+
+        (NodeKind::Portal(new_portal), NodeKind::Portal(old_portal)) => {
+            change_list.commit_traversal();
+
+            // A node cloned from a template is new, and its portal's children
+            // haven't been created, since templates are built without them.
+            if change_list.is_diffing_template() {
+                create_portal_children(cached_set, change_list, registry, new_portal, cached_roots);
+                return;
+            }
+
+            if new_portal.target.id != old_portal.target.id {
+                remove_subtree(cached_set, change_list, registry, old);
+                create_portal_children(cached_set, change_list, registry, new_portal, cached_roots);
+                return;
+            }
+
+            // [... portal]
+            if !change_list.push_portal(old_portal, new_portal) {
+                // The portal's children weren't rendered.
+                return;
+            }
+            // [... portal range]
+            work.push(Work::PopPortal);
+            work.push(Work::Children(old_portal.children, new_portal.children));
         }
 
         // Both the new and old nodes are cached.
@@ -103,7 +382,15 @@ pub(crate) fn diff(
             if new_template == old_template {
                 // If they are both using the same template, then just diff the
                 // subtrees.
-                diff(cached_set, change_list, registry, old, new, cached_roots);
+                diff_node(
+                    cached_set,
+                    change_list,
+                    registry,
+                    old,
+                    new,
+                    cached_roots,
+                    work,
+                );
             } else {
                 // Otherwise, they are probably different enough that
                 // re-constructing the subtree from scratch should be faster.
@@ -144,7 +431,22 @@ pub(crate) fn diff(
         (_, &NodeKind::Cached(_)) => {
             change_list.commit_traversal();
             create(cached_set, change_list, registry, new, cached_roots);
-            registry.remove_subtree(&old);
+            remove_subtree(cached_set, change_list, registry, old);
+            change_list.replace_with();
+        }
+
+        // A node was shown or hidden in place of a placeholder, or swapped with
+        // raw HTML or a portal. Because the new node occupies the old node's
+        // position, none of its siblings move.
+        (&NodeKind::Empty, _)
+        | (_, &NodeKind::Empty)
+        | (&NodeKind::RawHtml(_), _)
+        | (_, &NodeKind::RawHtml(_))
+        | (&NodeKind::Portal(_), _)
+        | (_, &NodeKind::Portal(_)) => {
+            change_list.commit_traversal();
+            create(cached_set, change_list, registry, new, cached_roots);
+            remove_subtree(cached_set, change_list, registry, old);
             change_list.replace_with();
         }
     }
@@ -177,7 +479,14 @@ fn diff_listeners(
 
         for old_l in old {
             if new_l.event == old_l.event {
-                change_list.update_event_listener(new_l);
+                if new_l.options == old_l.options {
+                    change_list.update_event_listener(new_l);
+                } else {
+                    // Listeners can't change their options in place, so
+                    // re-add it with the new options.
+                    change_list.remove_event_listener(old_l.event, old_l.options);
+                    change_list.new_event_listener(new_l);
+                }
                 continue 'outer1;
             }
         }
@@ -193,10 +502,201 @@ fn diff_listeners(
                 continue 'outer2;
             }
         }
-        change_list.remove_event_listener(old_l.event);
+        change_list.remove_event_listener(old_l.event, old_l.options);
     }
 }
 
+// Diff the node refs attached to an element.
+//
+// The element must be on top of the change list stack:
+//
+//     [... node]
+//
+// The change list stack is left unchanged.
+fn diff_node_refs(
+    change_list: &mut ChangeListBuilder,
+    old: Option<NodeRefId>,
+    new: Option<NodeRefId>,
+) {
+    if change_list.is_building_template() {
+        return;
+    }
+
+    // When diffing a template against the node it was created for, the node
+    // is new, and the template's node ref doesn't point at it.
+    let fresh = change_list.is_diffing_template();
+    if old == new && !fresh {
+        return;
+    }
+
+    if let Some(old) = old.filter(|_| !fresh) {
+        old.set(None);
+    }
+    if let Some(new) = new {
+        change_list.commit_traversal();
+        change_list.set_node_ref(new);
+    }
+}
+
+// Diff the lifecycles of an element.
+//
+// The element must be on top of the change list stack:
+//
+//     [... node]
+//
+// The change list stack is left unchanged.
+fn diff_lifecycles(
+    change_list: &mut ChangeListBuilder,
+    old: Option<&Lifecycle>,
+    new: Option<&Lifecycle>,
+) {
+    if change_list.is_building_template() {
+        return;
+    }
+
+    // Like with node refs, a node created from a template is new, and its
+    // template's lifecycle isn't tracked.
+    let fresh = change_list.is_diffing_template();
+    match (old.filter(|_| !fresh), new) {
+        (None, None) => {}
+        (Some(old), None) => unsafe { change_list.untrack_lifecycle(old, false) },
+        (Some(old), Some(new)) => change_list.retrack_lifecycle(old, new),
+        (None, Some(new)) => {
+            change_list.commit_traversal();
+            unsafe { change_list.track_lifecycle(new, fresh) };
+        }
+    }
+}
+
+// Clean up after a subtree that is being removed: unregister its listeners,
+// clear its node refs, unmount its lifecycles, and remove its portals' children
+// from their targets. Like listeners, node refs in cached subtrees belong to the
+// cached set, and are left alone. Their elements are unmounted all the same,
+// though.
+fn remove_subtree(
+    cached_set: &CachedSet,
+    change_list: &mut ChangeListBuilder,
+    registry: &mut EventsRegistry,
+    node: &Node,
+) {
+    registry.remove_subtree(node);
+    clear_node_refs(node);
+    unmount_lifecycles(cached_set, change_list, node);
+    remove_portals(cached_set, change_list, node);
+}
+
+fn clear_node_refs(node: &Node) {
+    match node.kind {
+        NodeKind::Element(&ElementNode {
+            node_ref, children, ..
+        }) => {
+            if let Some(node_ref) = node_ref {
+                node_ref.set(None);
+            }
+            for child in children {
+                clear_node_refs(child);
+            }
+        }
+        NodeKind::Fragment(FragmentNode { children })
+        | NodeKind::Portal(PortalNode { children, .. }) => {
+            for child in children {
+                clear_node_refs(child);
+            }
+        }
+        NodeKind::Cached(_) | NodeKind::Text(_) | NodeKind::Empty | NodeKind::RawHtml(_) => {}
+    }
+}
+
+// Unmount each lifecycle in the subtree, descendants before their ancestors.
+fn unmount_lifecycles(cached_set: &CachedSet, change_list: &mut ChangeListBuilder, node: &Node) {
+    match node.kind {
+        NodeKind::Element(&ElementNode {
+            lifecycle,
+            children,
+            ..
+        }) => {
+            for child in children {
+                unmount_lifecycles(cached_set, change_list, child);
+            }
+            if let Some(lifecycle) = lifecycle {
+                // The old nodes, and the cached set, outlive this change list.
+                unsafe { change_list.untrack_lifecycle(lifecycle, true) };
+            }
+        }
+        NodeKind::Fragment(FragmentNode { children })
+        | NodeKind::Portal(PortalNode { children, .. }) => {
+            for child in children {
+                unmount_lifecycles(cached_set, change_list, child);
+            }
+        }
+        NodeKind::Cached(ref c) => {
+            let (cached, _template) = cached_set.get(c.id);
+            unmount_lifecycles(cached_set, change_list, cached);
+        }
+        NodeKind::Text(_) | NodeKind::Empty | NodeKind::RawHtml(_) => {}
+    }
+}
+
+// Remove the children of each portal in the subtree, with their markers, from
+// the portal's target. The portals' placeholders are removed along with
+// the rest of the subtree.
+//
+// The change list stack is left unchanged.
+fn remove_portals(cached_set: &CachedSet, change_list: &mut ChangeListBuilder, node: &Node) {
+    // A template's portals never had their children created.
+    if change_list.is_diffing_template() {
+        return;
+    }
+    match node.kind {
+        NodeKind::Element(&ElementNode { children, .. })
+        | NodeKind::Fragment(FragmentNode { children }) => {
+            for child in children {
+                remove_portals(cached_set, change_list, child);
+            }
+        }
+        NodeKind::Cached(ref c) => {
+            let (cached, _template) = cached_set.get(c.id);
+            remove_portals(cached_set, change_list, cached);
+        }
+        NodeKind::Portal(ref portal) => {
+            // Nested portals' children are in other targets.
+            for child in portal.children {
+                remove_portals(cached_set, change_list, child);
+            }
+            change_list.remove_portal(portal);
+        }
+        NodeKind::Text(_) | NodeKind::Empty | NodeKind::RawHtml(_) => {}
+    }
+}
+
+// Create a portal's children, and append them to the portal's target between a
+// new pair of markers.
+//
+// The change list stack is left unchanged.
+pub(crate) fn create_portal_children(
+    cached_set: &CachedSet,
+    change_list: &mut ChangeListBuilder,
+    registry: &mut EventsRegistry,
+    portal: &PortalNode,
+    cached_roots: &mut FxHashSet<CacheId>,
+) {
+    debug_assert!(change_list.traversal_is_committed());
+    // [...]
+    if !change_list.push_new_portal(portal) {
+        return;
+    }
+    // [... range]
+    create_and_append_children(
+        cached_set,
+        change_list,
+        registry,
+        portal.children,
+        cached_roots,
+    );
+    change_list.pop();
+    // [...]
+}
+
 // Diff a node's attributes.
 //
 // The attributes' node must be on top of the change list stack:
@@ -204,7 +704,12 @@ fn diff_listeners(
 //     [... node]
 //
 // The change list stack is left unchanged.
-fn diff_attributes(change_list: &mut ChangeListBuilder, old: &[Attribute], new: &[Attribute], is_namespaced: bool) {
+fn diff_attributes(
+    change_list: &mut ChangeListBuilder,
+    old: &[Attribute],
+    new: &[Attribute],
+    is_namespaced: bool,
+) {
     // Do O(n^2) passes to add/update and remove attributes, since
     // there are almost always very few attributes.
     'outer: for new_attr in new {
@@ -239,26 +744,148 @@ fn diff_attributes(change_list: &mut ChangeListBuilder, old: &[Attribute], new:
     }
 }
 
-// Diff the given set of old and new children.
+// Diff a node's class list tokens.
+//
+// Only the tokens that were added or removed are touched, so that any classes
+// that were added to the physical node by something else are left alone.
+//
+// The classes' node must be on top of the change list stack:
+//
+//     [... node]
+//
+// The change list stack is left unchanged.
+fn diff_classes(change_list: &mut ChangeListBuilder, old: &[&str], new: &[&str]) {
+    for class in old {
+        if !new.contains(class) {
+            change_list.commit_traversal();
+            change_list.remove_class(class);
+        }
+    }
+
+    for class in new {
+        if !old.contains(class) {
+            change_list.commit_traversal();
+            change_list.add_class(class);
+        }
+    }
+}
+
+// Diff a node's inline styles.
+//
+// The styles' node must be on top of the change list stack:
+//
+//     [... node]
+//
+// The change list stack is left unchanged.
+fn style_attribute<'a>(attributes: &[Attribute<'a>]) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|attr| attr.name == "style")
+        .map(|attr| attr.value)
+}
+
+fn diff_styles(change_list: &mut ChangeListBuilder, old: &[Attribute], new: &[Attribute]) {
+    // Same as with attributes, do O(n^2) passes since there are almost always
+    // very few styles.
+    'outer: for new_style in new {
+        for old_style in old {
+            if old_style.name == new_style.name {
+                if old_style.value != new_style.value {
+                    change_list.commit_traversal();
+                    change_list.set_style(new_style.name, new_style.value);
+                }
+                continue 'outer;
+            }
+        }
+
+        change_list.commit_traversal();
+        change_list.set_style(new_style.name, new_style.value);
+    }
+
+    'outer2: for old_style in old {
+        for new_style in new {
+            if old_style.name == new_style.name {
+                continue 'outer2;
+            }
+        }
+
+        change_list.commit_traversal();
+        change_list.remove_style(old_style.name);
+    }
+}
+
+// Diff a node's properties.
+//
+// The properties' node must be on top of the change list stack:
+//
+//     [... node]
+//
+// The change list stack is left unchanged.
+fn diff_properties(change_list: &mut ChangeListBuilder, old: &[Property], new: &[Property]) {
+    // Nodes cloned from a template don't have any of the template's
+    // properties, so set all of them from scratch.
+    let old = if change_list.is_forcing_new_listeners() {
+        &[]
+    } else {
+        old
+    };
+
+    // Same as with attributes, do O(n^2) passes since there are almost always
+    // very few properties.
+    'outer: for new_prop in new {
+        for old_prop in old {
+            if old_prop.name == new_prop.name {
+                if old_prop.value != new_prop.value {
+                    change_list.commit_traversal();
+                    change_list.set_property(new_prop.name, new_prop.value);
+                }
+                continue 'outer;
+            }
+        }
+
+        change_list.commit_traversal();
+        change_list.set_property(new_prop.name, new_prop.value);
+    }
+
+    'outer2: for old_prop in old {
+        for new_prop in new {
+            if old_prop.name == new_prop.name {
+                continue 'outer2;
+            }
+        }
+
+        change_list.commit_traversal();
+        change_list.remove_property(old_prop.name);
+    }
+}
+
+// Diff the given set of old and new children, pushing the bulk of the work
+// onto the `work` stack.
 //
 // The parent must be on top of the change list stack when this function is
 // entered:
 //
 //     [... parent]
 //
-// the change list stack is in the same state when this function returns.
-fn diff_children(
-    cached_set: &CachedSet,
+// the change list stack is in the same state once the work is done.
+fn diff_children<'a>(
+    cached_set: &'a CachedSet,
     change_list: &mut ChangeListBuilder,
     registry: &mut EventsRegistry,
-    old: &[Node],
-    new: &[Node],
+    old: &'a [Node<'a>],
+    new: &'a [Node<'a>],
     cached_roots: &mut FxHashSet<CacheId>,
+    work: &mut Vec<Work<'a>>,
 ) {
+    let old = flatten_children(cached_set, old, &mut |_| {});
+    let new = flatten_children(cached_set, new, &mut |id| {
+        cached_roots.insert(id);
+    });
+
     if new.is_empty() {
         if !old.is_empty() {
             change_list.commit_traversal();
-            remove_all_children(change_list, registry, old);
+            remove_all_children(cached_set, change_list, registry, &old);
         }
         return;
     }
@@ -283,8 +910,8 @@ fn diff_children(
             ) => {
                 change_list.commit_traversal();
                 change_list.set_text(text);
-                for o in old {
-                    registry.remove_subtree(o);
+                for o in old.iter() {
+                    remove_subtree(cached_set, change_list, registry, o);
                 }
                 return;
             }
@@ -293,10 +920,8 @@ fn diff_children(
     }
 
     if old.is_empty() {
-        if !new.is_empty() {
-            change_list.commit_traversal();
-            create_and_append_children(cached_set, change_list, registry, new, cached_roots);
-        }
+        change_list.commit_traversal();
+        work.push(Work::AppendChildren { new, i: 0 });
         return;
     }
 
@@ -312,15 +937,47 @@ fn diff_children(
         "all siblings must be keyed or all siblings must be non-keyed"
     );
 
-    if new_is_keyed && old_is_keyed {
-        let t = change_list.next_temporary();
-        diff_keyed_children(cached_set, change_list, registry, old, new, cached_roots);
-        change_list.set_next_temporary(t);
+    let mut keyed = new_is_keyed && old_is_keyed;
+
+    // Distinct values can hash to the same key, so siblings with hashed keys
+    // are checked for duplicates in release builds too. Rather than corrupt
+    // the DOM, colliding siblings are diffed as if they weren't keyed.
+    if keyed {
+        let hashed = old.iter().chain(new.iter()).any(|n| n.key().is_hashed());
+        if (hashed || cfg!(debug_assertions)) && !(keys_are_unique(&old) && keys_are_unique(&new)) {
+            debug_assert!(hashed, "keyed siblings must each have a unique key");
+            warn!("diff_children: two hashed keys collided; diffing these siblings as non-keyed");
+            keyed = false;
+        }
+    }
+
+    if keyed {
+        work.push(Work::SetNextTemporary(change_list.next_temporary()));
+        //     [... parent]
+        change_list.go_down();
+        //     [... parent child]
+        work.push(Work::KeyedPrefix { old, new, i: 0 });
     } else {
-        diff_non_keyed_children(cached_set, change_list, registry, old, new, cached_roots);
+        //     [... parent]
+        change_list.go_down();
+        //     [... parent child]
+        work.push(Work::NonKeyedChildren { old, new, i: 0 });
     }
 }
 
+/// Do these keyed siblings each have a different key?
+fn keys_are_unique(children: &[Node]) -> bool {
+    let mut keys = FxHashSet::default();
+    children.iter().all(|child| {
+        let key = child.key();
+        debug_assert!(
+            key.is_some(),
+            "if any sibling is keyed, all siblings must be keyed"
+        );
+        keys.insert(key)
+    })
+}
+
 // Diffing "keyed" children.
 //
 // With keyed children, we care about whether we delete, move, or create nodes
@@ -336,52 +993,80 @@ fn diff_children(
 //
 // https://github.com/infernojs/inferno/blob/36fd96/packages/inferno/src/DOM/patching.ts#L530-L739
 //
-// When entering this function, the parent must be on top of the change list
-// stack:
+// First up, we diff all the nodes with the same key at the beginning of the
+// children, one at a time: this function diffs the `i`th child if it is still
+// part of that shared prefix, and pushes the rest of the prefix onto the `work`
+// stack. Once the prefix is done, the rest of the children are diffed.
 //
-//     [... parent]
+// When entering this function, the parent and the previous child must be on top
+// of the change list stack:
 //
-// Upon exiting, the change list stack is in the same state.
-fn diff_keyed_children(
-    cached_set: &CachedSet,
+//     [... parent prev_child]
+//
+// Once the work is done, the change list stack is:
+//
+//     [... parent]
+#[allow(clippy::too_many_arguments)]
+fn diff_keyed_prefix<'a>(
+    cached_set: &'a CachedSet,
     change_list: &mut ChangeListBuilder,
     registry: &mut EventsRegistry,
-    old: &[Node],
-    new: &[Node],
+    old: Cow<'a, [Node<'a>]>,
+    new: Cow<'a, [Node<'a>]>,
+    i: usize,
     cached_roots: &mut FxHashSet<CacheId>,
+    work: &mut Vec<Work<'a>>,
 ) {
-    if cfg!(debug_assertions) {
-        let mut keys = FxHashSet::default();
-        let mut assert_unique_keys = |children: &[Node]| {
-            keys.clear();
-            for child in children {
-                let key = child.key();
-                debug_assert!(
-                    key.is_some(),
-                    "if any sibling is keyed, all siblings must be keyed"
-                );
-                keys.insert(key);
-            }
-            debug_assert_eq!(
-                children.len(),
-                keys.len(),
-                "keyed siblings must each have a unique key"
-            );
-        };
-        assert_unique_keys(old);
-        assert_unique_keys(new);
+    if i < old.len() && i < new.len() && old[i].key() == new[i].key() {
+        change_list.go_to_sibling(i);
+        let (old_child, new_child) = (old[i].clone(), new[i].clone());
+        work.push(Work::KeyedPrefix { old, new, i: i + 1 });
+        diff_node(
+            cached_set,
+            change_list,
+            registry,
+            &old_child,
+            &new_child,
+            cached_roots,
+            work,
+        );
+        return;
     }
 
-    // First up, we diff all the nodes with the same key at the beginning of the
-    // children.
-    //
     // `shared_prefix_count` is the count of how many nodes at the start of
     // `new` and `old` share the same keys.
-    let shared_prefix_count =
-        match diff_keyed_prefix(cached_set, change_list, registry, old, new, cached_roots) {
-            KeyedPrefixResult::Finished => return,
-            KeyedPrefixResult::MoreWorkToDo(count) => count,
-        };
+    let shared_prefix_count = i;
+
+    // If that was all of the old children, then create and append the remaining
+    // new children and we're finished.
+    if shared_prefix_count == old.len() {
+        change_list.go_up();
+        // Avoid committing the traversal when there is nothing to append.
+        if shared_prefix_count < new.len() {
+            change_list.commit_traversal();
+            work.push(Work::AppendChildren {
+                new,
+                i: shared_prefix_count,
+            });
+        }
+        return;
+    }
+
+    // And if that was all of the new children, then remove all of the remaining
+    // old children and we're finished.
+    if shared_prefix_count == new.len() {
+        change_list.go_to_sibling(shared_prefix_count);
+        change_list.commit_traversal();
+        remove_self_and_next_siblings(
+            cached_set,
+            change_list,
+            registry,
+            &old[shared_prefix_count..],
+        );
+        return;
+    }
+
+    change_list.go_up();
 
     // Next, we find out how many of the nodes at the end of the children have
     // the same key. We do _not_ diff them yet, since we want to emit the change
@@ -416,86 +1101,18 @@ fn diff_keyed_children(
     );
 
     // Finally, diff the nodes at the end of `old` and `new` that share keys.
-    let old_suffix = &old[old_shared_suffix_start..];
-    let new_suffix = &new[new_shared_suffix_start..];
-    debug_assert_eq!(old_suffix.len(), new_suffix.len());
-    if !old_suffix.is_empty() {
-        diff_keyed_suffix(
-            cached_set,
-            change_list,
-            registry,
-            old_suffix,
-            new_suffix,
-            cached_roots,
-            new_shared_suffix_start,
-        );
-    }
-}
-
-enum KeyedPrefixResult {
-    // Fast path: we finished diffing all the children just by looking at the
-    // prefix of shared keys!
-    Finished,
-    // There is more diffing work to do. Here is a count of how many children at
-    // the beginning of `new` and `old` we already processed.
-    MoreWorkToDo(usize),
-}
-
-// Diff the prefix of children in `new` and `old` that share the same keys in
-// the same order.
-//
-// Upon entry of this function, the change list stack must be:
-//
-//     [... parent]
-//
-// Upon exit, the change list stack is the same.
-fn diff_keyed_prefix(
-    cached_set: &CachedSet,
-    change_list: &mut ChangeListBuilder,
-    registry: &mut EventsRegistry,
-    old: &[Node],
-    new: &[Node],
-    cached_roots: &mut FxHashSet<CacheId>,
-) -> KeyedPrefixResult {
-    change_list.go_down();
-    let mut shared_prefix_count = 0;
-
-    for (i, (old, new)) in old.iter().zip(new.iter()).enumerate() {
-        if old.key() != new.key() {
-            break;
-        }
-
-        change_list.go_to_sibling(i);
-        diff(cached_set, change_list, registry, old, new, cached_roots);
-        shared_prefix_count += 1;
-    }
-
-    // If that was all of the old children, then create and append the remaining
-    // new children and we're finished.
-    if shared_prefix_count == old.len() {
-        change_list.go_up();
-        change_list.commit_traversal();
-        create_and_append_children(
-            cached_set,
-            change_list,
-            registry,
-            &new[shared_prefix_count..],
-            cached_roots,
-        );
-        return KeyedPrefixResult::Finished;
-    }
-
-    // And if that was all of the new children, then remove all of the remaining
-    // old children and we're finished.
-    if shared_prefix_count == new.len() {
-        change_list.go_to_sibling(shared_prefix_count);
-        change_list.commit_traversal();
-        remove_self_and_next_siblings(change_list, registry, &old[shared_prefix_count..]);
-        return KeyedPrefixResult::Finished;
+    if shared_suffix_count > 0 {
+        // [... parent]
+        change_list.go_down();
+        // [... parent new_child]
+        work.push(Work::KeyedSuffix {
+            old,
+            new,
+            old_start: old_shared_suffix_start,
+            new_start: new_shared_suffix_start,
+            i: 0,
+        });
     }
-
-    change_list.go_up();
-    KeyedPrefixResult::MoreWorkToDo(shared_prefix_count)
 }
 
 // The most-general, expensive code path for keyed children diffing.
@@ -559,11 +1176,16 @@ fn diff_keyed_middle(
     if shared_suffix_count == 0 && shared_keys.is_empty() {
         if shared_prefix_count == 0 {
             change_list.commit_traversal();
-            remove_all_children(change_list, registry, old);
+            remove_all_children(cached_set, change_list, registry, old);
         } else {
             change_list.go_down_to_child(shared_prefix_count);
             change_list.commit_traversal();
-            remove_self_and_next_siblings(change_list, registry, &old[shared_prefix_count..]);
+            remove_self_and_next_siblings(
+                cached_set,
+                change_list,
+                registry,
+                &old[shared_prefix_count..],
+            );
         }
         create_and_append_children(cached_set, change_list, registry, new, cached_roots);
         return;
@@ -606,7 +1228,7 @@ fn diff_keyed_middle(
     let mut removed_count = 0;
     for (i, old_child) in old.iter().enumerate().rev() {
         if !shared_keys.contains(&old_child.key()) {
-            registry.remove_subtree(old_child);
+            remove_subtree(cached_set, change_list, registry, old_child);
             change_list.commit_traversal();
             change_list.remove_child(i + shared_prefix_count);
             removed_count += 1;
@@ -740,81 +1362,102 @@ fn diff_keyed_middle(
     // [... parent]
 }
 
-// Diff the suffix of keyed children that share the same keys in the same order.
+// Diff the suffix of keyed children that share the same keys in the same order,
+// which starts at `old_start` in `old` and `new_start` in `new`. This diffs the
+// suffix's `i`th child, and pushes the rest of the suffix onto the `work`
+// stack.
 //
-// The parent must be on the change list stack when we enter this function:
+// The parent and the previous child must be on the change list stack when we
+// enter this function:
 //
-//     [... parent]
+//     [... parent prev_child]
 //
-// When this function exits, the change list stack remains the same.
-fn diff_keyed_suffix(
-    cached_set: &CachedSet,
+// Once the work is done, the change list stack is:
+//
+//     [... parent]
+#[allow(clippy::too_many_arguments)]
+fn diff_keyed_suffix<'a>(
+    cached_set: &'a CachedSet,
     change_list: &mut ChangeListBuilder,
     registry: &mut EventsRegistry,
-    old: &[Node],
-    new: &[Node],
+    old: Cow<'a, [Node<'a>]>,
+    new: Cow<'a, [Node<'a>]>,
+    old_start: usize,
+    new_start: usize,
+    i: usize,
     cached_roots: &mut FxHashSet<CacheId>,
-    new_shared_suffix_start: usize,
+    work: &mut Vec<Work<'a>>,
 ) {
-    debug_assert_eq!(old.len(), new.len());
-    debug_assert!(!old.is_empty());
+    debug_assert_eq!(old.len() - old_start, new.len() - new_start);
 
-    // [... parent]
-    change_list.go_down();
-    // [... parent new_child]
-
-    for (i, (old_child, new_child)) in old.iter().zip(new.iter()).enumerate() {
-        change_list.go_to_sibling(new_shared_suffix_start + i);
-        diff(
-            cached_set,
-            change_list,
-            registry,
-            old_child,
-            new_child,
-            cached_roots,
-        );
+    if old_start + i == old.len() {
+        // [... parent]
+        change_list.go_up();
+        return;
     }
 
-    // [... parent]
-    change_list.go_up();
+    change_list.go_to_sibling(new_start + i);
+    let (old_child, new_child) = (old[old_start + i].clone(), new[new_start + i].clone());
+    work.push(Work::KeyedSuffix {
+        old,
+        new,
+        old_start,
+        new_start,
+        i: i + 1,
+    });
+    diff_node(
+        cached_set,
+        change_list,
+        registry,
+        &old_child,
+        &new_child,
+        cached_roots,
+        work,
+    );
 }
 
-// Diff children that are not keyed.
+// Diff children that are not keyed. This diffs the `i`th child, and pushes the
+// rest of the children onto the `work` stack.
 //
-// The parent must be on the top of the change list stack when entering this
-// function:
+// The parent and the previous child must be on the top of the change list stack
+// when entering this function:
 //
-//     [... parent]
+//     [... parent prev_child]
 //
-// the change list stack is in the same state when this function returns.
-fn diff_non_keyed_children(
-    cached_set: &CachedSet,
+// Once the work is done, the change list stack is:
+//
+//     [... parent]
+#[allow(clippy::too_many_arguments)]
+fn diff_non_keyed_children<'a>(
+    cached_set: &'a CachedSet,
     change_list: &mut ChangeListBuilder,
     registry: &mut EventsRegistry,
-    old: &[Node],
-    new: &[Node],
+    old: Cow<'a, [Node<'a>]>,
+    new: Cow<'a, [Node<'a>]>,
+    i: usize,
     cached_roots: &mut FxHashSet<CacheId>,
+    work: &mut Vec<Work<'a>>,
 ) {
-    // Handled these cases in `diff_children` before calling this function.
+    // Handled these cases in `diff_children` before getting here.
     debug_assert!(!new.is_empty());
     debug_assert!(!old.is_empty());
 
-    //     [... parent]
-    change_list.go_down();
-    //     [... parent child]
-
-    for (i, (new_child, old_child)) in new.iter().zip(old.iter()).enumerate() {
+    if i < old.len() && i < new.len() {
         // [... parent prev_child]
         change_list.go_to_sibling(i);
         // [... parent this_child]
-        diff(
+        let (old_child, new_child) = (old[i].clone(), new[i].clone());
+        work.push(Work::NonKeyedChildren { old, new, i: i + 1 });
+        diff_node(
             cached_set,
             change_list,
             registry,
-            old_child,
-            new_child,
+            &old_child,
+            &new_child,
             cached_roots,
+            work,
         );
+        return;
     }
 
     match old.len().cmp(&new.len()) {
@@ -823,7 +1466,7 @@ fn diff_non_keyed_children(
             change_list.go_to_sibling(new.len());
             // [... parent first_child_to_remove]
             change_list.commit_traversal();
-            remove_self_and_next_siblings(change_list, registry, &old[new.len()..]);
+            remove_self_and_next_siblings(cached_set, change_list, registry, &old[new.len()..]);
             // [... parent]
         }
         Ordering::Less => {
@@ -831,13 +1474,7 @@ fn diff_non_keyed_children(
             change_list.go_up();
             // [... parent]
             change_list.commit_traversal();
-            create_and_append_children(
-                cached_set,
-                change_list,
-                registry,
-                &new[old.len()..],
-                cached_roots,
-            );
+            work.push(Work::AppendChildren { new, i: old.len() });
         }
         Ordering::Equal => {
             // [... parent child]
@@ -854,7 +1491,7 @@ fn diff_non_keyed_children(
 //     [... parent]
 //
 // When this function returns, the change list stack is in the same state.
-fn create_and_append_children(
+pub(crate) fn create_and_append_children(
     cached_set: &CachedSet,
     change_list: &mut ChangeListBuilder,
     registry: &mut EventsRegistry,
@@ -862,12 +1499,90 @@ fn create_and_append_children(
     cached_roots: &mut FxHashSet<CacheId>,
 ) {
     debug_assert!(change_list.traversal_is_committed());
-    for child in new {
+    let new = flatten_children(cached_set, new, &mut |id| {
+        cached_roots.insert(id);
+    });
+    for child in new.iter() {
         create(cached_set, change_list, registry, child, cached_roots);
         change_list.append_child();
     }
 }
 
+// Flatten any fragments among `children` into their own children, so that the
+// resulting nodes correspond one-to-one with the parent's physical DOM
+// children.
+//
+// Cached nodes that render fragments are flattened as well, and `on_cached` is
+// invoked with each of their ids.
+pub(crate) fn flatten_children<'a>(
+    cached_set: &'a CachedSet,
+    children: &'a [Node<'a>],
+    on_cached: &mut dyn FnMut(CacheId),
+) -> Cow<'a, [Node<'a>]> {
+    // Only copy the children once the first fragment turns up, so that the
+    // common case of no fragments doesn't allocate.
+    let mut flattened: Option<Vec<Node<'a>>> = None;
+    for (i, child) in children.iter().enumerate() {
+        match fragment_children(cached_set, child, on_cached) {
+            Some(fragment) => {
+                let flattened = flattened.get_or_insert_with(|| {
+                    let mut flattened = Vec::with_capacity(children.len());
+                    flattened.extend_from_slice(&children[..i]);
+                    flattened
+                });
+                flatten_into(cached_set, fragment, on_cached, flattened);
+            }
+            None => {
+                if let Some(flattened) = &mut flattened {
+                    flattened.push(child.clone());
+                }
+            }
+        }
+    }
+
+    match flattened {
+        Some(flattened) => Cow::Owned(flattened),
+        None => Cow::Borrowed(children),
+    }
+}
+
+fn flatten_into<'a>(
+    cached_set: &'a CachedSet,
+    children: &'a [Node<'a>],
+    on_cached: &mut dyn FnMut(CacheId),
+    flattened: &mut Vec<Node<'a>>,
+) {
+    for child in children {
+        match fragment_children(cached_set, child, on_cached) {
+            Some(children) => flatten_into(cached_set, children, on_cached, flattened),
+            None => flattened.push(child.clone()),
+        }
+    }
+}
+
+// If `node` is a fragment, or a cached node that renders a fragment, get the
+// fragment's children.
+fn fragment_children<'a>(
+    cached_set: &'a CachedSet,
+    node: &'a Node<'a>,
+    on_cached: &mut dyn FnMut(CacheId),
+) -> Option<&'a [Node<'a>]> {
+    match node.kind {
+        NodeKind::Fragment(FragmentNode { children }) => Some(children),
+        NodeKind::Cached(ref c) => {
+            let (cached, _template) = cached_set.get(c.id);
+            let children = fragment_children(cached_set, cached, on_cached)?;
+            on_cached(c.id);
+            Some(children)
+        }
+        NodeKind::Text(_)
+        | NodeKind::Element(_)
+        | NodeKind::Empty
+        | NodeKind::RawHtml(_)
+        | NodeKind::Portal(_) => None,
+    }
+}
+
 // Remove all of a node's children.
 //
 // The change list stack must have this shape upon entry to this function:
@@ -876,13 +1591,14 @@ fn create_and_append_children(
 //
 // When this function returns, the change list stack is in the same state.
 fn remove_all_children(
+    cached_set: &CachedSet,
     change_list: &mut ChangeListBuilder,
     registry: &mut EventsRegistry,
     old: &[Node],
 ) {
     debug_assert!(change_list.traversal_is_committed());
     for child in old {
-        registry.remove_subtree(child);
+        remove_subtree(cached_set, change_list, registry, child);
     }
     // Fast way to remove all children: set the node's textContent to an empty
     // string.
@@ -899,13 +1615,14 @@ fn remove_all_children(
 //
 //     [... parent]
 fn remove_self_and_next_siblings(
+    cached_set: &CachedSet,
     change_list: &mut ChangeListBuilder,
     registry: &mut EventsRegistry,
     old: &[Node],
 ) {
     debug_assert!(change_list.traversal_is_committed());
     for child in old {
-        registry.remove_subtree(child);
+        remove_subtree(cached_set, change_list, registry, child);
     }
     change_list.remove_self_and_next_siblings();
 }
@@ -919,7 +1636,7 @@ fn remove_self_and_next_siblings(
 // When this function returns, the new node is on top of the change list stack:
 //
 //     [... node]
-fn create(
+pub(crate) fn create(
     cached_set: &CachedSet,
     change_list: &mut ChangeListBuilder,
     registry: &mut EventsRegistry,
@@ -928,16 +1645,38 @@ fn create(
 ) {
     debug_assert!(change_list.traversal_is_committed());
     match node.kind {
+        NodeKind::Fragment(_) => {
+            unreachable!("fragments are flattened into their parent's children")
+        }
         NodeKind::Text(TextNode { text }) => {
             change_list.create_text_node(text);
         }
+        NodeKind::Empty => {
+            change_list.create_comment();
+        }
+        NodeKind::RawHtml(RawHtmlNode { html }) => {
+            change_list.create_raw_html(html);
+        }
+        NodeKind::Portal(ref portal) => {
+            change_list.create_comment();
+            // Templates are cloned, so their portals' children are only
+            // created once the clone is diffed against the template.
+            if !change_list.is_building_template() {
+                create_portal_children(cached_set, change_list, registry, portal, cached_roots);
+            }
+        }
         NodeKind::Element(&ElementNode {
             key: _,
             tag_name,
             listeners,
             attributes,
+            properties,
+            styles,
+            classes,
             children,
             namespace,
+            node_ref,
+            lifecycle,
         }) => {
             if let Some(namespace) = namespace {
                 change_list.create_element_ns(tag_name, namespace);
@@ -956,6 +1695,28 @@ fn create(
                 change_list.set_attribute(&attr.name, &attr.value, namespace.is_some());
             }
 
+            for prop in properties {
+                change_list.set_property(prop.name, prop.value);
+            }
+
+            for style in styles {
+                change_list.set_style(style.name, style.value);
+            }
+
+            for class in classes {
+                change_list.add_class(class);
+            }
+
+            if !change_list.is_building_template() {
+                if let Some(node_ref) = node_ref {
+                    change_list.set_node_ref(node_ref);
+                }
+                if let Some(lifecycle) = lifecycle {
+                    // The new nodes outlive this change list.
+                    unsafe { change_list.track_lifecycle(lifecycle, true) };
+                }
+            }
+
             // Fast path: if there is a single text child, it is faster to
             // create-and-append the text node all at once via setting the
             // parent's `textContent` in a single change list instruction than
@@ -972,10 +1733,7 @@ fn create(
                 }
             }
 
-            for child in children {
-                create(cached_set, change_list, registry, child, cached_roots);
-                change_list.append_child();
-            }
+            create_and_append_children(cached_set, change_list, registry, children, cached_roots);
         }
         NodeKind::Cached(ref c) => {
             cached_roots.insert(c.id);
@@ -1032,7 +1790,9 @@ fn get_or_create_template<'a>(
         (template, true)
     } else {
         // [...]
+        let building = change_list.push_building_template();
         create(cached_set, change_list, registry, template, cached_roots);
+        change_list.pop_building_template(building);
         // [... template]
         change_list.save_template(template_id);
         // [... template]
@@ -1062,6 +1822,7 @@ fn create_and_replace(
             old_forcing = Some(change_list.push_force_new_listeners());
         }
 
+        let diffing = change_list.push_diffing_template();
         diff(
             cached_set,
             change_list,
@@ -1070,6 +1831,7 @@ fn create_and_replace(
             new,
             cached_roots,
         );
+        change_list.pop_diffing_template(diffing);
 
         if let Some(old) = old_forcing {
             change_list.pop_force_new_listeners(old);
@@ -1080,7 +1842,7 @@ fn create_and_replace(
         create(cached_set, change_list, registry, new, cached_roots);
         change_list.replace_with();
     }
-    registry.remove_subtree(old);
+    remove_subtree(cached_set, change_list, registry, old);
 }
 
 fn create_with_template(
@@ -1107,6 +1869,7 @@ fn create_with_template(
         old_forcing = Some(change_list.push_force_new_listeners());
     }
 
+    let diffing = change_list.push_diffing_template();
     diff(
         cached_set,
         change_list,
@@ -1115,6 +1878,7 @@ fn create_with_template(
         node,
         cached_roots,
     );
+    change_list.pop_diffing_template(diffing);
 
     if let Some(old) = old_forcing {
         change_list.pop_force_new_listeners(old);
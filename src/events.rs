@@ -15,12 +15,21 @@ cfg_if::cfg_if! {
             }
             pub(crate) fn remove(&mut self, _listener: &Listener) {}
             pub(crate) fn remove_subtree(&mut self, _node: &Node) {}
+            pub(crate) fn purge_subtree(&mut self, _node: &Node) {}
             pub(crate) unsafe fn add<'a>(&mut self, _listener: &'a Listener<'a>) {}
             pub(crate) fn clear_active_listeners(&mut self) {}
+            pub(crate) fn commit_removals(&mut self) {}
+            pub(crate) fn dispatch(
+                _registry: &Rc<RefCell<EventsRegistry>>,
+                _event: web_sys::Event,
+                _a: u32,
+                _b: u32,
+            ) {
+            }
         }
     } else {
         use crate::{
-            node::{ElementNode, ListenerCallback, NodeKind},
+            node::{ElementNode, FragmentNode, ListenerCallback, NodeKind, PortalNode},
             vdom::VdomWeak,
         };
         use fxhash::FxHashMap;
@@ -35,7 +44,19 @@ cfg_if::cfg_if! {
         /// buffering.
         pub(crate) struct EventsRegistry {
             vdom: Weak<VdomInner>,
-            active: FxHashMap<(u32, u32), ListenerCallback<'static>>,
+            active: FxHashMap<(u32, u32), ActiveListener>,
+            // The listeners that the render in progress removed. The physical
+            // DOM still has them until the render's changes are applied, so
+            // they stay active until then.
+            removed: Vec<(u32, u32)>,
+        }
+
+        /// A registered listener's callback, and how many times it is
+        /// registered. The same listener is registered more than once when it
+        /// is part of a cached subtree that is rendered in multiple places.
+        struct ActiveListener {
+            callback: ListenerCallback<'static>,
+            count: usize,
         }
 
         impl fmt::Debug for EventsRegistry {
@@ -59,58 +80,91 @@ cfg_if::cfg_if! {
                 let registry = Rc::new(RefCell::new(EventsRegistry {
                     vdom,
                     active: FxHashMap::default(),
+                    removed: vec![],
                 }));
 
                 let weak_registry = Rc::downgrade(&registry);
                 let closure = Closure::wrap(Box::new(move |event, a, b| {
-                    debug_assert!(a != 0);
-
                     // if the VdomInnerExclusive is keeping this closure alive, then the
                     // VdomInnerExclusive should also be keeping the registry alive
                     let registry = weak_registry.upgrade().unwrap_throw();
-                    let registry = registry.borrow();
+                    EventsRegistry::dispatch(&registry, event, a, b);
+                }) as Box<dyn Fn(web_sys::Event, u32, u32)>);
+
+                (registry, closure)
+            }
 
+            /// Invoke the listener with the given id parts, if it is still
+            /// active.
+            pub(crate) fn dispatch(
+                registry: &Rc<RefCell<EventsRegistry>>,
+                event: web_sys::Event,
+                a: u32,
+                b: u32,
+            ) {
+                // Copy the callback out, so that the registry isn't borrowed
+                // while finishing an incremental render modifies it.
+                let (callback, vdom) = {
+                    let registry = registry.borrow();
                     match registry.active.get(&(a, b)) {
-                        None => warn!(
-                            "EventsRegistry closure invoked with unknown listener parts: \
-                             (0x{:x}, 0x{:x})",
-                            a, b
-                        ),
-                        Some(callback) => {
-                            let vdom = registry.vdom.upgrade().expect_throw(
-                                "if the registry is still around, then the vdom should still be around",
+                        None => {
+                            warn!(
+                                "EventsRegistry closure invoked with unknown listener parts: \
+                                 (0x{:x}, 0x{:x})",
+                                a, b
                             );
-                            let vdom_weak = VdomWeak::new(&vdom);
-                            let mut vdom = vdom.exclusive.borrow_mut();
-                            let component = vdom.component_raw_mut();
-                            callback(component, vdom_weak, event);
+                            return;
                         }
+                        Some(active) => (active.callback, registry.vdom.clone()),
                     }
-                }) as Box<dyn Fn(web_sys::Event, u32, u32)>);
+                };
 
-                (registry, closure)
+                let vdom = vdom.upgrade().expect_throw(
+                    "if the registry is still around, then the vdom should still be around",
+                );
+                let vdom_weak = VdomWeak::new(&vdom);
+                let mut vdom = vdom.exclusive.borrow_mut();
+                // The render may remove this listener, but its callback lives
+                // on until the next render starts.
+                vdom.finish_render();
+                let component = vdom.component_raw_mut();
+                callback.call(component, vdom_weak, event);
             }
 
+            /// Remove one registration of the given listener, and deactivate it
+            /// if that was its last, once the render's changes are applied.
             pub(crate) fn remove(&mut self, listener: &Listener) {
-                let id = listener.get_callback_parts();
-                debug_assert!(id.0 != 0);
-                self.active.remove(&id);
+                self.removed.push(listener.id_parts());
             }
 
-            pub(crate) fn remove_subtree(&mut self, node: &Node) {
-                match node.kind {
-                    NodeKind::Cached(_) | NodeKind::Text(_) => {},
-                    NodeKind::Element(&ElementNode {listeners, children, ..}) => {
-                        for l in listeners {
-                            self.remove(l);
-                        }
-                        for child in children {
-                            self.remove_subtree(child)
+            /// Apply the removals of the render whose changes were just
+            /// applied.
+            pub(crate) fn commit_removals(&mut self) {
+                for id in self.removed.drain(..) {
+                    if let Some(active) = self.active.get_mut(&id) {
+                        active.count -= 1;
+                        if active.count == 0 {
+                            self.active.remove(&id);
                         }
                     }
                 }
             }
 
+            /// Remove one registration of each listener in the given subtree.
+            pub(crate) fn remove_subtree(&mut self, node: &Node) {
+                for_each_listener(node, &mut |l| self.remove(l));
+            }
+
+            /// Deactivate every listener in the given subtree, no matter how
+            /// many times they are registered. This is for when the subtree
+            /// itself is about to be dropped, such as when a cached subtree is
+            /// garbage collected.
+            pub(crate) fn purge_subtree(&mut self, node: &Node) {
+                for_each_listener(node, &mut |l| {
+                    self.active.remove(&l.id_parts());
+                });
+            }
+
             /// Add an event listener to the registry, exposing to JS.
             ///
             /// # Unsafety
@@ -120,19 +174,110 @@ cfg_if::cfg_if! {
             /// in the registry after it is dropped. This is maintained during
             /// diffing.
             pub(crate) unsafe fn add<'a>(&mut self, listener: &'a Listener<'a>) {
-                let id = listener.get_callback_parts();
-                debug_assert!(id.0 != 0);
-
                 let callback =
                     mem::transmute::<ListenerCallback<'a>, ListenerCallback<'static>>(listener.callback);
-                let old = self.active.insert(id, callback);
-                debug_assert!(old.is_none());
+                self.active
+                    .entry(listener.id_parts())
+                    .or_insert(ActiveListener { callback, count: 0 })
+                    .count += 1;
             }
 
             /// Clear all event listeners from the registry.
             pub(crate) fn clear_active_listeners(&mut self) {
                 self.active.clear();
+                self.removed.clear();
             }
         }
+
+        // Call `f` with each listener in the given subtree. Cached nodes are
+        // not descended into, since their listeners belong to the cached set.
+        fn for_each_listener<'a>(node: &Node<'a>, f: &mut dyn FnMut(&Listener<'a>)) {
+            match node.kind {
+                NodeKind::Cached(_)
+                | NodeKind::Text(_)
+                | NodeKind::Empty
+                | NodeKind::RawHtml(_) => {},
+                NodeKind::Element(&ElementNode {listeners, children, ..}) => {
+                    for l in listeners {
+                        f(l);
+                    }
+                    for child in children {
+                        for_each_listener(child, f);
+                    }
+                }
+                NodeKind::Fragment(FragmentNode { children })
+                | NodeKind::Portal(PortalNode { children, .. }) => {
+                    for child in children {
+                        for_each_listener(child, f);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "xxx-unstable-internal-use-only")))]
+mod tests {
+    use super::*;
+    use crate::builder::on;
+    use bumpalo::Bump;
+
+    fn registry() -> EventsRegistry {
+        EventsRegistry {
+            vdom: Weak::new(),
+            active: Default::default(),
+            removed: vec![],
+        }
+    }
+
+    #[test]
+    fn identical_closures_get_unique_ids() {
+        let bump = Bump::new();
+        let a = on(&bump, "click", |_root, _vdom, _event| {});
+        let b = on(&bump, "click", |_root, _vdom, _event| {});
+        assert_ne!(a.id_parts(), b.id_parts());
+
+        let mut registry = registry();
+        unsafe {
+            registry.add(&a);
+            registry.add(&b);
+        }
+        registry.remove(&a);
+        registry.commit_removals();
+        assert!(!registry.active.contains_key(&a.id_parts()));
+        assert!(registry.active.contains_key(&b.id_parts()));
+    }
+
+    #[test]
+    fn listeners_are_reference_counted() {
+        let bump = Bump::new();
+        let listener = on(&bump, "click", |_root, _vdom, _event| {});
+
+        let mut registry = registry();
+        unsafe {
+            registry.add(&listener);
+            registry.add(&listener);
+        }
+        registry.remove(&listener);
+        registry.commit_removals();
+        assert!(registry.active.contains_key(&listener.id_parts()));
+        registry.remove(&listener);
+        registry.commit_removals();
+        assert!(!registry.active.contains_key(&listener.id_parts()));
+    }
+
+    #[test]
+    fn removals_wait_for_the_render_to_be_applied() {
+        let bump = Bump::new();
+        let listener = on(&bump, "click", |_root, _vdom, _event| {});
+
+        let mut registry = registry();
+        unsafe {
+            registry.add(&listener);
+        }
+        registry.remove(&listener);
+        assert!(registry.active.contains_key(&listener.id_parts()));
+        registry.commit_removals();
+        assert!(!registry.active.contains_key(&listener.id_parts()));
     }
 }
@@ -0,0 +1,587 @@
+//! Hydrating server-rendered markup.
+//!
+//! Rather than emptying the container and building the physical DOM from
+//! scratch, hydration walks the physical DOM that is already inside the
+//! container (typically produced by `dodrio::ssr`) alongside the first rendered
+//! virtual DOM. Physical nodes that match are adopted as they are and only get
+//! their event listeners attached. Anything that doesn't match is reported with
+//! a warning and patched through the change list, just like diffing would.
+
+use crate::{
+    cached_set::{CacheId, CachedSet},
+    change_list::ChangeListBuilder,
+    events::EventsRegistry,
+    node::Node,
+};
+use fxhash::FxHashSet;
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "xxx-unstable-internal-use-only", not(target_arch = "wasm32")))] {
+        pub(crate) fn hydrate_container(
+            _cached_set: &CachedSet,
+            _change_list: &mut ChangeListBuilder,
+            _registry: &mut EventsRegistry,
+            _container: &crate::Element,
+            _node: &Node,
+            _cached_roots: &mut FxHashSet<CacheId>,
+        ) {
+            unreachable!("the in-memory DOM is never hydrated")
+        }
+
+        // Hydration reads the physical DOM through `web_sys`, so the in-memory
+        // DOM is always initialized from scratch instead.
+        pub(crate) fn initialize_container_for_hydration(_container: &crate::Element) -> bool {
+            false
+        }
+    } else {
+        use crate::node::{ElementNode, NodeKind, TextNode};
+        use std::slice;
+        use wasm_bindgen::{JsCast, UnwrapThrowExt};
+
+        const HTML_NAMESPACE: &str = "http://www.w3.org/1999/xhtml";
+
+        // Prepare the container's server-rendered contents for hydration.
+        //
+        // Whitespace and comments around the root nodes are left over from the
+        // page's markup rather than rendered by us, so they are removed. Empty
+        // comments are kept, since they are the placeholders that we render for
+        // empty nodes.
+        //
+        // Returns `false` if there is nothing to hydrate, in which case the
+        // caller should initialize the container from scratch instead.
+        pub(crate) fn initialize_container_for_hydration(container: &crate::Element) -> bool {
+            let mut child = container.first_child();
+            while let Some(node) = child {
+                child = node.next_sibling();
+                let insignificant = match node.node_type() {
+                    web_sys::Node::COMMENT_NODE => {
+                        !node.text_content().unwrap_or_default().is_empty()
+                    }
+                    web_sys::Node::TEXT_NODE => {
+                        node.text_content().unwrap_or_default().trim().is_empty()
+                    }
+                    _ => false,
+                };
+                if insignificant {
+                    container
+                        .remove_child(&node)
+                        .expect_throw("should remove child OK");
+                }
+            }
+
+            if container.first_child().is_none() {
+                warn!("hydrate: container is empty; rendering from scratch");
+                return false;
+            }
+
+            true
+        }
+
+        // Hydrate the container's physical children as the given virtual root
+        // node. The root may be a fragment, in which case the container has one
+        // physical child per flattened child of the fragment.
+        //
+        // The change list interpreter starts with the container on the top of its
+        // stack, so no traversal is needed to get there.
+        pub(crate) fn hydrate_container(
+            cached_set: &CachedSet,
+            change_list: &mut ChangeListBuilder,
+            registry: &mut EventsRegistry,
+            container: &crate::Element,
+            node: &Node,
+            cached_roots: &mut FxHashSet<CacheId>,
+        ) {
+            hydrate_children(
+                cached_set,
+                change_list,
+                registry,
+                container,
+                slice::from_ref(node),
+                cached_roots,
+            );
+        }
+
+        // Hydrate the `physical` DOM node as the virtual `node`, attaching event
+        // listeners and patching anything that doesn't match.
+        //
+        // Upon entry to this function, the physical DOM node must be on the top
+        // of the change list stack:
+        //
+        //     [... node]
+        //
+        // The change list stack is in the same state when this function exits.
+        fn hydrate(
+            cached_set: &CachedSet,
+            change_list: &mut ChangeListBuilder,
+            registry: &mut EventsRegistry,
+            physical: &web_sys::Node,
+            node: &Node,
+            cached_roots: &mut FxHashSet<CacheId>,
+        ) {
+            match node.kind {
+                NodeKind::Text(TextNode { text }) => {
+                    if physical.node_type() != web_sys::Node::TEXT_NODE {
+                        replace(cached_set, change_list, registry, physical, node, cached_roots);
+                        return;
+                    }
+                    if physical.text_content().as_deref() != Some(text) {
+                        warn!(
+                            "hydrate: text mismatch: expected {:?}, found {:?}",
+                            text,
+                            physical.text_content()
+                        );
+                        change_list.commit_traversal();
+                        change_list.set_text(text);
+                    }
+                }
+
+                NodeKind::Element(element) => match physical.dyn_ref::<web_sys::Element>() {
+                    Some(physical) if is_same_element(physical, element) => {
+                        hydrate_element(
+                            cached_set,
+                            change_list,
+                            registry,
+                            physical,
+                            element,
+                            cached_roots,
+                        );
+                    }
+                    _ => replace(cached_set, change_list, registry, physical, node, cached_roots),
+                },
+
+                NodeKind::Empty => {
+                    if physical.node_type() != web_sys::Node::COMMENT_NODE {
+                        replace(cached_set, change_list, registry, physical, node, cached_roots);
+                    }
+                }
+
+                // Only the portal's placeholder is rendered on the server, so
+                // its children are created afresh.
+                NodeKind::Portal(ref portal) => {
+                    if physical.node_type() != web_sys::Node::COMMENT_NODE {
+                        replace(cached_set, change_list, registry, physical, node, cached_roots);
+                        return;
+                    }
+                    change_list.commit_traversal();
+                    crate::diff::create_portal_children(
+                        cached_set,
+                        change_list,
+                        registry,
+                        portal,
+                        cached_roots,
+                    );
+                }
+
+                // The markup is trusted to be what the server rendered inside
+                // its wrapper, since the parsed physical nodes can't be
+                // compared with it reliably.
+                NodeKind::RawHtml(_) => {
+                    let is_wrapper = matches!(
+                        physical.dyn_ref::<web_sys::Element>(),
+                        Some(el) if el.namespace_uri().as_deref() == Some(HTML_NAMESPACE)
+                            && el.local_name() == "div"
+                    );
+                    if !is_wrapper {
+                        replace(cached_set, change_list, registry, physical, node, cached_roots);
+                    }
+                }
+
+                NodeKind::Cached(ref c) => {
+                    cached_roots.insert(c.id);
+                    let (node, _template) = cached_set.get(c.id);
+                    hydrate(cached_set, change_list, registry, physical, node, cached_roots);
+                }
+
+                NodeKind::Fragment(_) => {
+                    unreachable!("fragments are flattened into their parent's children")
+                }
+            }
+        }
+
+        fn is_same_element(physical: &web_sys::Element, element: &ElementNode) -> bool {
+            let namespace = element.namespace.unwrap_or(HTML_NAMESPACE);
+            if physical.namespace_uri().as_deref() != Some(namespace) {
+                return false;
+            }
+
+            // The HTML parser lowercases the names of HTML elements.
+            let local_name = physical.local_name();
+            if namespace == HTML_NAMESPACE {
+                local_name.eq_ignore_ascii_case(element.tag_name)
+            } else {
+                local_name == element.tag_name
+            }
+        }
+
+        // Replace a mismatched physical node with a freshly created one.
+        //
+        // Upon entry to this function, the physical DOM node must be on the top
+        // of the change list stack:
+        //
+        //     [... physical]
+        //
+        // When this function returns, the new node has taken its place:
+        //
+        //     [... node]
+        fn replace(
+            cached_set: &CachedSet,
+            change_list: &mut ChangeListBuilder,
+            registry: &mut EventsRegistry,
+            physical: &web_sys::Node,
+            node: &Node,
+            cached_roots: &mut FxHashSet<CacheId>,
+        ) {
+            warn!(
+                "hydrate: node mismatch: expected {:?}, found {:?}",
+                node,
+                physical.node_name()
+            );
+            change_list.commit_traversal();
+            crate::diff::create(cached_set, change_list, registry, node, cached_roots);
+            change_list.replace_with();
+        }
+
+        // Hydrate an element whose tag name and namespace already match.
+        //
+        // Upon entry to this function, the physical element must be on the top
+        // of the change list stack:
+        //
+        //     [... element]
+        //
+        // The change list stack is in the same state when this function exits.
+        fn hydrate_element(
+            cached_set: &CachedSet,
+            change_list: &mut ChangeListBuilder,
+            registry: &mut EventsRegistry,
+            physical: &web_sys::Element,
+            element: &ElementNode,
+            cached_roots: &mut FxHashSet<CacheId>,
+        ) {
+            // Listeners are never part of the server-rendered markup, so they
+            // always need to be attached.
+            for l in element.listeners {
+                unsafe {
+                    registry.add(l);
+                }
+                change_list.commit_traversal();
+                change_list.new_event_listener(l);
+            }
+
+            hydrate_attributes(change_list, physical, element);
+            hydrate_styles(change_list, physical, element);
+            hydrate_classes(change_list, physical, element.classes);
+
+            // Nor are properties, so set them all too.
+            for prop in element.properties {
+                change_list.commit_traversal();
+                change_list.set_property(prop.name, prop.value);
+            }
+
+            // The physical element is already at hand, so there's no need to
+            // go through the change list to fill in the node ref.
+            if let Some(node_ref) = element.node_ref {
+                node_ref.set(Some(physical.clone()));
+            }
+            if let Some(lifecycle) = element.lifecycle {
+                // The new nodes outlive this change list.
+                unsafe { change_list.track_lifecycle_element(lifecycle, physical.clone()) };
+            }
+
+            hydrate_children(
+                cached_set,
+                change_list,
+                registry,
+                physical,
+                element.children,
+                cached_roots,
+            );
+        }
+
+        // Patch any attributes whose physical value doesn't match the virtual
+        // one, and remove any physical attributes that aren't rendered.
+        //
+        // The element must be on the top of the change list stack:
+        //
+        //     [... element]
+        fn hydrate_attributes(
+            change_list: &mut ChangeListBuilder,
+            physical: &web_sys::Element,
+            element: &ElementNode,
+        ) {
+            let attributes = element.attributes;
+            let is_namespaced = element.namespace.is_some();
+            for attr in attributes {
+                // A `style` attribute is hydrated along with the inline styles.
+                if attr.name == "style" && !element.styles.is_empty() {
+                    continue;
+                }
+
+                let value = physical.get_attribute(attr.name);
+                if value.as_deref() != Some(attr.value) {
+                    warn!(
+                        "hydrate: attribute mismatch on <{}>: expected {}={:?}, found {:?}",
+                        physical.local_name(),
+                        attr.name,
+                        attr.value,
+                        value
+                    );
+                    change_list.commit_traversal();
+                    change_list.set_attribute(attr.name, attr.value, is_namespaced);
+                }
+            }
+
+            for name in physical.get_attribute_names().iter() {
+                let name = name.as_string().unwrap_throw();
+
+                // Server-side rendering declares the namespace of namespaced
+                // elements, but it isn't an attribute that we render.
+                if name == "xmlns" || attributes.iter().any(|attr| attr.name == name) {
+                    continue;
+                }
+
+                // Inline styles and class list tokens are hydrated separately.
+                if (name == "style" && !element.styles.is_empty())
+                    || (name == "class" && !element.classes.is_empty())
+                {
+                    continue;
+                }
+
+                warn!(
+                    "hydrate: removing unexpected attribute on <{}>: {:?}",
+                    physical.local_name(),
+                    name
+                );
+                change_list.commit_traversal();
+                change_list.remove_attribute(&name);
+            }
+        }
+
+        // Add any class list tokens that are missing from the physical element.
+        // Other tokens are left alone, just like when diffing.
+        //
+        // The element must be on the top of the change list stack:
+        //
+        //     [... element]
+        fn hydrate_classes(
+            change_list: &mut ChangeListBuilder,
+            physical: &web_sys::Element,
+            classes: &[&str],
+        ) {
+            if classes.is_empty() {
+                return;
+            }
+
+            let value = physical.get_attribute("class").unwrap_or_default();
+            for class in classes {
+                if value.split_whitespace().all(|c| c != *class) {
+                    warn!(
+                        "hydrate: class mismatch on <{}>: expected {:?} in {:?}",
+                        physical.local_name(),
+                        class,
+                        value
+                    );
+                    change_list.commit_traversal();
+                    change_list.add_class(class);
+                }
+            }
+        }
+
+        // Check that the physical `style` attribute is exactly what server-side
+        // rendering would have written for the given styles, and if it isn't,
+        // replace it with the `style` attribute and the styles one property at
+        // a time.
+        //
+        // The element must be on the top of the change list stack:
+        //
+        //     [... element]
+        fn hydrate_styles(
+            change_list: &mut ChangeListBuilder,
+            physical: &web_sys::Element,
+            element: &ElementNode,
+        ) {
+            let styles = element.styles;
+            if styles.is_empty() {
+                return;
+            }
+
+            let style_attribute = element
+                .attributes
+                .iter()
+                .find(|attr| attr.name == "style")
+                .map(|attr| attr.value);
+            let expected = style_attribute
+                .into_iter()
+                .map(str::to_string)
+                .chain(
+                    styles
+                        .iter()
+                        .map(|style| format!("{}: {}", style.name, style.value)),
+                )
+                .collect::<Vec<_>>()
+                .join("; ");
+            let value = physical.get_attribute("style");
+            if value.as_deref() == Some(&expected[..]) {
+                return;
+            }
+
+            warn!(
+                "hydrate: style mismatch on <{}>: expected {:?}, found {:?}",
+                physical.local_name(),
+                expected,
+                value
+            );
+            change_list.commit_traversal();
+            match style_attribute {
+                Some(value) => {
+                    change_list.set_attribute("style", value, element.namespace.is_some())
+                }
+                None => change_list.remove_attribute("style"),
+            }
+            for style in styles {
+                change_list.set_style(style.name, style.value);
+            }
+        }
+
+        // Hydrate an element's physical children as the given virtual children.
+        //
+        // The parent must be on the top of the change list stack when entering
+        // this function:
+        //
+        //     [... parent]
+        //
+        // the change list stack is in the same state when this function returns.
+        fn hydrate_children(
+            cached_set: &CachedSet,
+            change_list: &mut ChangeListBuilder,
+            registry: &mut EventsRegistry,
+            parent: &web_sys::Element,
+            children: &[Node],
+            cached_roots: &mut FxHashSet<CacheId>,
+        ) {
+            let children = crate::diff::flatten_children(cached_set, children, &mut |id| {
+                cached_roots.insert(id);
+            });
+            let children = &children[..];
+
+            let mut physical = vec![];
+            let mut child = parent.first_child();
+            while let Some(node) = child {
+                child = node.next_sibling();
+                physical.push(node);
+            }
+
+            // `i` indexes the virtual children and `j` the physical children we
+            // have not yet hydrated. Once the change list is applied, the
+            // physical child `j` will sit at index `i`.
+            let mut i = 0;
+            let mut j = 0;
+
+            //     [... parent]
+            change_list.go_down();
+            //     [... parent child]
+
+            while i < children.len() && j < physical.len() {
+                // [... parent prev_child]
+                change_list.go_to_sibling(i);
+                // [... parent this_child]
+
+                if let NodeKind::Text(TextNode { text }) = resolve(cached_set, &children[i]).kind {
+                    let is_text = physical[j].node_type() == web_sys::Node::TEXT_NODE;
+
+                    // Empty text nodes don't survive a round trip through HTML,
+                    // so create them again.
+                    if text.is_empty() && !(is_text && physical[j].text_content().as_deref() == Some("")) {
+                        debug!("hydrate: creating empty text node");
+                        change_list.commit_traversal();
+                        change_list.create_text_node("");
+                        change_list.insert_before();
+                        i += 1;
+                        continue;
+                    }
+
+                    // Neither do the boundaries between adjacent text nodes, so
+                    // split them apart again.
+                    let next_is_text = i + 1 < children.len()
+                        && matches!(
+                            resolve(cached_set, &children[i + 1]).kind,
+                            NodeKind::Text(_)
+                        );
+                    if is_text && next_is_text {
+                        let content = physical[j].text_content().unwrap_or_default();
+                        if content.len() > text.len() && content.starts_with(text) {
+                            let offset = text.encode_utf16().count() as u32;
+                            let rest = physical[j]
+                                .unchecked_ref::<web_sys::Text>()
+                                .split_text(offset)
+                                .expect_throw("should split text OK");
+                            physical.insert(j + 1, rest.into());
+                        }
+                    }
+                }
+
+                hydrate(
+                    cached_set,
+                    change_list,
+                    registry,
+                    &physical[j],
+                    &children[i],
+                    cached_roots,
+                );
+                i += 1;
+                j += 1;
+            }
+
+            if i < children.len() {
+                if children[i..].iter().any(|child| !is_empty_text(cached_set, child)) {
+                    warn!(
+                        "hydrate: <{}> is missing {} children",
+                        parent.local_name(),
+                        children.len() - i
+                    );
+                }
+                // [... parent last_child]
+                change_list.go_up();
+                // [... parent]
+                change_list.commit_traversal();
+                crate::diff::create_and_append_children(
+                    cached_set,
+                    change_list,
+                    registry,
+                    &children[i..],
+                    cached_roots,
+                );
+            } else if j < physical.len() {
+                warn!(
+                    "hydrate: removing {} unexpected children of <{}>",
+                    physical.len() - j,
+                    parent.local_name()
+                );
+                // [... parent prev_child]
+                change_list.go_to_sibling(i);
+                // [... parent first_child_to_remove]
+                change_list.commit_traversal();
+                change_list.remove_self_and_next_siblings();
+                // [... parent]
+            } else {
+                // [... parent child]
+                change_list.go_up();
+                // [... parent]
+            }
+        }
+
+        // Look through cached nodes to the node they render.
+        fn resolve<'a>(cached_set: &'a CachedSet, node: &'a Node<'a>) -> &'a Node<'a> {
+            match node.kind {
+                NodeKind::Cached(ref c) => resolve(cached_set, cached_set.get(c.id).0),
+                _ => node,
+            }
+        }
+
+        fn is_empty_text(cached_set: &CachedSet, node: &Node) -> bool {
+            match resolve(cached_set, node).kind {
+                NodeKind::Text(TextNode { text }) => text.is_empty(),
+                _ => false,
+            }
+        }
+    }
+}
@@ -89,20 +89,29 @@ mod cached;
 mod cached_set;
 mod diff;
 mod events;
+mod hydrate;
 mod node;
+mod node_ref;
 mod render;
 mod render_context;
 mod strace;
+mod tasks;
 mod vdom;
 
 pub mod builder;
+pub mod remote;
+pub mod scheduler;
+pub mod ssr;
 
 // Re-export items at the top level.
 pub use self::cached::Cached;
-pub use self::node::{Attribute, Listener, Node, NodeKey};
+pub use self::node::{
+    Attribute, Listener, ListenerOptions, Node, NodeKey, Property, PropertyValue,
+};
+pub use self::node_ref::NodeRef;
 pub use self::render::{Render, RootRender};
 pub use self::render_context::RenderContext;
-pub use self::vdom::{Vdom, VdomWeak};
+pub use self::vdom::{FlushError, RenderBudget, Vdom, VdomBuilder, VdomWeak};
 
 cfg_if::cfg_if! {
     if #[cfg(all(target_arch = "wasm32", not(feature = "xxx-unstable-internal-use-only")))] {
@@ -115,8 +124,11 @@ cfg_if::cfg_if! {
 // Polyfill some Web stuff for benchmarking...
 cfg_if::cfg_if! {
     if #[cfg(all(feature = "xxx-unstable-internal-use-only", not(target_arch = "wasm32")))] {
+        #[doc(hidden)]
+        pub mod native_dom;
+
         /// An element node in the physical DOM.
-        pub type Element = ();
+        pub type Element = native_dom::Node;
 
         pub(crate) type EventsTrampoline = ();
     } else {
@@ -130,6 +142,10 @@ cfg_if::cfg_if! {
 cfg_if::cfg_if! {
     if #[cfg(feature = "xxx-unstable-internal-use-only")] {
         pub use self::cached_set::{CachedSet};
-        pub use self::node::{ElementNode, NodeKind, TextNode};
+        pub use self::node::{
+            ElementNode, FragmentNode, Lifecycle, NodeKind, PortalNode, PortalTarget, RawHtmlNode,
+            TextNode,
+        };
+        pub use self::node_ref::NodeRefId;
     }
 }
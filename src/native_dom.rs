@@ -0,0 +1,584 @@
+//! An in-memory stand-in for the physical DOM.
+//!
+//! When the `xxx-unstable-internal-use-only` feature is enabled on targets
+//! other than wasm, there is no Web browser to apply change lists to. Instead,
+//! change lists are applied to a tree of these nodes, which models just enough
+//! of the DOM to check that diffing produces the right physical DOM with an
+//! ordinary `cargo test`.
+
+use crate::ListenerOptions;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+/// The value of a DOM property set on an in-memory element.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyValue {
+    /// A boolean property value.
+    Bool(bool),
+    /// A number property value.
+    Number(f64),
+    /// A string property value.
+    String(String),
+}
+
+impl<'a> From<crate::PropertyValue<'a>> for PropertyValue {
+    fn from(value: crate::PropertyValue<'a>) -> PropertyValue {
+        match value {
+            crate::PropertyValue::Bool(b) => PropertyValue::Bool(b),
+            crate::PropertyValue::Number(n) => PropertyValue::Number(n),
+            crate::PropertyValue::String(s) => PropertyValue::String(s.to_string()),
+        }
+    }
+}
+
+/// A node in the in-memory DOM: either an element, a text node, a comment, or
+/// unparsed raw HTML.
+///
+/// Like a `web_sys::Node`, this is a handle, and clones of it refer to the same
+/// node.
+#[derive(Clone)]
+pub struct Node(Rc<RefCell<NodeData>>);
+
+struct NodeData {
+    parent: Weak<RefCell<NodeData>>,
+    kind: NodeKind,
+}
+
+// The event type, the listener's id parts, and its options.
+type Listener = (String, (u32, u32), ListenerOptions);
+
+enum NodeKind {
+    Text(String),
+    Comment,
+    // The in-memory DOM doesn't parse HTML, so raw HTML is kept as markup.
+    RawHtml(String),
+    Element {
+        tag_name: String,
+        namespace: Option<String>,
+        attributes: Vec<(String, String)>,
+        // Unlike attributes, properties are not copied by `clone_node`.
+        properties: Vec<(String, PropertyValue)>,
+        // Inline styles, which the DOM keeps in the `style` attribute, but are
+        // kept separately here so that they can be checked one at a time.
+        styles: Vec<(String, String)>,
+        listeners: Vec<Listener>,
+        children: Vec<Node>,
+    },
+}
+
+impl fmt::Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.borrow().kind {
+            NodeKind::Text(ref text) => write!(f, "#text({:?})", text),
+            NodeKind::Comment => write!(f, "<!---->"),
+            NodeKind::RawHtml(ref html) => write!(f, "{}", html),
+            NodeKind::Element {
+                ref tag_name,
+                ref attributes,
+                ref children,
+                ..
+            } => {
+                write!(f, "<{}", tag_name)?;
+                for (name, value) in attributes {
+                    write!(f, " {}={:?}", name, value)?;
+                }
+                write!(f, ">")?;
+                for child in children {
+                    write!(f, "{:?}", child)?;
+                }
+                write!(f, "</{}>", tag_name)
+            }
+        }
+    }
+}
+
+// The value that a property has on an element until it is set, for the
+// properties that reflect the element's state, and that dodrio's tests use. In
+// a browser, these are defined by the element's prototype.
+fn default_property(
+    tag_name: &str,
+    attributes: &[(String, String)],
+    name: &str,
+) -> Option<PropertyValue> {
+    let attribute = |name: &str| {
+        attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let value = match (tag_name, name) {
+        // These reflect their attribute until they are set.
+        ("input", "value") | ("option", "value") | ("button", "value") => {
+            PropertyValue::String(attribute("value").unwrap_or_default().to_string())
+        }
+        ("input", "checked") => PropertyValue::Bool(attribute("checked").is_some()),
+        ("option", "selected") => PropertyValue::Bool(attribute("selected").is_some()),
+        ("audio", "muted") | ("video", "muted") => PropertyValue::Bool(false),
+        ("audio", "volume") | ("video", "volume") => PropertyValue::Number(1.0),
+        ("audio", "currentTime") | ("video", "currentTime") => PropertyValue::Number(0.0),
+        ("input", "indeterminate") => PropertyValue::Bool(false),
+        ("a", "tabIndex")
+        | ("button", "tabIndex")
+        | ("input", "tabIndex")
+        | ("select", "tabIndex")
+        | ("textarea", "tabIndex") => PropertyValue::Number(0.0),
+        (_, "tabIndex") => PropertyValue::Number(-1.0),
+        (_, "scrollTop") | (_, "scrollLeft") => PropertyValue::Number(0.0),
+        (_, "title") | (_, "id") => {
+            PropertyValue::String(attribute(name).unwrap_or_default().to_string())
+        }
+        (_, "hidden") => PropertyValue::Bool(attribute("hidden").is_some()),
+        _ => return None,
+    };
+    Some(value)
+}
+
+// Like `web_sys::Node`s, handles are equal when they refer to the same node.
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        self.is_same_node(other)
+    }
+}
+
+impl Eq for Node {}
+
+impl Node {
+    fn new(kind: NodeKind) -> Node {
+        Node(Rc::new(RefCell::new(NodeData {
+            parent: Weak::new(),
+            kind,
+        })))
+    }
+
+    /// Create a new, detached element.
+    pub fn create_element(tag_name: &str) -> Node {
+        Node::new(NodeKind::Element {
+            tag_name: tag_name.to_string(),
+            namespace: None,
+            attributes: vec![],
+            properties: vec![],
+            styles: vec![],
+            listeners: vec![],
+            children: vec![],
+        })
+    }
+
+    /// Create a new, detached element in the given namespace.
+    pub fn create_element_ns(tag_name: &str, namespace: &str) -> Node {
+        let node = Node::create_element(tag_name);
+        if let NodeKind::Element {
+            namespace: ref mut ns,
+            ..
+        } = node.0.borrow_mut().kind
+        {
+            *ns = Some(namespace.to_string());
+        }
+        node
+    }
+
+    /// Create a new, detached text node.
+    pub fn create_text_node(text: &str) -> Node {
+        Node::new(NodeKind::Text(text.to_string()))
+    }
+
+    /// Create a new, detached, empty comment node.
+    pub fn create_comment() -> Node {
+        Node::new(NodeKind::Comment)
+    }
+
+    /// Create a new, detached node from raw HTML markup.
+    pub fn create_raw_html(html: &str) -> Node {
+        Node::new(NodeKind::RawHtml(html.to_string()))
+    }
+
+    /// Is this a text node?
+    pub fn is_text(&self) -> bool {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) => true,
+            NodeKind::Comment | NodeKind::RawHtml(_) | NodeKind::Element { .. } => false,
+        }
+    }
+
+    /// Is this a comment node?
+    pub fn is_comment(&self) -> bool {
+        match self.0.borrow().kind {
+            NodeKind::Comment => true,
+            NodeKind::Text(_) | NodeKind::RawHtml(_) | NodeKind::Element { .. } => false,
+        }
+    }
+
+    /// Get the markup that this node was created from, if it is raw HTML.
+    pub fn raw_html(&self) -> Option<String> {
+        match self.0.borrow().kind {
+            NodeKind::RawHtml(ref html) => Some(html.clone()),
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::Element { .. } => None,
+        }
+    }
+
+    /// Get this element's tag name, or `None` if this is not an element.
+    pub fn tag_name(&self) -> Option<String> {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::RawHtml(_) => None,
+            NodeKind::Element { ref tag_name, .. } => Some(tag_name.clone()),
+        }
+    }
+
+    /// Get this element's namespace, if any.
+    pub fn namespace(&self) -> Option<String> {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::RawHtml(_) => None,
+            NodeKind::Element { ref namespace, .. } => namespace.clone(),
+        }
+    }
+
+    /// Get the text of this node and all of its descendants, like the DOM's
+    /// `textContent`.
+    pub fn text_content(&self) -> String {
+        match self.0.borrow().kind {
+            NodeKind::Text(ref text) => text.clone(),
+            NodeKind::Comment | NodeKind::RawHtml(_) => String::new(),
+            NodeKind::Element { ref children, .. } => {
+                children.iter().map(Node::text_content).collect()
+            }
+        }
+    }
+
+    /// Get the value of this element's attribute with the given name.
+    pub fn attribute(&self, name: &str) -> Option<String> {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::RawHtml(_) => None,
+            NodeKind::Element { ref attributes, .. } => attributes
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.clone()),
+        }
+    }
+
+    /// Get all of this element's attributes, in the order they were first set.
+    pub fn attributes(&self) -> Vec<(String, String)> {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::RawHtml(_) => vec![],
+            NodeKind::Element { ref attributes, .. } => attributes.clone(),
+        }
+    }
+
+    /// Get the value of this element's property with the given name.
+    ///
+    /// Like in a browser, a property that reflects the element's state has a
+    /// default value until it is set, and again once it is removed. Any other
+    /// property, such as a custom element's expando, has no value then.
+    pub fn property(&self, name: &str) -> Option<PropertyValue> {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::RawHtml(_) => None,
+            NodeKind::Element {
+                ref tag_name,
+                ref attributes,
+                ref properties,
+                ..
+            } => properties
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| default_property(tag_name, attributes, name)),
+        }
+    }
+
+    /// Get all of this element's properties that are set, in the order they
+    /// were first set.
+    pub fn properties(&self) -> Vec<(String, PropertyValue)> {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::RawHtml(_) => vec![],
+            NodeKind::Element { ref properties, .. } => properties.clone(),
+        }
+    }
+
+    /// Get the value of this element's inline style property with the given
+    /// name.
+    pub fn style(&self, property: &str) -> Option<String> {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::RawHtml(_) => None,
+            NodeKind::Element { ref styles, .. } => styles
+                .iter()
+                .find(|(p, _)| p == property)
+                .map(|(_, value)| value.clone()),
+        }
+    }
+
+    /// Get all of this element's inline style properties, in the order they
+    /// were first set.
+    pub fn styles(&self) -> Vec<(String, String)> {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::RawHtml(_) => vec![],
+            NodeKind::Element { ref styles, .. } => styles.clone(),
+        }
+    }
+
+    /// Get the event types that this element is listening to.
+    pub fn listeners(&self) -> Vec<String> {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::RawHtml(_) => vec![],
+            NodeKind::Element { ref listeners, .. } => listeners
+                .iter()
+                .map(|(event, _, _)| event.clone())
+                .collect(),
+        }
+    }
+
+    /// Get the options of this element's listener for the given event type.
+    pub fn listener_options(&self, event: &str) -> Option<ListenerOptions> {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::RawHtml(_) => None,
+            NodeKind::Element { ref listeners, .. } => listeners
+                .iter()
+                .find(|(e, _, _)| e == event)
+                .map(|(_, _, options)| *options),
+        }
+    }
+
+    /// Get this node's children.
+    pub fn children(&self) -> Vec<Node> {
+        match self.0.borrow().kind {
+            NodeKind::Text(_) | NodeKind::Comment | NodeKind::RawHtml(_) => vec![],
+            NodeKind::Element { ref children, .. } => children.clone(),
+        }
+    }
+
+    /// Get this node's first child, if any.
+    pub fn first_child(&self) -> Option<Node> {
+        self.children().into_iter().next()
+    }
+
+    /// Get this node's parent, if it is attached to one.
+    pub fn parent(&self) -> Option<Node> {
+        self.0.borrow().parent.upgrade().map(Node)
+    }
+
+    /// Are `self` and `other` handles to the same node?
+    pub fn is_same_node(&self, other: &Node) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    fn with_element<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut Vec<(String, String)>, &mut Vec<Listener>, &mut Vec<Node>) -> T,
+    {
+        match self.0.borrow_mut().kind {
+            NodeKind::Text(_) => panic!("expected an element, found a text node"),
+            NodeKind::Comment => panic!("expected an element, found a comment"),
+            NodeKind::RawHtml(_) => panic!("expected an element, found raw HTML"),
+            NodeKind::Element {
+                ref mut attributes,
+                ref mut listeners,
+                ref mut children,
+                ..
+            } => f(attributes, listeners, children),
+        }
+    }
+
+    /// Set the text content of this node. For elements, this replaces all of
+    /// their children with a single text node, like the DOM's `textContent`.
+    pub(crate) fn set_text_content(&self, text: &str) {
+        match self.0.borrow_mut().kind {
+            NodeKind::Text(ref mut t) => {
+                *t = text.to_string();
+                return;
+            }
+            // Comments only ever hold placeholders, which have no text, and raw
+            // HTML is never diffed.
+            NodeKind::Comment | NodeKind::RawHtml(_) => return,
+            NodeKind::Element { .. } => {}
+        }
+
+        for child in self.children() {
+            child.remove();
+        }
+        if !text.is_empty() {
+            self.append_child(&Node::create_text_node(text));
+        }
+    }
+
+    pub(crate) fn set_attribute(&self, name: &str, value: &str) {
+        // Like in the DOM, the `style` attribute replaces all inline styles.
+        if name == "style" {
+            self.clear_styles();
+        }
+        self.with_element(
+            |attributes, _, _| match attributes.iter_mut().find(|(n, _)| n == name) {
+                Some((_, v)) => *v = value.to_string(),
+                None => attributes.push((name.to_string(), value.to_string())),
+            },
+        )
+    }
+
+    pub(crate) fn remove_attribute(&self, name: &str) {
+        if name == "style" {
+            self.clear_styles();
+        }
+        self.with_element(|attributes, _, _| attributes.retain(|(n, _)| n != name))
+    }
+
+    pub(crate) fn set_property(&self, name: &str, value: PropertyValue) {
+        match self.0.borrow_mut().kind {
+            NodeKind::Element {
+                ref mut properties, ..
+            } => match properties.iter_mut().find(|(n, _)| n == name) {
+                Some((_, v)) => *v = value,
+                None => properties.push((name.to_string(), value)),
+            },
+            _ => panic!("expected an element to set property {:?} on", name),
+        }
+    }
+
+    /// Remove a property that was set, which restores its default value, if
+    /// it has one. See `property`.
+    pub(crate) fn remove_property(&self, name: &str) {
+        match self.0.borrow_mut().kind {
+            NodeKind::Element {
+                ref mut properties, ..
+            } => properties.retain(|(n, _)| n != name),
+            _ => panic!("expected an element to remove property {:?} from", name),
+        }
+    }
+
+    /// Add a token to the `class` attribute, if it isn't already there, like
+    /// the DOM's `classList.add`.
+    pub fn add_class(&self, class: &str) {
+        let classes = self.attribute("class").unwrap_or_default();
+        if classes.split_whitespace().all(|c| c != class) {
+            let classes = classes
+                .split_whitespace()
+                .chain(Some(class))
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.set_attribute("class", &classes);
+        }
+    }
+
+    /// Remove a token from the `class` attribute, like the DOM's
+    /// `classList.remove`.
+    pub fn remove_class(&self, class: &str) {
+        if let Some(classes) = self.attribute("class") {
+            let classes = classes
+                .split_whitespace()
+                .filter(|c| *c != class)
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.set_attribute("class", &classes);
+        }
+    }
+
+    pub(crate) fn set_style(&self, property: &str, value: &str) {
+        match self.0.borrow_mut().kind {
+            NodeKind::Element { ref mut styles, .. } => {
+                match styles.iter_mut().find(|(p, _)| p == property) {
+                    Some((_, v)) => *v = value.to_string(),
+                    None => styles.push((property.to_string(), value.to_string())),
+                }
+            }
+            _ => panic!("expected an element to set style {:?} on", property),
+        }
+    }
+
+    pub(crate) fn remove_style(&self, property: &str) {
+        match self.0.borrow_mut().kind {
+            NodeKind::Element { ref mut styles, .. } => styles.retain(|(p, _)| p != property),
+            _ => panic!("expected an element to remove style {:?} from", property),
+        }
+    }
+
+    fn clear_styles(&self) {
+        if let NodeKind::Element { ref mut styles, .. } = self.0.borrow_mut().kind {
+            styles.clear();
+        }
+    }
+
+    /// Add a listener, or update the id parts of an existing listener,
+    /// for the given event type. Like the DOM, listeners in the capture phase
+    /// are distinct from listeners in the bubbling phase.
+    pub(crate) fn set_listener(&self, event: &str, parts: (u32, u32), options: ListenerOptions) {
+        self.with_element(|_, listeners, _| {
+            match listeners
+                .iter_mut()
+                .find(|(e, _, o)| e == event && o.capture == options.capture)
+            {
+                Some((_, p, _)) => *p = parts,
+                None => listeners.push((event.to_string(), parts, options)),
+            }
+        })
+    }
+
+    pub(crate) fn remove_listener(&self, event: &str, options: ListenerOptions) {
+        self.with_element(|_, listeners, _| {
+            listeners.retain(|(e, _, o)| e != event || o.capture != options.capture)
+        })
+    }
+
+    /// Remove this node from its parent, if it has one.
+    pub(crate) fn remove(&self) {
+        if let Some(parent) = self.parent() {
+            parent.with_element(|_, _, children| children.retain(|c| !c.is_same_node(self)));
+        }
+        self.0.borrow_mut().parent = Weak::new();
+    }
+
+    fn index_of(&self, child: &Node) -> usize {
+        self.children()
+            .iter()
+            .position(|c| c.is_same_node(child))
+            .expect("should be a child of its parent")
+    }
+
+    /// Append `child` to this element, moving it from its old parent if it
+    /// had one.
+    pub(crate) fn append_child(&self, child: &Node) {
+        child.remove();
+        self.with_element(|_, _, children| children.push(child.clone()));
+        child.0.borrow_mut().parent = Rc::downgrade(&self.0);
+    }
+
+    /// Insert `new_node` into this element, before its existing child
+    /// `reference`.
+    pub(crate) fn insert_before(&self, new_node: &Node, reference: &Node) {
+        new_node.remove();
+        let index = self.index_of(reference);
+        self.with_element(|_, _, children| children.insert(index, new_node.clone()));
+        new_node.0.borrow_mut().parent = Rc::downgrade(&self.0);
+    }
+
+    /// Replace this node with `new_node` in its parent.
+    pub(crate) fn replace_with(&self, new_node: &Node) {
+        let parent = self.parent().expect("should have a parent");
+        parent.insert_before(new_node, self);
+        self.remove();
+    }
+
+    /// Make a deep copy of this node. Like the DOM's `cloneNode(true)`, event
+    /// listeners and properties are not copied.
+    pub(crate) fn clone_node(&self) -> Node {
+        let node = match self.0.borrow().kind {
+            NodeKind::Text(ref text) => return Node::create_text_node(text),
+            NodeKind::Comment => return Node::create_comment(),
+            NodeKind::RawHtml(ref html) => return Node::create_raw_html(html),
+            NodeKind::Element {
+                ref tag_name,
+                ref namespace,
+                ref attributes,
+                ref styles,
+                ..
+            } => Node::new(NodeKind::Element {
+                tag_name: tag_name.clone(),
+                namespace: namespace.clone(),
+                attributes: attributes.clone(),
+                properties: vec![],
+                styles: styles.clone(),
+                listeners: vec![],
+                children: vec![],
+            }),
+        };
+        for child in self.children() {
+            node.append_child(&child.clone_node());
+        }
+        node
+    }
+}
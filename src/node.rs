@@ -1,8 +1,13 @@
-use crate::{cached_set::CacheId, RootRender, VdomWeak};
+use crate::{cached_set::CacheId, node_ref::NodeRefId, RootRender, VdomWeak};
 use bumpalo::Bump;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::iter;
-use std::mem;
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::u32;
 
 /// A virtual DOM node.
@@ -17,7 +22,8 @@ pub struct Node<'a> {
 }
 
 pub_unstable_internal! {
-    /// A node is either a text node or an element.
+    /// A node is either a text node, an element, a cached node, a fragment, an
+    /// empty placeholder, raw HTML, or a portal.
     #[derive(Debug, Clone)]
     pub(crate) enum NodeKind<'a> {
         /// A text node.
@@ -29,6 +35,18 @@ pub_unstable_internal! {
         /// A node in the vdom's `CachedSet`. This allows us to avoid
         /// re-rendering and re-diffing subtrees.
         Cached(CachedNode),
+
+        /// A sequence of sibling nodes without a wrapper element.
+        Fragment(FragmentNode<'a>),
+
+        /// A placeholder for nothing, rendered as an empty comment node.
+        Empty,
+
+        /// Trusted HTML markup that is parsed into a physical DOM node as-is.
+        RawHtml(RawHtmlNode<'a>),
+
+        /// Children that are rendered into another container element.
+        Portal(PortalNode<'a>),
     }
 }
 
@@ -41,6 +59,16 @@ pub_unstable_internal! {
     }
 }
 
+pub_unstable_internal! {
+    /// Raw HTML nodes are a string of markup for a single DOM node, which is
+    /// neither escaped nor diffed. They are replaced wholesale whenever their
+    /// markup changes.
+    #[derive(Debug, Clone)]
+    pub(crate) struct RawHtmlNode<'a> {
+        pub html: &'a str,
+    }
+}
+
 pub_unstable_internal! {
     /// Elements have a tag name, zero or more attributes, and zero or more
     /// children.
@@ -50,8 +78,54 @@ pub_unstable_internal! {
         pub tag_name: &'a str,
         pub listeners: &'a [Listener<'a>],
         pub attributes: &'a [Attribute<'a>],
+        pub properties: &'a [Property<'a>],
+        // Inline style properties and their values, such as `color: red`.
+        pub styles: &'a [Attribute<'a>],
+        // Class names that are added to and removed from the element's class
+        // list one at a time.
+        pub classes: &'a [&'a str],
         pub children: &'a [Node<'a>],
         pub namespace: Option<&'a str>,
+        // The `NodeRef` that is filled in with this element's physical node.
+        pub node_ref: Option<NodeRefId>,
+        // The callbacks to invoke when this element is mounted and unmounted.
+        pub lifecycle: Option<&'a Lifecycle<'a>>,
+    }
+}
+
+pub_unstable_internal! {
+    /// Fragments are a sequence of nodes that are rendered in place of the
+    /// fragment, as siblings within the fragment's parent. They have no
+    /// physical DOM node of their own.
+    #[derive(Debug, Clone)]
+    pub(crate) struct FragmentNode<'a> {
+        pub children: &'a [Node<'a>],
+    }
+}
+
+pub_unstable_internal! {
+    /// Portals render their children into a target element elsewhere in the
+    /// physical DOM, rather than into the portal's parent. In its parent, a
+    /// portal is an empty placeholder comment node.
+    #[derive(Debug, Clone)]
+    pub(crate) struct PortalNode<'a> {
+        /// Identifies this portal's physical children across renders, like a
+        /// lifecycle's id.
+        pub id: u64,
+        pub target: &'a PortalTarget,
+        pub children: &'a [Node<'a>],
+    }
+}
+
+pub_unstable_internal! {
+    /// An element that portals render into. Each virtual DOM keeps its portal
+    /// targets in its `CachedSet`, and gives each distinct element an id. A
+    /// target is released once no rendered portal uses it, and gets a new id
+    /// if it is rendered into again.
+    #[derive(Debug)]
+    pub(crate) struct PortalTarget {
+        pub id: u32,
+        pub element: crate::Element,
     }
 }
 
@@ -73,8 +147,11 @@ pub_unstable_internal! {
 /// Keys must be unique among siblings.
 ///
 /// If any sibling is keyed, then they all must be keyed.
+///
+/// A key is either a `u32` or the hash of some other value, such as a string.
+/// Hashed keys never equal `u32` keys, so the two may be mixed among siblings.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-pub struct NodeKey(pub(crate) u32);
+pub struct NodeKey(pub(crate) u64);
 
 impl Default for NodeKey {
     fn default() -> NodeKey {
@@ -84,7 +161,10 @@ impl Default for NodeKey {
 
 impl NodeKey {
     /// The default, lack of a key.
-    pub const NONE: NodeKey = NodeKey(u32::MAX);
+    pub const NONE: NodeKey = NodeKey(u64::MAX);
+
+    // Set on every hashed key, and never on `u32` keys.
+    const HASHED: u64 = 1 << 63;
 
     /// Is this key `NodeKey::NONE`?
     #[inline]
@@ -98,13 +178,45 @@ impl NodeKey {
         !self.is_none()
     }
 
+    /// Was this key created with `NodeKey::hashed`?
+    #[inline]
+    pub(crate) fn is_hashed(&self) -> bool {
+        self.is_some() && self.0 & Self::HASHED != 0
+    }
+
     /// Create a new `NodeKey`.
     ///
     /// `key` must not be `u32::MAX`.
     #[inline]
     pub fn new(key: u32) -> Self {
         debug_assert_ne!(key, u32::MAX);
-        NodeKey(key)
+        NodeKey(u64::from(key))
+    }
+
+    /// Create a new `NodeKey` by hashing `key`.
+    ///
+    /// This lets lists of records be keyed by their existing identifiers, such
+    /// as UUIDs or slugs, without interning them into `u32`s first. Equal
+    /// values hash to the same key within a program, but the hash function is
+    /// unspecified and may change between Rust releases, so don't persist
+    /// these keys.
+    ///
+    /// Hashes are 64 bits wide, so it is vanishingly unlikely that two
+    /// different values among the same siblings collide. Should that happen,
+    /// the collision is detected when diffing, in release builds too, and
+    /// those siblings are diffed as if they weren't keyed: correct, but without
+    /// preserving their elements' identities.
+    #[inline]
+    pub fn hashed<K>(key: &K) -> Self
+    where
+        K: ?Sized + Hash,
+    {
+        // `DefaultHasher::new` always uses the same keys, so that the hash of
+        // a value doesn't change from one render to the next.
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish() | Self::HASHED;
+        NodeKey(if hash == Self::NONE.0 { hash - 1 } else { hash })
     }
 }
 
@@ -115,15 +227,195 @@ impl NodeKey {
 /// 1. The virtual DOM's root rendering component.
 /// 2. A capability to scheduler virtual DOM re-rendering.
 /// 3. The event that occurred.
-pub(crate) type ListenerCallback<'a> =
-    &'a (dyn Fn(&mut dyn RootRender, VdomWeak, web_sys::Event) + 'static);
+#[derive(Clone, Copy)]
+pub(crate) enum ListenerCallback<'a> {
+    /// A closure, allocated in the same bump arena as its listener's node.
+    Closure(&'a (dyn Fn(&mut dyn RootRender, VdomWeak, web_sys::Event) + 'static)),
+
+    /// A plain function and the data to invoke it with, from `on_with`.
+    WithData(ListenerData<'a>),
+}
+
+/// A type-erased plain function and the data that `on_with` passes to it.
+#[derive(Clone, Copy)]
+pub(crate) struct ListenerData<'a> {
+    // Casts `function` and `data` back to their real types, and calls the one
+    // with the other.
+    call: unsafe fn(*const (), &MaybeUninit<usize>, &mut dyn RootRender, VdomWeak, web_sys::Event),
+    // The `fn(&mut dyn RootRender, VdomWeak, web_sys::Event, T)` to call.
+    function: *const (),
+    // The `T` itself if it fits in a word, or else a `&'a T` to a copy of it in
+    // the bump arena. Either way, no closure is allocated per listener.
+    data: MaybeUninit<usize>,
+    _data: PhantomData<&'a ()>,
+}
+
+impl<'a> ListenerCallback<'a> {
+    /// Create a callback that invokes `function` with `data`.
+    pub(crate) fn with_data<T>(
+        bump: &'a Bump,
+        data: T,
+        function: fn(&mut dyn RootRender, VdomWeak, web_sys::Event, T),
+    ) -> ListenerCallback<'a>
+    where
+        T: 'a + Copy,
+    {
+        let mut word = MaybeUninit::<usize>::uninit();
+        unsafe {
+            if fits_in_word::<T>() {
+                ptr::write(word.as_mut_ptr() as *mut T, data);
+            } else {
+                ptr::write(word.as_mut_ptr(), bump.alloc(data) as *const T as usize);
+            }
+        }
+        ListenerCallback::WithData(ListenerData {
+            call: call_with_data::<T>,
+            function: function as *const (),
+            data: word,
+            _data: PhantomData,
+        })
+    }
+
+    /// Invoke this callback.
+    // The in-memory DOM never triggers events, so never invokes callbacks.
+    #[cfg_attr(
+        all(
+            feature = "xxx-unstable-internal-use-only",
+            not(target_arch = "wasm32")
+        ),
+        allow(dead_code)
+    )]
+    pub(crate) fn call(self, root: &mut dyn RootRender, vdom: VdomWeak, event: web_sys::Event) {
+        match self {
+            ListenerCallback::Closure(f) => f(root, vdom, event),
+            ListenerCallback::WithData(d) => unsafe {
+                (d.call)(d.function, &d.data, root, vdom, event)
+            },
+        }
+    }
+}
+
+#[inline]
+fn fits_in_word<T>() -> bool {
+    mem::size_of::<T>() <= mem::size_of::<usize>()
+        && mem::align_of::<T>() <= mem::align_of::<usize>()
+}
+
+// The `ListenerData::call` for data of type `T`.
+unsafe fn call_with_data<T: Copy>(
+    function: *const (),
+    data: &MaybeUninit<usize>,
+    root: &mut dyn RootRender,
+    vdom: VdomWeak,
+    event: web_sys::Event,
+) {
+    let function: fn(&mut dyn RootRender, VdomWeak, web_sys::Event, T) = mem::transmute(function);
+    let data = if fits_in_word::<T>() {
+        ptr::read(data.as_ptr() as *const T)
+    } else {
+        *(data.assume_init() as *const T)
+    };
+    function(root, vdom, event, data)
+}
+
+/// A lifecycle callback function.
+///
+/// It takes the same first two parameters as a `ListenerCallback`, and the
+/// physical element that was mounted or unmounted.
+pub(crate) type LifecycleCallback<'a> =
+    &'a (dyn Fn(&mut dyn RootRender, VdomWeak, crate::Element) + 'static);
+
+pub_unstable_internal! {
+    /// The callbacks to invoke once an element is inserted into the physical
+    /// DOM, and once it is removed again.
+    #[derive(Clone, Copy)]
+    pub(crate) struct Lifecycle<'a> {
+        // This lifecycle's unique id, which the element's physical node is
+        // tracked by between being mounted and unmounted.
+        pub(crate) id: u64,
+        pub(crate) on_mount: Option<LifecycleCallback<'a>>,
+        pub(crate) on_unmount: Option<LifecycleCallback<'a>>,
+    }
+}
 
 /// An event listener.
 pub struct Listener<'a> {
     /// The type of event to listen for.
     pub(crate) event: &'a str,
     /// The callback to invoke when the event happens.
+    // The in-memory DOM never triggers events, so never invokes callbacks.
+    #[cfg_attr(
+        all(
+            feature = "xxx-unstable-internal-use-only",
+            not(target_arch = "wasm32")
+        ),
+        allow(dead_code)
+    )]
     pub(crate) callback: ListenerCallback<'a>,
+    /// The options that the listener is added with.
+    pub(crate) options: ListenerOptions,
+    /// This listener's unique id, which the events registry and the physical
+    /// DOM refer to it by.
+    pub(crate) id: u64,
+}
+
+/// Options for an event listener, matching the options that can be passed to
+/// `addEventListener`.
+///
+/// # Example
+///
+/// ```
+/// use dodrio::ListenerOptions;
+///
+/// let options = ListenerOptions {
+///     passive: true,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ListenerOptions {
+    /// Invoke the listener during the capture phase, before the event reaches
+    /// the element's descendants, rather than during the bubbling phase.
+    pub capture: bool,
+    /// Promise that the listener never calls `preventDefault`, which lets the
+    /// browser scroll without waiting for it.
+    pub passive: bool,
+    /// Remove the listener after it is invoked once.
+    pub once: bool,
+}
+
+impl ListenerOptions {
+    const CAPTURE: u32 = 1 << 0;
+    const PASSIVE: u32 = 1 << 1;
+    const ONCE: u32 = 1 << 2;
+
+    // Encode these options as a change list immediate.
+    #[inline]
+    pub(crate) fn to_bits(self) -> u32 {
+        let mut bits = 0;
+        if self.capture {
+            bits |= ListenerOptions::CAPTURE;
+        }
+        if self.passive {
+            bits |= ListenerOptions::PASSIVE;
+        }
+        if self.once {
+            bits |= ListenerOptions::ONCE;
+        }
+        bits
+    }
+
+    // Decode options that were encoded with `to_bits`, for disassembly and
+    // the in-memory DOM.
+    #[cfg(feature = "xxx-unstable-internal-use-only")]
+    #[inline]
+    pub(crate) fn from_bits(bits: u32) -> ListenerOptions {
+        ListenerOptions {
+            capture: bits & ListenerOptions::CAPTURE != 0,
+            passive: bits & ListenerOptions::PASSIVE != 0,
+            once: bits & ListenerOptions::ONCE != 0,
+        }
+    }
 }
 
 /// An attribute on a DOM node, such as `id="my-thing"` or
@@ -134,6 +426,63 @@ pub struct Attribute<'a> {
     pub(crate) value: &'a str,
 }
 
+/// A DOM property on a DOM node, such as `indeterminate` on a checkbox or
+/// `scrollTop` on a scrolling container.
+///
+/// Unlike attributes, properties are set directly on the physical DOM node's
+/// JavaScript object, and they are not part of server-rendered markup.
+#[derive(Clone, Debug)]
+pub struct Property<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) value: PropertyValue<'a>,
+}
+
+/// The value of a `Property`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PropertyValue<'a> {
+    /// A boolean value, such as for `muted`.
+    Bool(bool),
+    /// A number value, such as for `scrollTop`.
+    Number(f64),
+    /// A string value, such as for `value`.
+    String(&'a str),
+}
+
+impl From<bool> for PropertyValue<'_> {
+    #[inline]
+    fn from(b: bool) -> Self {
+        PropertyValue::Bool(b)
+    }
+}
+
+impl From<f64> for PropertyValue<'_> {
+    #[inline]
+    fn from(n: f64) -> Self {
+        PropertyValue::Number(n)
+    }
+}
+
+impl From<i32> for PropertyValue<'_> {
+    #[inline]
+    fn from(n: i32) -> Self {
+        PropertyValue::Number(n.into())
+    }
+}
+
+impl From<u32> for PropertyValue<'_> {
+    #[inline]
+    fn from(n: u32) -> Self {
+        PropertyValue::Number(n.into())
+    }
+}
+
+impl<'a> From<&'a str> for PropertyValue<'a> {
+    #[inline]
+    fn from(s: &'a str) -> Self {
+        PropertyValue::String(s)
+    }
+}
+
 impl<'a> From<CachedNode> for Node<'a> {
     #[inline]
     fn from(c: CachedNode) -> Self {
@@ -143,14 +492,46 @@ impl<'a> From<CachedNode> for Node<'a> {
     }
 }
 
+/// A node that is only conditionally rendered becomes an empty placeholder
+/// when it is `None`, so that its siblings keep their positions.
+impl<'a> From<Option<Node<'a>>> for Node<'a> {
+    #[inline]
+    fn from(node: Option<Node<'a>>) -> Self {
+        node.unwrap_or_else(Node::empty)
+    }
+}
+
 impl fmt::Debug for Listener<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let (a, b) = self.get_callback_parts();
-        let a = a as *mut u32;
-        let b = b as *mut u32;
         f.debug_struct("Listener")
             .field("event", &self.event)
-            .field("callback", &(a, b))
+            .field("callback", &"..")
+            .field("options", &self.options)
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+impl<'a> Lifecycle<'a> {
+    /// Construct a new lifecycle, without any callbacks, with a fresh id.
+    #[inline]
+    pub(crate) fn new() -> Lifecycle<'a> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        Lifecycle {
+            id,
+            on_mount: None,
+            on_unmount: None,
+        }
+    }
+}
+
+impl fmt::Debug for Lifecycle<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Lifecycle")
+            .field("id", &self.id)
+            .field("on_mount", &self.on_mount.map(|_| ".."))
+            .field("on_unmount", &self.on_unmount.map(|_| ".."))
             .finish()
     }
 }
@@ -181,6 +562,20 @@ impl<'a> Attribute<'a> {
     }
 }
 
+impl<'a> Property<'a> {
+    /// Get this property's name, such as `"indeterminate"`.
+    #[inline]
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Get this property's value.
+    #[inline]
+    pub fn value(&self) -> PropertyValue<'a> {
+        self.value
+    }
+}
+
 impl<'a> Node<'a> {
     /// Low-level constructor for making a new `Node` of type element with given
     /// parts.
@@ -203,8 +598,13 @@ impl<'a> Node<'a> {
             tag_name,
             listeners,
             attributes,
+            properties: &[],
+            styles: &[],
+            classes: &[],
             children,
             namespace,
+            node_ref: None,
+            lifecycle: None,
         });
 
         Node {
@@ -212,6 +612,52 @@ impl<'a> Node<'a> {
         }
     }
 
+    /// Low-level constructor for making a new `Node` of type fragment with the
+    /// given children.
+    ///
+    /// Prefer `dodrio::builder::fragment` when building nodes by-hand.
+    #[inline]
+    pub fn fragment(children: &'a [Node<'a>]) -> Node<'a> {
+        Node {
+            kind: NodeKind::Fragment(FragmentNode { children }),
+        }
+    }
+
+    /// Low-level constructor for making a new empty placeholder `Node`.
+    ///
+    /// Prefer `dodrio::builder::empty` when building nodes by-hand.
+    #[inline]
+    pub fn empty() -> Node<'a> {
+        Node {
+            kind: NodeKind::Empty,
+        }
+    }
+
+    /// Low-level constructor for making a new `Node` of type raw HTML with the
+    /// given markup.
+    ///
+    /// Prefer `dodrio::builder::raw_html` when building nodes by-hand.
+    #[inline]
+    pub fn raw_html(html: &'a str) -> Node<'a> {
+        Node {
+            kind: NodeKind::RawHtml(RawHtmlNode { html }),
+        }
+    }
+
+    /// Construct a new portal node with the given target and children.
+    #[inline]
+    pub(crate) fn portal(target: &'a PortalTarget, children: &'a [Node<'a>]) -> Node<'a> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        Node {
+            kind: NodeKind::Portal(PortalNode {
+                id,
+                target,
+                children,
+            }),
+        }
+    }
+
     /// Construct a new text node with the given text.
     #[inline]
     pub(crate) fn text(text: &'a str) -> Node<'a> {
@@ -223,7 +669,11 @@ impl<'a> Node<'a> {
     #[inline]
     pub(crate) fn key(&self) -> NodeKey {
         match &self.kind {
-            NodeKind::Text(_) => NodeKey::NONE,
+            NodeKind::Text(_)
+            | NodeKind::Fragment(_)
+            | NodeKind::Empty
+            | NodeKind::RawHtml(_)
+            | NodeKind::Portal(_) => NodeKey::NONE,
             NodeKind::Element(e) => e.key,
             NodeKind::Cached(c) => c.key,
         }
@@ -246,26 +696,31 @@ impl<'a> IntoIterator for Node<'a> {
     }
 }
 
-union CallbackFatPtr<'a> {
-    callback: ListenerCallback<'a>,
-    parts: (u32, u32),
-}
-
-impl Listener<'_> {
+impl<'a> Listener<'a> {
+    /// Construct a new listener with a fresh id.
     #[inline]
-    pub(crate) fn get_callback_parts(&self) -> (u32, u32) {
-        assert_eq!(
-            mem::size_of::<ListenerCallback>(),
-            mem::size_of::<CallbackFatPtr>()
-        );
-
-        unsafe {
-            let fat = CallbackFatPtr {
-                callback: self.callback,
-            };
-            let (a, b) = fat.parts;
-            debug_assert!(a != 0);
-            (a, b)
+    pub(crate) fn new(
+        event: &'a str,
+        callback: ListenerCallback<'a>,
+        options: ListenerOptions,
+    ) -> Listener<'a> {
+        // Listeners can't be identified by their callback's address, since
+        // zero-sized closures all share the same dangling pointer, so every
+        // listener gets its own id instead.
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        Listener {
+            event,
+            callback,
+            options,
+            id,
         }
     }
+
+    /// Get this listener's id, split into the two words that change lists
+    /// encode it as.
+    #[inline]
+    pub(crate) fn id_parts(&self) -> (u32, u32) {
+        (self.id as u32, (self.id >> 32) as u32)
+    }
 }
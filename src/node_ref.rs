@@ -0,0 +1,127 @@
+use fxhash::FxHashMap;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+/// A handle to the physical DOM element that a virtual element became.
+///
+/// Create a `NodeRef` once, keep it in your component, and attach it to an
+/// element with `ElementBuilder::node_ref` every time you render. Once the
+/// rendered changes are applied, the `NodeRef` holds the physical element,
+/// for focusing it, measuring it, and such. It keeps holding that element
+/// across renders for as long as the element is in the DOM with this
+/// `NodeRef` attached, and is cleared when the element is removed.
+///
+/// Cloning a `NodeRef` gives another handle to the same reference.
+///
+/// A `NodeRef` is never filled in by a `Vdom` that was created with
+/// `Vdom::with_transport`, since its physical elements live elsewhere.
+///
+/// # Example
+///
+/// ```no_run
+/// use dodrio::{builder::*, Node, NodeRef, Render, RenderContext};
+///
+/// struct Search {
+///     input: NodeRef,
+/// }
+///
+/// impl<'a> Render<'a> for Search {
+///     fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+///         input(&cx)
+///             .attr("type", "search")
+///             .node_ref(&self.input)
+///             .finish()
+///     }
+/// }
+///
+/// impl Search {
+///     fn focus(&self) {
+///         if let Some(input) = self.input.get() {
+///             // Focus the `input` element...
+///         }
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct NodeRef {
+    inner: Rc<NodeRefInner>,
+}
+
+struct NodeRefInner {
+    id: NodeRefId,
+    element: RefCell<Option<crate::Element>>,
+}
+
+pub_unstable_internal! {
+    /// The identity of a `NodeRef`, which is what virtual elements hold on to.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub(crate) struct NodeRefId(u64);
+}
+
+thread_local! {
+    // Every live `NodeRef`, so that they can be found by id once the elements
+    // they are attached to are created.
+    static NODE_REFS: RefCell<(u64, FxHashMap<NodeRefId, Weak<NodeRefInner>>)> =
+        RefCell::new((0, FxHashMap::default()));
+}
+
+impl NodeRef {
+    /// Create a new `NodeRef` that isn't attached to any element yet.
+    pub fn new() -> NodeRef {
+        NODE_REFS.with(|refs| {
+            let mut refs = refs.borrow_mut();
+            refs.0 += 1;
+            let id = NodeRefId(refs.0);
+            let inner = Rc::new(NodeRefInner {
+                id,
+                element: RefCell::new(None),
+            });
+            refs.1.insert(id, Rc::downgrade(&inner));
+            NodeRef { inner }
+        })
+    }
+
+    /// Get the physical element that this `NodeRef` is attached to, if any.
+    pub fn get(&self) -> Option<crate::Element> {
+        self.inner.element.borrow().clone()
+    }
+
+    #[inline]
+    pub(crate) fn id(&self) -> NodeRefId {
+        self.inner.id
+    }
+}
+
+impl Default for NodeRef {
+    fn default() -> NodeRef {
+        NodeRef::new()
+    }
+}
+
+impl fmt::Debug for NodeRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NodeRef")
+            .field("id", &self.inner.id)
+            .field("element", &self.inner.element.borrow())
+            .finish()
+    }
+}
+
+impl Drop for NodeRefInner {
+    fn drop(&mut self) {
+        let id = self.id;
+        // The thread-local may already be gone when the thread is exiting.
+        let _ = NODE_REFS.try_with(|refs| refs.borrow_mut().1.remove(&id));
+    }
+}
+
+impl NodeRefId {
+    /// Set the element of the `NodeRef` with this id, if it is still alive.
+    pub(crate) fn set(self, element: Option<crate::Element>) {
+        let inner = NODE_REFS.with(|refs| refs.borrow().1.get(&self).and_then(Weak::upgrade));
+        if let Some(inner) = inner {
+            *inner.element.borrow_mut() = element;
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! Running the virtual DOM in one place and applying its changes in another.
+//!
+//! Normally, a `Vdom` renders, diffs, and applies the resulting change list to
+//! the physical DOM all in the same wasm instance. In remote mode, the `Vdom`
+//! is created with `Vdom::with_transport` instead: each change list is copied
+//! into a self-contained `Frame` and handed to a `Transport`, which can send it
+//! anywhere (for example, from a web worker to the main thread with
+//! `postMessage`). On the other side, an `Applier` applies received frames to a
+//! container element.
+//!
+//! Events flow in the opposite direction: when a listener fires, the `Applier`
+//! invokes its event callback with the `ListenerId` that the listener was
+//! registered with. Send that back along with whatever parts of the event your
+//! components need, re-create an event from them, and pass both to
+//! `VdomWeak::dispatch_event`.
+
+pub use crate::change_list::emitter::DecodeError;
+
+use crate::change_list::emitter::Instruction;
+use crate::change_list::interpreter::ChangeListInterpreter;
+use std::fmt;
+use std::str;
+
+/// A self-contained, serializable change list.
+///
+/// Unlike the change lists that a locally mounted `Vdom` applies, a frame does
+/// not point into the memory of the instance that created it: the text that
+/// its instructions refer to is carried along with it.
+///
+/// A frame only makes sense when applied in order with the frames that the same
+/// `Vdom` produced before it, since cached strings and templates persist across
+/// frames.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Frame {
+    words: Vec<u32>,
+    text: Vec<u8>,
+}
+
+/// An error that occurred while reading a `Frame`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    /// The frame's bytes ended early.
+    Truncated,
+    /// One of the frame's instructions could not be decoded.
+    Decode(DecodeError),
+    /// An instruction refers to text that is out of bounds or is not valid
+    /// UTF-8.
+    InvalidText {
+        /// The offset of the text within the frame.
+        offset: u32,
+        /// The length of the text.
+        length: u32,
+    },
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::Truncated => write!(f, "change list frame is truncated"),
+            FrameError::Decode(e) => write!(f, "invalid change list frame: {}", e),
+            FrameError::InvalidText { offset, length } => write!(
+                f,
+                "change list frame has invalid text at offset {} with length {}",
+                offset, length
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<DecodeError> for FrameError {
+    fn from(e: DecodeError) -> FrameError {
+        FrameError::Decode(e)
+    }
+}
+
+impl Frame {
+    pub(crate) fn new(words: Vec<u32>, text: Vec<u8>) -> Frame {
+        Frame { words, text }
+    }
+
+    /// Does this frame contain no changes?
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// The frame's encoded instructions.
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    /// The text that the frame's instructions refer to, by offset and length.
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    /// Get the string at the given offset and length in this frame's text.
+    pub(crate) fn string(&self, offset: u32, length: u32) -> Result<&str, FrameError> {
+        let start = offset as usize;
+        let end = start.checked_add(length as usize);
+        end.and_then(|end| self.text.get(start..end))
+            .and_then(|bytes| str::from_utf8(bytes).ok())
+            .ok_or(FrameError::InvalidText { offset, length })
+    }
+
+    /// Serialize this frame into bytes.
+    ///
+    /// The encoding is the number of instruction words, followed by the words,
+    /// followed by the text, with all integers in little-endian byte order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + self.words.len() * 4 + self.text.len());
+        bytes.extend_from_slice(&(self.words.len() as u32).to_le_bytes());
+        for word in &self.words {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.text);
+        bytes
+    }
+
+    /// Deserialize a frame that was serialized with `Frame::to_bytes`.
+    ///
+    /// The frame's instructions are validated, so that a malformed frame is
+    /// reported here rather than when it is applied.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Frame, FrameError> {
+        fn read_word(bytes: &[u8]) -> u32 {
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+        }
+
+        if bytes.len() < 4 {
+            return Err(FrameError::Truncated);
+        }
+        let num_words = read_word(bytes) as usize;
+        let text_start = num_words
+            .checked_mul(4)
+            .and_then(|n| n.checked_add(4))
+            .filter(|&n| n <= bytes.len())
+            .ok_or(FrameError::Truncated)?;
+
+        let words = bytes[4..text_start]
+            .chunks_exact(4)
+            .map(read_word)
+            .collect();
+        let text = bytes[text_start..].to_vec();
+        let mut frame = Frame { words, text };
+
+        let mut words = std::mem::take(&mut frame.words);
+        relocate_text(&mut words, |offset, length| {
+            frame.string(offset, length)?;
+            Ok(offset)
+        })?;
+        frame.words = words;
+        Ok(frame)
+    }
+}
+
+/// Rewrite the pointer immediate of every instruction in `words` that refers
+/// to text with `f(pointer, length)`.
+pub(crate) fn relocate_text<F>(words: &mut [u32], mut f: F) -> Result<(), FrameError>
+where
+    F: FnMut(u32, u32) -> Result<u32, FrameError>,
+{
+    let mut encoded = vec![];
+    let mut i = 0;
+    while i < words.len() {
+        let (mut instruction, len) = Instruction::decode(&words[i..])?;
+        if let Some((pointer, length)) = instruction.text_immediates_mut() {
+            *pointer = f(*pointer, *length)?;
+            encoded.clear();
+            instruction.encode(&mut encoded);
+            words[i..i + len].copy_from_slice(&encoded);
+        }
+        i += len;
+    }
+    Ok(())
+}
+
+/// Something that can deliver frames to an `Applier`.
+///
+/// Implemented for all `FnMut(Frame)` closures.
+pub trait Transport {
+    /// Send the next frame.
+    ///
+    /// Frames must be applied in the order that they are sent.
+    fn send_frame(&mut self, frame: Frame);
+}
+
+impl<F> Transport for F
+where
+    F: FnMut(Frame),
+{
+    fn send_frame(&mut self, frame: Frame) {
+        self(frame)
+    }
+}
+
+/// Identifies an event listener across a transport.
+///
+/// It is only meaningful to the `Vdom` whose frames registered the listener.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ListenerId {
+    a: u32,
+    b: u32,
+}
+
+impl ListenerId {
+    /// Re-create a listener id from the parts returned by `ListenerId::parts`.
+    pub fn from_parts(a: u32, b: u32) -> ListenerId {
+        ListenerId { a, b }
+    }
+
+    /// Get this listener id's parts, for serialization.
+    pub fn parts(&self) -> (u32, u32) {
+        (self.a, self.b)
+    }
+}
+
+/// Applies frames from a remote `Vdom` to a container element.
+///
+/// When dropped, the applier empties its container and removes its event
+/// listeners.
+#[derive(Debug)]
+pub struct Applier {
+    container: crate::Element,
+    interpreter: ChangeListInterpreter,
+    // Kept alive for as long as the interpreter's listeners may invoke it.
+    _events_trampoline: crate::EventsTrampoline,
+}
+
+impl Applier {
+    /// Create a new applier that applies frames to the given container.
+    ///
+    /// The container is emptied, and then has the dummy element that every
+    /// `Vdom` starts out rendered as put in it. Whenever a listener that a
+    /// frame added is triggered, `on_event` is invoked with the listener's id
+    /// and the event.
+    pub fn new<F>(container: &crate::Element, on_event: F) -> Applier
+    where
+        F: 'static + Fn(ListenerId, web_sys::Event),
+    {
+        crate::vdom::initialize_container(container);
+        let applier = Applier {
+            container: container.clone(),
+            interpreter: ChangeListInterpreter::new(container, false),
+            _events_trampoline: events_trampoline(on_event),
+        };
+        applier
+            .interpreter
+            .init_events_trampoline(&applier._events_trampoline);
+        applier
+    }
+}
+
+impl Drop for Applier {
+    fn drop(&mut self) {
+        self.interpreter.unmount();
+        crate::vdom::empty_container(&self.container);
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "xxx-unstable-internal-use-only", not(target_arch = "wasm32")))] {
+        // The in-memory DOM never triggers events.
+        fn events_trampoline<F>(_on_event: F) -> crate::EventsTrampoline
+        where
+            F: 'static + Fn(ListenerId, web_sys::Event),
+        {
+        }
+
+        impl Applier {
+            /// Apply the next frame.
+            pub fn apply(&mut self, frame: &Frame) {
+                self.interpreter.apply_frame(frame);
+            }
+        }
+    } else {
+        use wasm_bindgen::prelude::*;
+
+        fn events_trampoline<F>(on_event: F) -> crate::EventsTrampoline
+        where
+            F: 'static + Fn(ListenerId, web_sys::Event),
+        {
+            Closure::wrap(Box::new(move |event, a, b| {
+                on_event(ListenerId::from_parts(a, b), event);
+            }) as Box<dyn Fn(web_sys::Event, u32, u32)>)
+        }
+
+        impl Applier {
+            /// Apply the next frame.
+            pub fn apply(&mut self, frame: &Frame) {
+                if frame.is_empty() {
+                    return;
+                }
+
+                // The JS interpreter reads text directly out of wasm's linear
+                // memory, so turn the frame's text offsets into pointers.
+                let base = frame.text.as_ptr() as u32;
+                let mut words = frame.words.clone();
+                relocate_text(&mut words, |offset, _length| Ok(base + offset))
+                    .expect_throw("frame should contain valid instructions");
+
+                self.interpreter
+                    .add_change_list_range(words.as_ptr() as usize, words.len() * 4);
+                self.interpreter.apply_changes(wasm_bindgen::memory());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> Frame {
+        let mut words = vec![];
+        Instruction::AddCachedString {
+            pointer: 0,
+            length: 3,
+            key: 0,
+        }
+        .encode(&mut words);
+        Instruction::CreateElement { tag_name_key: 0 }.encode(&mut words);
+        Instruction::CreateTextNode {
+            pointer: 3,
+            length: 5,
+        }
+        .encode(&mut words);
+        Frame::new(words, b"divhello".to_vec())
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let frame = frame();
+        assert_eq!(Frame::from_bytes(&frame.to_bytes()), Ok(frame));
+        assert_eq!(
+            Frame::from_bytes(&Frame::default().to_bytes()),
+            Ok(Frame::default())
+        );
+    }
+
+    #[test]
+    fn from_bytes_errors() {
+        let bytes = frame().to_bytes();
+        assert_eq!(Frame::from_bytes(&bytes[..2]), Err(FrameError::Truncated));
+        assert_eq!(Frame::from_bytes(&bytes[..12]), Err(FrameError::Truncated));
+        assert_eq!(
+            Frame::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(FrameError::InvalidText {
+                offset: 3,
+                length: 5
+            })
+        );
+
+        let mut bytes = bytes;
+        bytes[4] = 255;
+        assert_eq!(
+            Frame::from_bytes(&bytes),
+            Err(FrameError::Decode(DecodeError::UnknownOpcode(255)))
+        );
+    }
+
+    #[test]
+    fn relocate() {
+        let mut frame = frame();
+        relocate_text(&mut frame.words, |offset, _| Ok(offset + 100)).unwrap();
+        assert_eq!(frame.words[1], 100);
+        assert_eq!(frame.words[7], 103);
+        assert_eq!(frame.words[4..6], [10, 0]);
+    }
+}
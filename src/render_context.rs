@@ -1,11 +1,16 @@
 use crate::{
     cached::{Cached, TemplateId},
     cached_set::{CacheId, CachedSet},
-    Node, Render,
+    node::PortalTarget,
+    Node, Render, RootRender, VdomWeak,
 };
 use bumpalo::Bump;
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use std::fmt;
+use std::mem;
+
+/// A callback queued with `RenderContext::after_render`.
+pub(crate) type AfterRender = Box<dyn FnOnce(&mut dyn RootRender, VdomWeak)>;
 
 /// Common context available to all `Render` implementations.
 ///
@@ -30,6 +35,13 @@ pub struct RenderContext<'a> {
 
     pub(crate) templates: &'a mut FxHashMap<TemplateId, Option<CacheId>>,
 
+    pub(crate) after_render: Vec<AfterRender>,
+
+    // The ids of the portal targets that this render uses, so that the cached
+    // set knows which ones are still needed. Portals are constructed with a
+    // shared reference to the context.
+    portal_targets: crate::RefCell<FxHashSet<u32>>,
+
     // Prevent exhaustive matching on the rendering context, so we can always
     // add more members in a semver-compatible way.
     _non_exhaustive: (),
@@ -39,6 +51,7 @@ impl fmt::Debug for RenderContext<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("RenderContext")
             .field("bump", &self.bump)
+            .field("after_render", &self.after_render.len())
             .finish()
     }
 }
@@ -54,11 +67,72 @@ impl<'a> RenderContext<'a> {
                 bump,
                 cached_set,
                 templates,
+                after_render: vec![],
+                portal_targets: Default::default(),
                 _non_exhaustive: (),
             }
         }
     }
 
+    /// Queue a callback to run once this render's changes have been applied
+    /// to the physical DOM.
+    ///
+    /// This is for effects that need the DOM to reflect the new render, such
+    /// as scrolling a new message into view or restoring focus. Queued
+    /// callbacks run in order, after the whole render has been diffed and
+    /// applied, and are given the root rendering component and a handle to
+    /// the virtual DOM, just like event listeners are. Callbacks queued while
+    /// rendering to a string with `dodrio::ssr` never run.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// use dodrio::{builder::*, Node, NodeRef, Render, RenderContext};
+    ///
+    /// struct Chat {
+    ///     last_message: NodeRef,
+    /// }
+    ///
+    /// impl Chat {
+    ///     fn scroll_to_last_message(&self) {
+    ///         // Scroll `self.last_message.get()` into view...
+    ///     }
+    /// }
+    ///
+    /// impl<'a> Render<'a> for Chat {
+    ///     fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+    ///         cx.after_render(|root, _vdom| {
+    ///             root.unwrap_mut::<Chat>().scroll_to_last_message();
+    ///         });
+    ///
+    ///         // ...
+    /// #       p(&cx).node_ref(&self.last_message).finish()
+    ///     }
+    /// }
+    /// ```
+    pub fn after_render<F>(&mut self, callback: F)
+    where
+        F: 'static + FnOnce(&mut dyn RootRender, VdomWeak),
+    {
+        self.after_render.push(Box::new(callback));
+    }
+
+    /// Get the portal target for the given element.
+    pub(crate) fn portal_target(&self, element: &crate::Element) -> &'a PortalTarget {
+        let mut cached_set = self.cached_set.borrow_mut();
+        let target: *const PortalTarget = cached_set.portal_target(element);
+        // Safety: portal targets are boxed, and the cached set only removes
+        // them once no render uses them. This render's are recorded here.
+        let target = unsafe { &*target };
+        self.portal_targets.borrow_mut().insert(target.id);
+        target
+    }
+
+    /// Take the ids of the portal targets that this render has used.
+    pub(crate) fn take_portal_targets(&mut self) -> FxHashSet<u32> {
+        mem::take(&mut *self.portal_targets.borrow_mut())
+    }
+
     pub(crate) fn cache<F>(&mut self, pinned: bool, template: Option<CacheId>, f: F) -> CacheId
     where
         F: for<'b> FnOnce(&mut RenderContext<'b>) -> Node<'b>,
@@ -82,8 +156,11 @@ impl<'a> RenderContext<'a> {
         self.templates.insert(template_id, None);
 
         // Render the default `R` and save that as the template for all
-        // `Cached<R>`s.
+        // `Cached<R>`s. The default `R` is never really rendered, so drop
+        // anything it queued to run after rendering.
+        let queued = self.after_render.len();
         let cache_id = self.cache(true, None, |nested_cx| R::default().render(nested_cx));
+        self.after_render.truncate(queued);
         self.templates.insert(template_id, Some(cache_id));
         Some(cache_id)
     }
@@ -0,0 +1,172 @@
+//! Strategies for deciding when a scheduled render actually happens.
+//!
+//! By default, a `Vdom` renders during the next animation frame after
+//! `VdomWeak::schedule_render` or `VdomWeak::render` is called. Animation
+//! frames aren't always available, though: web workers and Node-based test
+//! runners may not have them, and browsers stop running them in background
+//! tabs. Use `VdomBuilder::scheduler` to configure a different strategy.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use dodrio::{builder::*, scheduler, Node, Render, RenderContext, Vdom};
+//!
+//! struct Hello;
+//!
+//! impl<'a> Render<'a> for Hello {
+//!     fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+//!         p(&cx).children([text("Hello!")]).finish()
+//!     }
+//! }
+//!
+//! # fn container() -> dodrio::Element { unimplemented!() }
+//! let manual = scheduler::Manual::new();
+//! let vdom = Vdom::builder()
+//!     .scheduler(manual.clone())
+//!     .mount(&container(), Hello);
+//!
+//! vdom.weak().schedule_render();
+//!
+//! // Nothing is rendered until the pending renders are run.
+//! assert!(manual.is_pending());
+//! manual.run();
+//! # vdom.forget();
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Decides when a scheduled render happens.
+///
+/// A virtual DOM coalesces the renders that are scheduled before the next one
+/// happens, so `schedule` is called at most once for every render.
+pub trait Scheduler {
+    /// Call `render` once, at some later point.
+    ///
+    /// `render` must not be called synchronously from within `schedule`.
+    fn schedule(&self, render: Box<dyn FnOnce()>);
+}
+
+/// Render during the next animation frame, with `requestAnimationFrame`.
+///
+/// This is the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AnimationFrame;
+
+/// Render on the micro-task queue, as soon as the currently running script
+/// and any micro-tasks queued before the render have finished.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Microtask;
+
+/// Render in a new task, with `setTimeout` and a delay of zero.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timeout;
+
+/// Render when the browser is idle, with `requestIdleCallback`.
+///
+/// Where `requestIdleCallback` isn't supported, this falls back to
+/// `setTimeout` with a delay of zero.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Idle;
+
+/// Only render when told to with `Manual::run`.
+///
+/// This is useful for tests, and for driving rendering from your own event
+/// loop. Cloning a `Manual` gives another handle to the same pending renders.
+#[derive(Clone, Default)]
+pub struct Manual {
+    pending: Rc<RefCell<Vec<Render>>>,
+}
+
+type Render = Box<dyn FnOnce()>;
+
+#[wasm_bindgen]
+extern "C" {
+    // These are bound as globals, rather than as methods of `Window`, so that
+    // they also work in web workers.
+    #[wasm_bindgen(js_name = requestAnimationFrame)]
+    fn request_animation_frame(f: &js_sys::Function) -> f64;
+
+    #[wasm_bindgen(js_name = requestIdleCallback)]
+    fn request_idle_callback(f: &js_sys::Function) -> f64;
+
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(f: &js_sys::Function, delay: i32) -> JsValue;
+}
+
+fn once(render: Box<dyn FnOnce()>) -> js_sys::Function {
+    Closure::once_into_js(render).unchecked_into()
+}
+
+impl Scheduler for AnimationFrame {
+    fn schedule(&self, render: Box<dyn FnOnce()>) {
+        request_animation_frame(&once(render));
+    }
+}
+
+impl Scheduler for Microtask {
+    fn schedule(&self, render: Box<dyn FnOnce()>) {
+        wasm_bindgen_futures::spawn_local(async move { render() });
+    }
+}
+
+impl Scheduler for Timeout {
+    fn schedule(&self, render: Box<dyn FnOnce()>) {
+        set_timeout(&once(render), 0);
+    }
+}
+
+impl Scheduler for Idle {
+    fn schedule(&self, render: Box<dyn FnOnce()>) {
+        let supported =
+            js_sys::Reflect::has(&js_sys::global(), &"requestIdleCallback".into()).unwrap_or(false);
+        if supported {
+            request_idle_callback(&once(render));
+        } else {
+            set_timeout(&once(render), 0);
+        }
+    }
+}
+
+impl Manual {
+    /// Construct a new manual scheduler, with no pending renders.
+    pub fn new() -> Manual {
+        Manual::default()
+    }
+
+    /// Are there any renders waiting for `run`?
+    pub fn is_pending(&self) -> bool {
+        !self.pending.borrow().is_empty()
+    }
+
+    /// Perform every pending render, and return how many there were.
+    ///
+    /// Renders that are scheduled while this is running, including those of a
+    /// virtual DOM that is busy running a listener, are left pending until the
+    /// next call.
+    pub fn run(&self) -> usize {
+        let pending = self.pending.replace(vec![]);
+        let n = pending.len();
+        for render in pending {
+            render();
+        }
+        n
+    }
+}
+
+impl fmt::Debug for Manual {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Manual")
+            .field("pending", &self.pending.borrow().len())
+            .finish()
+    }
+}
+
+impl Scheduler for Manual {
+    fn schedule(&self, render: Box<dyn FnOnce()>) {
+        self.pending.borrow_mut().push(render);
+    }
+}
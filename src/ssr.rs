@@ -0,0 +1,506 @@
+//! Server-side rendering of virtual DOM trees to HTML strings.
+//!
+//! Rendering a component to a string does not require a `Vdom`, a physical
+//! DOM, or even a Web browser, so it works on native targets as well. Event
+//! listeners are not part of the generated markup.
+//!
+//! ## Example
+//!
+//! ```
+//! use dodrio::{ssr, Node, Render, RenderContext};
+//!
+//! struct Greeting;
+//!
+//! impl<'a> Render<'a> for Greeting {
+//!     fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+//!         use dodrio::builder::*;
+//!
+//!         p(&cx)
+//!             .attr("class", "greeting")
+//!             .children([text("Hello & welcome!")])
+//!             .finish()
+//!     }
+//! }
+//!
+//! assert_eq!(
+//!     ssr::render_to_string(&Greeting),
+//!     r#"<p class="greeting">Hello &amp; welcome!</p>"#,
+//! );
+//! ```
+
+use crate::{
+    cached_set::CachedSet,
+    node::{ElementNode, FragmentNode, Node, NodeKind, RawHtmlNode, TextNode},
+    Render, RenderContext,
+};
+use bumpalo::Bump;
+use fxhash::FxHashMap;
+use std::fmt::{self, Write};
+
+const HTML_NAMESPACE: &str = "http://www.w3.org/1999/xhtml";
+
+/// Render the given component to a string of HTML.
+pub fn render_to_string<R>(component: &R) -> String
+where
+    R: for<'a> Render<'a>,
+{
+    let mut html = String::new();
+    render_to_writer(component, &mut html).expect("writing to a `String` should not fail");
+    html
+}
+
+/// Render the given component as HTML into the given writer.
+pub fn render_to_writer<R, W>(component: &R, out: &mut W) -> fmt::Result
+where
+    R: for<'a> Render<'a>,
+    W: Write,
+{
+    let bump = &Bump::new();
+    let cached_set = &crate::RefCell::new(CachedSet::default());
+    let templates = &mut FxHashMap::default();
+    let mut cx = RenderContext::new(bump, cached_set, templates);
+    let node = component.render(&mut cx);
+
+    let cached_set = cached_set.borrow();
+    write_node(&cached_set, out, &node, Parent::default())
+}
+
+/// The context an element's children are written within.
+#[derive(Clone, Copy, Default)]
+struct Parent<'a> {
+    // The namespace of the parent element, or `None` for HTML.
+    namespace: Option<&'a str>,
+    // Whether the parent's text content is raw text that must not be escaped,
+    // as it is for `<script>` and `<style>`.
+    raw_text: bool,
+}
+
+fn write_node<W>(cached_set: &CachedSet, out: &mut W, node: &Node, parent: Parent) -> fmt::Result
+where
+    W: Write,
+{
+    match node.kind {
+        NodeKind::Text(TextNode { text }) => {
+            if parent.raw_text {
+                out.write_str(text)
+            } else {
+                escape(out, text, false)
+            }
+        }
+        // Placeholders are written as empty comments, which hydration adopts.
+        NodeKind::Empty => out.write_str("<!---->"),
+        // Portals render their children into another container, so only their
+        // placeholder is written here.
+        NodeKind::Portal(_) => out.write_str("<!---->"),
+        // Raw HTML is trusted, so it is written without escaping, inside the
+        // same wrapper that the change list interpreter creates for it.
+        NodeKind::RawHtml(RawHtmlNode { html }) => {
+            out.write_str("<div>")?;
+            out.write_str(html)?;
+            out.write_str("</div>")
+        }
+        NodeKind::Element(element) => write_element(cached_set, out, element, parent),
+        NodeKind::Cached(ref c) => {
+            let (node, _template) = cached_set.get(c.id);
+            write_node(cached_set, out, node, parent)
+        }
+        NodeKind::Fragment(FragmentNode { children }) => {
+            for child in children {
+                write_node(cached_set, out, child, parent)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn write_element<W>(
+    cached_set: &CachedSet,
+    out: &mut W,
+    element: &ElementNode,
+    parent: Parent,
+) -> fmt::Result
+where
+    W: Write,
+{
+    let namespace = element.namespace.filter(|ns| *ns != HTML_NAMESPACE);
+
+    out.write_char('<')?;
+    out.write_str(element.tag_name)?;
+
+    // The HTML parser infers the namespace of `<svg>` and `<math>` on its own,
+    // but emitting `xmlns` keeps the markup correct for every other namespace
+    // and when it is consumed as XML.
+    if namespace != parent.namespace {
+        if let Some(namespace) = namespace {
+            out.write_str(" xmlns=\"")?;
+            escape(out, namespace, true)?;
+            out.write_char('"')?;
+        }
+    }
+
+    for attr in element.attributes {
+        // A `style` attribute is merged with the inline styles below.
+        if attr.name == "style" && !element.styles.is_empty() {
+            continue;
+        }
+
+        out.write_char(' ')?;
+        out.write_str(attr.name)?;
+
+        // Boolean attributes (see `ElementBuilder::bool_attr`) are written
+        // without a value, which the HTML parser treats as the empty string.
+        if attr.value.is_empty() && namespace.is_none() {
+            continue;
+        }
+
+        out.write_str("=\"")?;
+        escape(out, attr.value, true)?;
+        out.write_char('"')?;
+    }
+
+    // Class list tokens are written as a single `class` attribute.
+    if !element.classes.is_empty() {
+        out.write_str(" class=\"")?;
+        for (i, class) in element.classes.iter().enumerate() {
+            if i > 0 {
+                out.write_char(' ')?;
+            }
+            escape(out, class, true)?;
+        }
+        out.write_char('"')?;
+    }
+
+    // Inline styles are written as a single `style` attribute, after the
+    // declarations of the `style` attribute if there is one, which hydration
+    // expects to be in exactly this format.
+    if !element.styles.is_empty() {
+        out.write_str(" style=\"")?;
+        let style_attribute = element.attributes.iter().find(|attr| attr.name == "style");
+        if let Some(attr) = style_attribute {
+            escape(out, attr.value, true)?;
+        }
+        for (i, style) in element.styles.iter().enumerate() {
+            if i > 0 || style_attribute.is_some() {
+                out.write_str("; ")?;
+            }
+            escape(out, style.name, true)?;
+            out.write_str(": ")?;
+            escape(out, style.value, true)?;
+        }
+        out.write_char('"')?;
+    }
+
+    if namespace.is_none() && is_void_element(element.tag_name) {
+        debug_assert!(
+            element.children.is_empty(),
+            "void elements cannot have children"
+        );
+        return out.write_char('>');
+    }
+
+    if namespace.is_some() && element.children.is_empty() {
+        return out.write_str("/>");
+    }
+
+    out.write_char('>')?;
+
+    let parent = Parent {
+        namespace,
+        raw_text: namespace.is_none() && is_raw_text_element(element.tag_name),
+    };
+    if parent.raw_text {
+        // The element's end tag could be split across several text nodes, so
+        // its contents are checked as a whole.
+        let mut text = String::new();
+        for child in element.children {
+            write_node(cached_set, &mut text, child, parent)?;
+        }
+        write_raw_text(out, &text, element.tag_name)?;
+    } else {
+        for child in element.children {
+            write_node(cached_set, out, child, parent)?;
+        }
+    }
+
+    out.write_str("</")?;
+    out.write_str(element.tag_name)?;
+    out.write_char('>')
+}
+
+/// Void elements are written without a closing tag, and may not have children.
+///
+/// https://html.spec.whatwg.org/multipage/syntax.html#void-elements
+fn is_void_element(tag_name: &str) -> bool {
+    matches!(
+        tag_name,
+        "area"
+            | "base"
+            | "br"
+            | "col"
+            | "embed"
+            | "hr"
+            | "img"
+            | "input"
+            | "link"
+            | "meta"
+            | "param"
+            | "source"
+            | "track"
+            | "wbr"
+    )
+}
+
+/// The contents of raw text elements are not parsed as markup, and therefore
+/// must not be escaped either.
+///
+/// https://html.spec.whatwg.org/multipage/syntax.html#raw-text-elements
+fn is_raw_text_element(tag_name: &str) -> bool {
+    matches!(tag_name, "script" | "style")
+}
+
+/// Write the contents of a raw text element. They can't be escaped, but they
+/// must not contain the element's end tag, which would end it early, and let
+/// the rest of the text be parsed as markup. Every `</` that the element's tag
+/// name follows, in any case, is written as `<\/` instead, which scripts and
+/// style sheets read as `</` inside their strings.
+fn write_raw_text<W>(out: &mut W, text: &str, tag_name: &str) -> fmt::Result
+where
+    W: Write,
+{
+    let mut written = 0;
+    for (i, _) in text.match_indices("</") {
+        let rest = &text.as_bytes()[i + 2..];
+        if rest.len() >= tag_name.len()
+            && rest[..tag_name.len()].eq_ignore_ascii_case(tag_name.as_bytes())
+        {
+            out.write_str(&text[written..=i])?;
+            out.write_char('\\')?;
+            written = i + 1;
+        }
+    }
+    out.write_str(&text[written..])
+}
+
+/// Write `s` with the characters that are significant in markup replaced by
+/// their character references. Double quotes are only significant inside
+/// attribute values.
+fn escape<W>(out: &mut W, s: &str, in_attribute: bool) -> fmt::Result
+where
+    W: Write,
+{
+    let mut written = 0;
+    for (i, b) in s.bytes().enumerate() {
+        let reference = match b {
+            b'&' => "&amp;",
+            b'<' => "&lt;",
+            b'>' => "&gt;",
+            b'"' if in_attribute => "&quot;",
+            _ => continue,
+        };
+        out.write_str(&s[written..i])?;
+        out.write_str(reference)?;
+        written = i + 1;
+    }
+    out.write_str(&s[written..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_to_string;
+    use crate::{builder::*, Cached, Node, Render, RenderContext};
+
+    struct RenderFn<F>(F)
+    where
+        F: for<'a> Fn(&mut RenderContext<'a>) -> Node<'a>;
+
+    impl<'a, F> Render<'a> for RenderFn<F>
+    where
+        F: for<'b> Fn(&mut RenderContext<'b>) -> Node<'b>,
+    {
+        fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+            (self.0)(cx)
+        }
+    }
+
+    #[test]
+    fn escapes_text_and_attributes() {
+        let html = render_to_string(&RenderFn(|cx| {
+            a(&cx)
+                .attr("href", "/search?q=\"dodrio\"&lang=en")
+                .children([text("<b>Tom & Jerry</b>")])
+                .finish()
+        }));
+        assert_eq!(
+            html,
+            "<a href=\"/search?q=&quot;dodrio&quot;&amp;lang=en\">\
+             &lt;b&gt;Tom &amp; Jerry&lt;/b&gt;</a>"
+        );
+    }
+
+    #[test]
+    fn void_and_boolean_attributes() {
+        let html = render_to_string(&RenderFn(|cx| {
+            div(&cx)
+                .children([
+                    input(&cx)
+                        .attr("type", "checkbox")
+                        .bool_attr("checked", true)
+                        .bool_attr("disabled", false)
+                        .finish(),
+                    br(&cx).finish(),
+                ])
+                .finish()
+        }));
+        assert_eq!(html, "<div><input type=\"checkbox\" checked><br></div>");
+    }
+
+    #[test]
+    fn svg_elements() {
+        let html = render_to_string(&RenderFn(|cx| {
+            div(&cx)
+                .children([svg(cx.bump)
+                    .attr("viewBox", "0 0 10 10")
+                    .children([circle(cx.bump).attr("r", "5").finish()])
+                    .finish()])
+                .finish()
+        }));
+        assert_eq!(
+            html,
+            "<div><svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 10 10\">\
+             <circle r=\"5\"/></svg></div>"
+        );
+    }
+
+    #[test]
+    fn raw_text_elements_are_not_escaped() {
+        let html = render_to_string(&RenderFn(|cx| {
+            style(&cx)
+                .children([text("ul > li { color: red; }")])
+                .finish()
+        }));
+        assert_eq!(html, "<style>ul > li { color: red; }</style>");
+    }
+
+    #[test]
+    fn end_tags_in_raw_text_elements_are_broken_up() {
+        let html = render_to_string(&RenderFn(|cx| {
+            div(&cx)
+                .children([
+                    script(&cx)
+                        .children([text("var s = \"</SCRIPT><img src=x onerror=alert(1)>\";")])
+                        .finish(),
+                    style(&cx)
+                        .children([text("a::after { content: \"<"), text("/style>\" }")])
+                        .finish(),
+                ])
+                .finish()
+        }));
+        assert_eq!(
+            html,
+            "<div><script>var s = \"<\\/SCRIPT><img src=x onerror=alert(1)>\";</script>\
+             <style>a::after { content: \"<\\/style>\" }</style></div>"
+        );
+    }
+
+    #[test]
+    fn cached_nodes() {
+        #[derive(Default)]
+        struct Item;
+
+        impl<'a> Render<'a> for Item {
+            fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+                li(&cx).children([text("cached")]).finish()
+            }
+        }
+
+        let item = Cached::new(Item);
+        let html = render_to_string(&RenderFn(move |cx| {
+            ul(&cx)
+                .children([item.render(cx), item.render(cx)])
+                .finish()
+        }));
+        assert_eq!(html, "<ul><li>cached</li><li>cached</li></ul>");
+    }
+
+    #[test]
+    fn fragments_are_written_inline() {
+        let html = render_to_string(&RenderFn(|cx| {
+            let items = fragment(cx.bump, [li(&cx).finish(), li(&cx).finish()]);
+            fragment(
+                cx.bump,
+                [ul(&cx).children([items]).finish(), fragment(cx.bump, [])],
+            )
+        }));
+        assert_eq!(html, "<ul><li></li><li></li></ul>");
+    }
+
+    #[test]
+    fn empty_nodes_are_comments() {
+        let html = render_to_string(&RenderFn(|cx| {
+            div(&cx).children([empty(), text("hi")]).finish()
+        }));
+        assert_eq!(html, "<div><!---->hi</div>");
+    }
+
+    #[test]
+    fn raw_html_is_not_escaped() {
+        let html = render_to_string(&RenderFn(|cx| {
+            article(&cx)
+                .children([raw_html("<p>Hello, <em>world</em>!</p>")])
+                .finish()
+        }));
+        assert_eq!(
+            html,
+            "<article><div><p>Hello, <em>world</em>!</p></div></article>"
+        );
+    }
+
+    #[test]
+    fn classes_are_written_as_the_class_attribute() {
+        let html = render_to_string(&RenderFn(|cx| {
+            li(&cx)
+                .class("todo")
+                .class_if("completed", true)
+                .class_if("editing", false)
+                .finish()
+        }));
+        assert_eq!(html, "<li class=\"todo completed\"></li>");
+    }
+
+    #[test]
+    fn styles_are_written_as_the_style_attribute() {
+        let html = render_to_string(&RenderFn(|cx| {
+            div(&cx)
+                .attr("id", "x")
+                .style("color", "red")
+                .style("font-family", "\"Fira Sans\"")
+                .finish()
+        }));
+        assert_eq!(
+            html,
+            "<div id=\"x\" style=\"color: red; font-family: &quot;Fira Sans&quot;\"></div>"
+        );
+    }
+
+    #[test]
+    fn class_attribute_and_class_names_are_one_attribute() {
+        let html = render_to_string(&RenderFn(|cx| {
+            li(&cx)
+                .attr("class", "todo big")
+                .class("completed")
+                .finish()
+        }));
+        assert_eq!(html, "<li class=\"todo big completed\"></li>");
+    }
+
+    #[test]
+    fn style_attribute_and_styles_are_one_attribute() {
+        let html = render_to_string(&RenderFn(|cx| {
+            div(&cx)
+                .attr("style", "color: red")
+                .style("left", "0px")
+                .finish()
+        }));
+        assert_eq!(html, "<div style=\"color: red; left: 0px\"></div>");
+    }
+}
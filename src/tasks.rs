@@ -0,0 +1,106 @@
+//! Futures that a virtual DOM drives on behalf of its async listeners, and
+//! cancels when it is dropped.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll, Waker};
+
+/// The set of futures spawned by a virtual DOM that haven't completed yet.
+#[derive(Default)]
+pub(crate) struct Tasks {
+    tasks: RefCell<Vec<Weak<RefCell<Task>>>>,
+}
+
+struct Task {
+    // `None` once the task has completed or been cancelled, or while it is
+    // being polled.
+    future: Option<Pin<Box<dyn Future<Output = ()>>>>,
+    cancelled: bool,
+    waker: Option<Waker>,
+}
+
+/// The future that is actually spawned on the micro-task queue. It drives its
+/// task's future until it completes or is cancelled.
+struct Spawned(Rc<RefCell<Task>>);
+
+impl fmt::Debug for Tasks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tasks = self.tasks.borrow();
+        let running = tasks.iter().filter(|t| t.strong_count() > 0).count();
+        f.debug_struct("Tasks").field("running", &running).finish()
+    }
+}
+
+impl Tasks {
+    /// Spawn the given future, which will be dropped without being polled
+    /// again if `cancel_all` is called before it completes.
+    pub(crate) fn spawn<F>(&self, future: F)
+    where
+        F: 'static + Future<Output = ()>,
+    {
+        let task = Rc::new(RefCell::new(Task {
+            future: Some(Box::pin(future)),
+            cancelled: false,
+            waker: None,
+        }));
+
+        let mut tasks = self.tasks.borrow_mut();
+        tasks.retain(|t| t.strong_count() > 0);
+        tasks.push(Rc::downgrade(&task));
+
+        wasm_bindgen_futures::spawn_local(Spawned(task));
+    }
+
+    /// Cancel every task that hasn't completed yet, dropping their futures.
+    pub(crate) fn cancel_all(&self) {
+        let tasks = self.tasks.replace(vec![]);
+        for task in tasks.iter().filter_map(Weak::upgrade) {
+            // Take the future out before dropping it, in case dropping it
+            // spawns or cancels other tasks.
+            let (future, waker) = {
+                let mut task = task.borrow_mut();
+                task.cancelled = true;
+                (task.future.take(), task.waker.take())
+            };
+            drop(future);
+
+            // Wake the spawned future so that it finishes and the executor
+            // lets go of it.
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Future for Spawned {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let mut future = {
+            let mut task = self.0.borrow_mut();
+            if task.cancelled {
+                return Poll::Ready(());
+            }
+            task.waker = Some(cx.waker().clone());
+            task.future
+                .take()
+                .expect("should not poll a completed task")
+        };
+
+        // Don't hold the borrow while polling, since the future may drop the
+        // virtual DOM and cancel this task.
+        let poll = future.as_mut().poll(cx);
+
+        let mut task = self.0.borrow_mut();
+        if poll.is_pending() && !task.cancelled {
+            task.future = Some(future);
+            return Poll::Pending;
+        }
+        task.waker = None;
+        Poll::Ready(())
+    }
+}
@@ -2,20 +2,26 @@ use super::change_list::ChangeListPersistentState;
 use super::RootRender;
 use crate::cached::TemplateId;
 use crate::cached_set::{CacheId, CachedSet};
+use crate::diff::Diff;
 use crate::events::EventsRegistry;
 use crate::node::{Node, NodeKey};
+use crate::remote::{ListenerId, Transport};
+use crate::render_context::AfterRender;
+use crate::scheduler::{self, Scheduler};
+use crate::tasks::Tasks;
 use crate::RenderContext;
 use bumpalo::Bump;
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use std::cell::Cell;
 use std::cell::RefCell;
+use std::cmp;
 use std::fmt;
 use std::future::Future;
 use std::mem;
 use std::mem::ManuallyDrop;
 use std::rc::{Rc, Weak};
+use std::time::Duration;
 use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
 
 /// A strong handle to a mounted virtual DOM.
@@ -48,7 +54,16 @@ pub(crate) struct VdomInner {
 }
 
 pub(crate) struct VdomInnerShared {
-    pub(crate) render_scheduled: Cell<Option<js_sys::Promise>>,
+    // Whether a render has been scheduled and hasn't started yet.
+    pub(crate) render_scheduled: Cell<bool>,
+    // Whether the scheduler has our callback for doing scheduled work.
+    work_scheduled: Cell<bool>,
+    // If anything is waiting for the scheduled render, the promise that
+    // resolves once it happens, and the function that resolves it.
+    render_promise: Cell<Option<(js_sys::Promise, js_sys::Function)>>,
+    scheduler: Rc<dyn Scheduler>,
+    // The futures of async listeners that are still running.
+    tasks: Tasks,
 }
 
 pub(crate) struct VdomInnerExclusive {
@@ -59,31 +74,71 @@ pub(crate) struct VdomInnerExclusive {
 
     dom_buffers: Option<[Bump; 2]>,
     change_list: ManuallyDrop<ChangeListPersistentState>,
-    container: crate::Element,
+    // `None` when the change lists are applied remotely.
+    container: Option<crate::Element>,
     events_registry: Option<Rc<RefCell<EventsRegistry>>>,
     events_trampoline: Option<crate::EventsTrampoline>,
+    // Always `Some` after mounting. A weak handle to this virtual DOM, for
+    // invoking lifecycle callbacks.
+    weak: Option<VdomWeak>,
     cached_set: crate::RefCell<CachedSet>,
     templates: FxHashMap<TemplateId, Option<CacheId>>,
 
     // Actually a reference into `self.dom_buffers[0]` or if `self.component` is
     // caching renders, into `self.component`'s bump.
     current_root: Option<Node<'static>>,
+
+    // How much to diff at a time when rendering incrementally, or `None` to
+    // render all at once.
+    budget: Option<RenderBudget>,
+    // The render that is being diffed incrementally, if any.
+    pending: Option<PendingRender>,
+}
+
+// A render whose diff is only partly done. Its changes are only applied once
+// the diff is finished, so that the physical DOM never shows half a render.
+struct PendingRender {
+    // Taken out of `VdomInnerExclusive` until the render is finished.
+    dom_buffers: [Bump; 2],
+    // Actually a reference into `dom_buffers[1]`.
+    new_contents: &'static Node<'static>,
+    // Actually references into `dom_buffers`, and the cached set.
+    diff: Diff<'static>,
+    // Whether to hydrate the container before diffing.
+    hydrate: bool,
+    next_temporary: u32,
+    cache_roots: FxHashSet<CacheId>,
+    // The portal targets that the new contents render into, outside of cached
+    // renders.
+    portal_targets: FxHashSet<u32>,
+    after_render: Vec<AfterRender>,
+    // Resolves the promise of `VdomWeak::render` futures that are waiting for
+    // this render.
+    resolve: Option<js_sys::Function>,
 }
 
 unsafe fn extend_node_lifetime<'a>(node: Node<'a>) -> Node<'static> {
     mem::transmute(node)
 }
 
+unsafe fn extend_node_ref_lifetime(node: &Node<'static>) -> &'static Node<'static> {
+    mem::transmute(node)
+}
+
 impl fmt::Debug for VdomInnerShared {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let render_scheduled = Cell::new(None);
-        self.render_scheduled.swap(&render_scheduled);
-        let render_scheduled = render_scheduled.into_inner();
+        let render_promise = Cell::new(None);
+        self.render_promise.swap(&render_promise);
+        let render_promise = render_promise.into_inner();
         let r = f
             .debug_struct("VdomInnerShared")
-            .field("render_scheduled", &render_scheduled)
+            .field("render_scheduled", &self.render_scheduled)
+            .field("work_scheduled", &self.work_scheduled)
+            .field("render_promise", &render_promise)
+            .field("scheduler", &"..")
+            .field("tasks", &self.tasks)
             .finish();
-        self.render_scheduled.set(render_scheduled);
+        self.render_promise.set(render_promise);
         r
     }
 }
@@ -98,13 +153,40 @@ impl fmt::Debug for VdomInnerExclusive {
             .field("events_registry", &self.events_registry)
             .field("events_trampoline", &"..")
             .field("current_root", &self.current_root)
+            .field("budget", &self.budget)
+            .field("pending", &self.pending.as_ref().map(|_| ".."))
             .finish()
     }
 }
 
+impl Drop for VdomInnerShared {
+    fn drop(&mut self) {
+        self.tasks.cancel_all();
+    }
+}
+
 impl Drop for VdomInnerExclusive {
     fn drop(&mut self) {
         debug!("Dropping VdomInnerExclusive");
+        self.tear_down();
+    }
+}
+
+impl VdomInnerExclusive {
+    // Remove the rendered contents from the container and clean up after them,
+    // including invoking their elements' `on_unmount` callbacks. Does nothing
+    // if the virtual DOM is already torn down.
+    fn tear_down(&mut self) {
+        if self.events_registry.is_none() {
+            return;
+        }
+
+        // Apply any render in progress first, so that its elements are
+        // unmounted along with the rest.
+        if self.component.is_some() {
+            self.finish_render();
+        }
+        let unmount_calls = self.change_list.unmount_all();
 
         // Make sure that we clean up our JS listeners and all that before we
         // empty the container.
@@ -113,23 +195,39 @@ impl Drop for VdomInnerExclusive {
         }
 
         let registry = self.events_registry.take().unwrap_throw();
-        let mut registry = registry.borrow_mut();
-        registry.clear_active_listeners();
+        registry.borrow_mut().clear_active_listeners();
+
+        if let Some(container) = &self.container {
+            empty_container(container);
+        }
 
-        empty_container(&self.container);
+        // Only now are the elements removed from the physical DOM.
+        if let (Some(vdom), true) = (self.weak.clone(), self.component.is_some()) {
+            for call in unmount_calls {
+                call.invoke(self.component_raw_mut(), vdom.clone());
+            }
+        }
     }
 }
 
 cfg_if::cfg_if! {
     if #[cfg(all(feature = "xxx-unstable-internal-use-only", not(target_arch = "wasm32")))] {
-        fn empty_container(_container: &crate::Element) {}
-        fn initialize_container(_container: &crate::Element) {}
+        pub(crate) fn empty_container(container: &crate::Element) {
+            container.set_text_content("");
+        }
+
+        pub(crate) fn initialize_container(container: &crate::Element) {
+            empty_container(container);
+
+            // Create the dummy `<div/>` child in the container.
+            container.append_child(&crate::native_dom::Node::create_element("div"));
+        }
     } else {
-        fn empty_container(container: &crate::Element) {
+        pub(crate) fn empty_container(container: &crate::Element) {
             container.set_inner_html("");
         }
 
-        fn initialize_container(container: &crate::Element) {
+        pub(crate) fn initialize_container(container: &crate::Element) {
             empty_container(container);
 
             // Create the dummy `<div/>` child in the container.
@@ -166,22 +264,106 @@ impl Vdom {
     pub fn with_boxed_root_render(
         container: &crate::Element,
         component: Box<dyn RootRender>,
+    ) -> Vdom {
+        Self::mount(container, component, VdomBuilder::new())
+    }
+
+    /// Mount a new `Vdom` in the given container element, hydrating the
+    /// server-rendered markup that is already inside it.
+    ///
+    /// The container's contents should be the result of rendering the same
+    /// component with `dodrio::ssr`. Instead of emptying the container and
+    /// re-creating those physical DOM nodes, they are reused and only have
+    /// their event listeners attached. Any physical nodes that don't match the
+    /// rendered virtual DOM are reported as warnings and patched.
+    pub fn hydrate<R>(container: &crate::Element, component: R) -> Vdom
+    where
+        R: RootRender,
+    {
+        Vdom::builder().hydrate(true).mount(container, component)
+    }
+
+    /// Get a builder to configure how a new `Vdom` is mounted.
+    ///
+    /// `Vdom::new` and `Vdom::hydrate` cover the common configurations, and
+    /// the builder is for opting into anything else, such as event delegation.
+    #[inline]
+    pub fn builder() -> VdomBuilder {
+        VdomBuilder::new()
+    }
+
+    /// Create a new `Vdom` whose changes are applied remotely.
+    ///
+    /// Instead of being applied to a local container element, each change list
+    /// is sent through the `transport` as a `dodrio::remote::Frame`, for a
+    /// `dodrio::remote::Applier` to apply elsewhere. Events that the applier
+    /// reports should be passed to `VdomWeak::dispatch_event`.
+    ///
+    /// The physical elements that the applier creates can't be handed back, so
+    /// `NodeRef`s are never filled in, and `on_mount` and `on_unmount`
+    /// callbacks are never invoked.
+    ///
+    /// Use `VdomBuilder::mount_with_transport` to configure the `Vdom`, for
+    /// example with a scheduler or a render budget.
+    pub fn with_transport<R, T>(component: R, transport: T) -> Vdom
+    where
+        R: RootRender,
+        T: 'static + Transport,
+    {
+        VdomBuilder::new().mount_with_transport(component, transport)
+    }
+
+    fn mount(
+        container: &crate::Element,
+        component: Box<dyn RootRender>,
+        config: VdomBuilder,
+    ) -> Vdom {
+        let change_list = ChangeListPersistentState::new(container, config.delegate_events);
+        Self::mount_with_change_list(Some(container), change_list, component, config)
+    }
+
+    fn mount_with_change_list(
+        container: Option<&crate::Element>,
+        change_list: ChangeListPersistentState,
+        component: Box<dyn RootRender>,
+        config: VdomBuilder,
     ) -> Vdom {
         crate::strace::init_strace();
 
         let dom_buffers = [Bump::new(), Bump::new()];
-        let change_list = ManuallyDrop::new(ChangeListPersistentState::new(container));
-
-        // Create a dummy `<div/>` in our container.
-        initialize_container(container);
-        let current_root =
-            Node::element(&dom_buffers[0], NodeKey::NONE, "div", &[], &[], &[], None);
-        let current_root = Some(unsafe { extend_node_lifetime(current_root) });
+        let change_list = ManuallyDrop::new(change_list);
+
+        // When hydrating, there is no current root: the first render walks the
+        // server-rendered physical DOM instead of diffing. Otherwise, create a
+        // dummy `<div/>` in our container. Remote appliers create their own
+        // dummy `<div/>`.
+        let current_root = match container {
+            Some(container)
+                if config.hydrate
+                    && crate::hydrate::initialize_container_for_hydration(container) =>
+            {
+                None
+            }
+            container => {
+                if let Some(container) = container {
+                    initialize_container(container);
+                }
+                let current_root =
+                    Node::element(&dom_buffers[0], NodeKey::NONE, "div", &[], &[], &[], None);
+                Some(unsafe { extend_node_lifetime(current_root) })
+            }
+        };
 
-        let container = container.clone();
+        let container = container.cloned();
         let inner = Rc::new(VdomInner {
             shared: VdomInnerShared {
-                render_scheduled: Cell::new(None),
+                render_scheduled: Cell::new(false),
+                work_scheduled: Cell::new(false),
+                render_promise: Cell::new(None),
+                scheduler: config
+                    .scheduler
+                    .unwrap_or_else(|| Rc::new(scheduler::AnimationFrame)),
+                tasks: Tasks::default(),
             },
             exclusive: RefCell::new(VdomInnerExclusive {
                 component: Some(component),
@@ -191,12 +373,16 @@ impl Vdom {
                 current_root,
                 events_registry: None,
                 events_trampoline: None,
+                weak: None,
                 cached_set: crate::RefCell::new(Default::default()),
                 templates: Default::default(),
+                budget: config.budget,
+                pending: None,
             }),
         });
 
         let (events_registry, events_trampoline) = EventsRegistry::new(Rc::downgrade(&inner));
+        let weak = VdomWeak::new(&inner);
 
         {
             let mut inner = inner.exclusive.borrow_mut();
@@ -204,8 +390,10 @@ impl Vdom {
             inner.change_list.init_events_trampoline(&events_trampoline);
             debug_assert!(inner.events_trampoline.is_none());
             inner.events_trampoline = Some(events_trampoline);
+            inner.weak = Some(weak);
 
-            // Diff and apply the `contents` against our dummy `<div/>`.
+            // Diff and apply the `contents` against our dummy `<div/>`, or
+            // hydrate the server-rendered contents.
             inner.render();
         }
 
@@ -220,17 +408,48 @@ impl Vdom {
         R: RootRender,
     {
         let mut exclusive = self.inner.exclusive.borrow_mut();
+        exclusive.finish_render();
         let component = Box::new(component) as Box<dyn RootRender>;
         exclusive.component = Some(component);
         exclusive.render();
     }
 
+    /// Immediately re-render and diff, and return a disassembly of the
+    /// resulting change list. Only for internal testing and debugging
+    /// purposes.
+    #[cfg(feature = "xxx-unstable-internal-use-only")]
+    pub fn immediately_render_and_disassemble<R>(
+        &self,
+        component: R,
+    ) -> crate::change_list::disassemble::Disassembly
+    where
+        R: RootRender,
+    {
+        let mut exclusive = self.inner.exclusive.borrow_mut();
+        exclusive.finish_render();
+        exclusive.change_list.record_disassembly();
+        let component = Box::new(component) as Box<dyn RootRender>;
+        exclusive.component = Some(component);
+        exclusive.render();
+        exclusive.change_list.take_disassembly().unwrap_throw()
+    }
+
     /// Run this virtual DOM and its listeners forever and never unmount it.
     #[inline]
     pub fn forget(self) {
         mem::forget(self);
     }
 
+    /// Re-render and apply the changes to the physical DOM right away, rather
+    /// than waiting for the next animation frame.
+    ///
+    /// This also completes any render that was already scheduled, since it is
+    /// no longer necessary. See `VdomWeak::render_now` for details.
+    #[inline]
+    pub fn flush(&self) -> Result<(), FlushError> {
+        self.inner.flush()
+    }
+
     /// Get a weak handle to this virtual DOM.
     #[inline]
     pub fn weak(&self) -> VdomWeak {
@@ -241,14 +460,278 @@ impl Vdom {
     /// root render component.
     #[inline]
     pub fn unmount(self) -> Box<dyn RootRender> {
-        Rc::try_unwrap(self.inner.clone())
+        let mut exclusive = Rc::try_unwrap(self.inner)
             .map_err(|_| ())
             .unwrap_throw()
             .exclusive
-            .into_inner()
-            .component
+            .into_inner();
+        exclusive.tear_down();
+        exclusive.component.take().unwrap_throw()
+    }
+}
+
+/// A builder for mounting a `Vdom` with a non-default configuration.
+///
+/// Get one with `Vdom::builder`, configure it, and then `mount` it:
+///
+/// ```no_run
+/// use dodrio::{builder::*, Node, Render, RenderContext, Vdom};
+///
+/// struct Hello;
+///
+/// impl<'a> Render<'a> for Hello {
+///     fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+///         button(&cx)
+///             .on("click", |_root, _vdom, _event| {})
+///             .children([text("Hello!")])
+///             .finish()
+///     }
+/// }
+///
+/// # fn container() -> dodrio::Element { unimplemented!() }
+/// let vdom = Vdom::builder()
+///     .delegate_events(true)
+///     .mount(&container(), Hello);
+/// # vdom.forget();
+/// ```
+#[derive(Clone, Default)]
+pub struct VdomBuilder {
+    delegate_events: bool,
+    hydrate: bool,
+    scheduler: Option<Rc<dyn Scheduler>>,
+    budget: Option<RenderBudget>,
+}
+
+impl fmt::Debug for VdomBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VdomBuilder")
+            .field("delegate_events", &self.delegate_events)
+            .field("hydrate", &self.hydrate)
+            .field("scheduler", &self.scheduler.as_ref().map(|_| ".."))
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+impl VdomBuilder {
+    /// Construct a builder with the default configuration, which is the same
+    /// as what `Vdom::new` uses.
+    #[inline]
+    pub fn new() -> VdomBuilder {
+        VdomBuilder::default()
+    }
+
+    /// Whether to delegate event listeners to the container. Defaults to
+    /// `false`.
+    ///
+    /// Normally, every listener is added to its own element. When delegating,
+    /// there is instead a single physical listener for each event type on the
+    /// container, and when an event fires, the chain of elements from its
+    /// target up to the container is walked to invoke the listeners that were
+    /// registered on them. This makes creating and removing many elements with
+    /// listeners, say rows in a large table, much cheaper.
+    ///
+    /// Events still bubble from the innermost element outwards, and calling
+    /// `stopPropagation` in a listener stops the walk, like it does for native
+    /// listeners. Events that don't bubble, like `focus`, are only dispatched
+    /// to their target. The `currentTarget` of the event is the element the
+    /// listener was registered on.
+    ///
+    /// Listeners with non-default `ListenerOptions` are always added to their
+    /// element, since capture-phase, passive, and once listeners can't be
+    /// emulated by a bubbling listener on the container. As a result, the
+    /// order of a delegated listener and a bubbling listener with options
+    /// differs from that of native listeners: the latter is called first, when
+    /// the event bubbles through its element on the way to the container, even
+    /// if it is on an outer element. Likewise, stopping propagation in a
+    /// delegated listener doesn't keep the bubbling listeners with options
+    /// inside the container from being called, since they already were. Give
+    /// listeners that must run in a particular order relative to each other the
+    /// same kind of options.
+    #[inline]
+    pub fn delegate_events(mut self, delegate_events: bool) -> Self {
+        self.delegate_events = delegate_events;
+        self
+    }
+
+    /// Whether to hydrate the server-rendered markup that is already inside the
+    /// container, like `Vdom::hydrate` does. Defaults to `false`.
+    #[inline]
+    pub fn hydrate(mut self, hydrate: bool) -> Self {
+        self.hydrate = hydrate;
+        self
+    }
+
+    /// The strategy for deciding when scheduled renders happen. Defaults to
+    /// `dodrio::scheduler::AnimationFrame`.
+    ///
+    /// See the `dodrio::scheduler` module for the built-in strategies.
+    #[inline]
+    pub fn scheduler<S>(mut self, scheduler: S) -> Self
+    where
+        S: 'static + Scheduler,
+    {
+        self.scheduler = Some(Rc::new(scheduler));
+        self
+    }
+
+    /// Render incrementally, diffing only as much as the `budget` allows at a
+    /// time. Defaults to `None`, which diffs each render all at once.
+    ///
+    /// With a budget, a scheduled render diffs until the budget is spent, and
+    /// then yields, asking the scheduler to continue the diff later. Once the
+    /// diff is finished, all of its changes are applied to the physical DOM at
+    /// once, so a half-diffed render is never visible. This keeps rendering
+    /// very large trees from blocking the main thread for long.
+    ///
+    /// The initial render when mounting, `Vdom::flush`, and
+    /// `VdomWeak::render_now` always render all at once. Anything that needs
+    /// the root rendering component, such as a listener or `with_component`,
+    /// first finishes the render in progress all at once.
+    ///
+    /// Creating a new subtree, and diffing cached or keyed children that have
+    /// moved around, are each done in one go, so a slice can overrun its budget.
+    #[inline]
+    pub fn render_budget(mut self, budget: Option<RenderBudget>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Mount a new `Vdom` with this configuration in the given container
+    /// element with the given root rendering component.
+    pub fn mount<R>(self, container: &crate::Element, component: R) -> Vdom
+    where
+        R: RootRender,
+    {
+        Vdom::mount(container, Box::new(component) as Box<dyn RootRender>, self)
+    }
+
+    /// Create a new `Vdom` with this configuration whose changes are applied
+    /// remotely, like `Vdom::with_transport` does.
+    ///
+    /// The remote `Applier` decides how its listeners are added, so
+    /// `delegate_events` has no effect, and neither does `hydrate`.
+    pub fn mount_with_transport<R, T>(self, component: R, transport: T) -> Vdom
+    where
+        R: RootRender,
+        T: 'static + Transport,
+    {
+        let change_list = ChangeListPersistentState::with_transport(Box::new(transport));
+        Vdom::mount_with_change_list(
+            None,
+            change_list,
+            Box::new(component) as Box<dyn RootRender>,
+            self,
+        )
+    }
+}
+
+impl VdomInner {
+    /// Render synchronously, unless something else already has exclusive
+    /// access, and resolve the scheduled render's promise.
+    fn flush(&self) -> Result<(), FlushError> {
+        let mut exclusive = self
+            .exclusive
+            .try_borrow_mut()
+            .map_err(|_| FlushError::Reentrant)?;
+
+        // The render in progress, if any, is out of date, but its promise
+        // still needs resolving.
+        exclusive.finish_render();
+
+        // Clear the scheduled render before rendering, so that renders which
+        // are scheduled while rendering are handed to the scheduler again.
+        self.shared.render_scheduled.set(false);
+        let resolve = self
+            .shared
+            .render_promise
             .take()
-            .unwrap_throw()
+            .map(|(_, resolve)| resolve);
+        exclusive.start_render(resolve);
+        exclusive.finish_render();
+        Ok(())
+    }
+
+    /// Schedule a render, unless one is already scheduled.
+    fn schedule_render(self: &Rc<Self>) {
+        if !self.shared.render_scheduled.replace(true) {
+            self.schedule_work();
+        }
+    }
+
+    /// Hand our callback for doing scheduled work to the scheduler, unless it
+    /// already has it.
+    fn schedule_work(self: &Rc<Self>) {
+        if self.shared.work_scheduled.replace(true) {
+            return;
+        }
+
+        let vdom = VdomWeak::new(self);
+        self.shared.scheduler.schedule(Box::new(move || {
+            let inner = match vdom.inner.upgrade() {
+                Some(inner) => inner,
+                None => {
+                    warn!("VdomWeak::schedule_render: vdom unmounted before we could render");
+                    return;
+                }
+            };
+            inner.shared.work_scheduled.set(false);
+            inner.work();
+        }));
+    }
+
+    /// Continue the incremental render that is in progress, or else start the
+    /// scheduled render, if it wasn't already flushed.
+    fn work(self: &Rc<Self>) {
+        let mut exclusive = match self.exclusive.try_borrow_mut() {
+            Ok(exclusive) => exclusive,
+            Err(_) => {
+                // Something else has exclusive access right now, so try again
+                // later.
+                self.schedule_work();
+                return;
+            }
+        };
+
+        if !exclusive.is_rendering() {
+            if !self.shared.render_scheduled.replace(false) {
+                return;
+            }
+            let resolve = self
+                .shared
+                .render_promise
+                .take()
+                .map(|(_, resolve)| resolve);
+            exclusive.start_render(resolve);
+        }
+
+        let budget = exclusive.budget;
+        let finished = exclusive.continue_render(budget);
+        drop(exclusive);
+
+        if !finished || self.shared.render_scheduled.get() {
+            self.schedule_work();
+        }
+    }
+
+    /// Get the promise that resolves once the scheduled render happens.
+    fn render_promise(&self) -> js_sys::Promise {
+        if let Some((promise, resolve)) = self.shared.render_promise.take() {
+            self.shared
+                .render_promise
+                .set(Some((promise.clone(), resolve)));
+            return promise;
+        }
+
+        let mut resolve = None;
+        let promise = js_sys::Promise::new(&mut |resolve_fn, _reject| {
+            resolve = Some(resolve_fn);
+        });
+        let resolve = resolve.expect_throw("promise executors are called immediately");
+        self.shared
+            .render_promise
+            .set(Some((promise.clone(), resolve)));
+        promise
     }
 }
 
@@ -260,57 +743,196 @@ impl VdomInnerExclusive {
         &mut **self.component.as_mut().unwrap_throw()
     }
 
-    /// Re-render this virtual dom's current component.
+    /// Re-render this virtual dom's current component, and apply the changes
+    /// all at once.
     pub(crate) fn render(&mut self) {
+        self.finish_render();
+        self.start_render(None);
+        self.finish_render();
+    }
+
+    /// Is there an incremental render in progress?
+    #[inline]
+    pub(crate) fn is_rendering(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Finish the diff of the incremental render in progress, if any, and
+    /// apply its changes.
+    ///
+    /// This must happen before anything else gets at the component, since the
+    /// render's virtual DOM may borrow from it.
+    pub(crate) fn finish_render(&mut self) {
+        if self.is_rendering() {
+            let finished = self.continue_render(None);
+            debug_assert!(finished);
+        }
+    }
+
+    /// Render the current component, and get ready to diff it. The given
+    /// `resolve` function is called once the render's changes are applied.
+    fn start_render(&mut self, resolve: Option<js_sys::Function>) {
+        debug_assert!(!self.is_rendering());
         unsafe {
-            let events_registry = self.events_registry.take().unwrap();
-            {
-                let mut registry = events_registry.borrow_mut();
-
-                // Reset the inactive bump arena's pointer.
-                let mut dom_buffers = self.dom_buffers.take().unwrap_throw();
-                dom_buffers[1].reset();
-
-                // Render the new current contents into the inactive bump arena.
-                let mut cx =
-                    RenderContext::new(&dom_buffers[1], &self.cached_set, &mut self.templates);
-                let new_contents = self.component.as_ref().unwrap_throw().render(&mut cx);
-                let new_contents = extend_node_lifetime(new_contents);
-
-                // Diff the old contents with the new contents.
-                let old_contents = self.current_root.take().unwrap();
-                let mut cache_roots;
-                {
-                    let cached_set = self.cached_set.borrow();
-                    cache_roots = cached_set.new_roots_set();
-                    let mut change_list = self.change_list.builder();
-                    crate::diff::diff(
-                        &cached_set,
-                        &mut change_list,
-                        &mut registry,
-                        &old_contents,
-                        &new_contents,
-                        &mut cache_roots,
-                    );
-
-                    // Tell JS to apply our diff-generated changes to the physical DOM!
-                    change_list.finish();
+            // Reset the inactive bump arena's pointer.
+            let mut dom_buffers = self.dom_buffers.take().unwrap_throw();
+            debug_assert!(
+                !self.change_list.tracks_lifecycles_in(&mut dom_buffers[1]),
+                "the previous render's lifecycles should all be untracked or retracked by now"
+            );
+            dom_buffers[1].reset();
+            self.cached_set.borrow_mut().drop_collected();
+
+            // Render the new current contents into the inactive bump arena.
+            let mut cx = RenderContext::new(&dom_buffers[1], &self.cached_set, &mut self.templates);
+            let new_contents = self.component.as_ref().unwrap_throw().render(&mut cx);
+            let new_contents = extend_node_lifetime(new_contents);
+            let after_render = mem::take(&mut cx.after_render);
+            let portal_targets = cx.take_portal_targets();
+
+            // The diff holds references to the old and new contents, so they
+            // need a place to live until it is finished.
+            let new_contents = extend_node_ref_lifetime(dom_buffers[1].alloc(new_contents));
+
+            // Diff the old contents with the new contents. If there are no
+            // old contents, then this is the first render of a hydrating
+            // `Vdom`.
+            let old_contents = self.current_root.take();
+            let hydrate = old_contents.is_none();
+            let diff = match old_contents {
+                Some(old_contents) => {
+                    let old_contents = extend_node_ref_lifetime(dom_buffers[1].alloc(old_contents));
+                    Diff::root(old_contents, new_contents)
                 }
+                None => Diff::default(),
+            };
+
+            let cache_roots = self.cached_set.borrow().new_roots_set();
+            self.pending = Some(PendingRender {
+                dom_buffers,
+                new_contents,
+                diff,
+                hydrate,
+                next_temporary: 0,
+                cache_roots,
+                portal_targets,
+                after_render,
+                resolve,
+            });
+        }
+    }
+
+    /// Diff some more of the incremental render in progress, as much as the
+    /// `budget` allows, or all of it if there is no budget. Once the diff is
+    /// finished, apply the render's changes.
+    ///
+    /// Returns whether the render is finished.
+    fn continue_render(&mut self, budget: Option<RenderBudget>) -> bool {
+        let mut pending = self.pending.take().unwrap_throw();
+        let events_registry = self.events_registry.take().unwrap_throw();
+        let mut registry = events_registry.borrow_mut();
 
-                {
-                    // Clean up unused cached renders.
-                    let mut cached_set = self.cached_set.borrow_mut();
-                    cached_set.gc(&mut registry, cache_roots);
+        {
+            // The cached set isn't modified until the render is finished, so
+            // the diff may keep its references to cached nodes between slices.
+            let cached_set = self.cached_set.borrow();
+            let cached_set: &'static CachedSet = unsafe { &*(&*cached_set as *const CachedSet) };
+            let mut change_list = self.change_list.builder();
+            change_list.set_next_temporary(pending.next_temporary);
+
+            if pending.hydrate {
+                pending.hydrate = false;
+                crate::hydrate::hydrate_container(
+                    cached_set,
+                    &mut change_list,
+                    &mut registry,
+                    self.container
+                        .as_ref()
+                        .expect_throw("only `Vdom`s with a container hydrate"),
+                    pending.new_contents,
+                    &mut pending.cache_roots,
+                );
+            }
+
+            let diff = &mut pending.diff;
+            let cache_roots = &mut pending.cache_roots;
+            match budget {
+                None => diff.finish(cached_set, &mut change_list, &mut registry, cache_roots),
+                Some(RenderBudget::Nodes(nodes)) => {
+                    for _ in 0..cmp::max(nodes, 1) {
+                        if diff.is_finished() {
+                            break;
+                        }
+                        diff.step(cached_set, &mut change_list, &mut registry, cache_roots);
+                    }
                 }
+                Some(RenderBudget::Time(time)) => {
+                    let deadline = Deadline::new(time);
+                    while !diff.is_finished() {
+                        diff.step(cached_set, &mut change_list, &mut registry, cache_roots);
+                        if deadline.has_passed() {
+                            break;
+                        }
+                    }
+                }
+            }
 
-                // Swap the buffers to make the bump arena with the new contents the
-                // active arena, and the old one into the inactive arena.
-                self.swap_buffers(dom_buffers);
-                self.set_current_root(new_contents);
+            if !pending.diff.is_finished() {
+                pending.next_temporary = change_list.next_temporary();
+                drop(registry);
+                self.events_registry = Some(events_registry);
+                self.pending = Some(pending);
+                return false;
             }
 
-            self.events_registry = Some(events_registry);
+            // Tell JS to apply our diff-generated changes to the physical DOM!
+            change_list.finish();
+        }
+
+        // Now that the changes are applied, invoke the lifecycle callbacks.
+        // They may live in cached renders, so this must happen before those are
+        // cleaned up.
+        let vdom = self.weak.clone().unwrap_throw();
+        for call in self.change_list.take_lifecycle_calls() {
+            call.invoke(self.component_raw_mut(), vdom.clone());
+        }
+
+        // The physical DOM no longer has the listeners that the render
+        // removed.
+        registry.commit_removals();
+
+        // Clean up unused cached renders, and portal targets.
+        let unused_portal_targets = self.cached_set.borrow_mut().gc(
+            &mut registry,
+            &self.change_list,
+            pending.cache_roots,
+            pending.portal_targets,
+        );
+        for id in unused_portal_targets {
+            self.change_list.remove_portal_target(id);
+        }
+
+        // Swap the buffers to make the bump arena with the new contents the
+        // active arena, and the old one into the inactive arena.
+        self.swap_buffers(pending.dom_buffers);
+        unsafe {
+            self.set_current_root(pending.new_contents.clone());
+        }
+
+        drop(registry);
+        self.events_registry = Some(events_registry);
+
+        // Finally, run the callbacks that the render queued up, now that the
+        // physical DOM reflects it.
+        for callback in pending.after_render {
+            callback(self.component_raw_mut(), vdom.clone());
+        }
+
+        if let Some(resolve) = pending.resolve {
+            let r = resolve.call0(&JsValue::null());
+            debug_assert!(r.is_ok());
         }
+        true
     }
 
     fn swap_buffers(&mut self, mut dom_buffers: [Bump; 2]) {
@@ -326,29 +948,6 @@ impl VdomInnerExclusive {
     }
 }
 
-fn request_animation_frame(f: &Closure<dyn FnMut()>) {
-    web_sys::window()
-        .expect_throw("should have a window")
-        .request_animation_frame(f.as_ref().unchecked_ref())
-        .expect_throw("should register `requestAnimationFrame` OK");
-}
-
-fn with_animation_frame<F>(mut f: F)
-where
-    F: 'static + FnMut(),
-{
-    let g = Rc::new(RefCell::new(None));
-    let h = g.clone();
-
-    let f = Closure::wrap(Box::new(move || {
-        *g.borrow_mut() = None;
-        f();
-    }) as Box<dyn FnMut()>);
-    request_animation_frame(&f);
-
-    *h.borrow_mut() = Some(f);
-}
-
 /// An operation failed because the virtual DOM was already dropped and
 /// unmounted.
 #[derive(Debug)]
@@ -362,6 +961,77 @@ impl fmt::Display for VdomDroppedError {
 
 impl std::error::Error for VdomDroppedError {}
 
+/// How much diffing an incremental render does at a time. See
+/// `VdomBuilder::render_budget`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderBudget {
+    /// Yield once this much time has been spent diffing.
+    Time(Duration),
+    /// Yield once this many virtual nodes have been diffed.
+    Nodes(usize),
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(target_arch = "wasm32")] {
+        /// When an incremental render with a `RenderBudget::Time` yields.
+        struct Deadline(f64);
+
+        impl Deadline {
+            fn new(budget: Duration) -> Deadline {
+                Deadline(Deadline::now() + budget.as_secs_f64() * 1000.0)
+            }
+
+            fn has_passed(&self) -> bool {
+                Deadline::now() >= self.0
+            }
+
+            fn now() -> f64 {
+                // Web workers, where remote `Vdom`s often live, have no
+                // `Window`.
+                match web_sys::window().and_then(|window| window.performance()) {
+                    Some(performance) => performance.now(),
+                    None => js_sys::Date::now(),
+                }
+            }
+        }
+    } else {
+        /// When an incremental render with a `RenderBudget::Time` yields.
+        struct Deadline(std::time::Instant);
+
+        impl Deadline {
+            fn new(budget: Duration) -> Deadline {
+                Deadline(std::time::Instant::now() + budget)
+            }
+
+            fn has_passed(&self) -> bool {
+                std::time::Instant::now() >= self.0
+            }
+        }
+    }
+}
+
+/// An error from rendering synchronously with `Vdom::flush` or
+/// `VdomWeak::render_now`.
+#[derive(Debug)]
+pub enum FlushError {
+    /// The virtual DOM was already dropped and unmounted.
+    Dropped,
+    /// The virtual DOM is busy: it is rendering, running a listener, or
+    /// lending its root rendering component out. Schedule a render instead.
+    Reentrant,
+}
+
+impl fmt::Display for FlushError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlushError::Dropped => write!(f, "The virtual DOM was dropped."),
+            FlushError::Reentrant => write!(f, "The virtual DOM is busy and cannot render now."),
+        }
+    }
+}
+
+impl std::error::Error for FlushError {}
+
 impl VdomWeak {
     /// Construct a new weak handle to the given virtual DOM.
     #[inline]
@@ -371,6 +1041,32 @@ impl VdomWeak {
         }
     }
 
+    /// Invoke the given listener with the given event.
+    ///
+    /// This is how events reach a `Vdom` that was created with
+    /// `Vdom::with_transport`: its remote `Applier` reports which listener an
+    /// event triggered, and the event must be re-created on this side.
+    ///
+    /// Fails with `FlushError::Reentrant` when called while the virtual DOM is
+    /// rendering or running a listener, such as from a lifecycle or
+    /// after-render callback. Dispatch the event later instead.
+    pub fn dispatch_event(
+        &self,
+        listener: ListenerId,
+        event: web_sys::Event,
+    ) -> Result<(), FlushError> {
+        let inner = self.inner.upgrade().ok_or(FlushError::Dropped)?;
+        let registry = inner
+            .exclusive
+            .try_borrow()
+            .ok()
+            .and_then(|exclusive| exclusive.events_registry.clone())
+            .ok_or(FlushError::Reentrant)?;
+        let (a, b) = listener.parts();
+        EventsRegistry::dispatch(&registry, event, a, b);
+        Ok(())
+    }
+
     /// Replace the root rendering component with the new `root`.
     ///
     /// Returns a future that resolves to the *old* root component.
@@ -385,6 +1081,7 @@ impl VdomWeak {
 
         let old = {
             let mut exclusive = inner.exclusive.borrow_mut();
+            exclusive.finish_render();
             mem::replace(&mut *exclusive.component.as_mut().unwrap_throw(), root)
         };
 
@@ -408,26 +1105,80 @@ impl VdomWeak {
         let _ = JsFuture::from(js_sys::Promise::resolve(&JsValue::null())).await;
 
         let mut exclusive = inner.exclusive.borrow_mut();
+        exclusive.finish_render();
 
         Ok(f(exclusive.component_raw_mut()))
     }
 
-    /// Schedule a render to occur during the next animation frame.
+    /// Spawn the future of an async listener, and schedule a render once it
+    /// completes. If the virtual DOM is dropped first, the future is dropped
+    /// without being polled again.
+    pub(crate) fn spawn_listener_future<F>(&self, future: F)
+    where
+        F: 'static + Future<Output = ()>,
+    {
+        let inner = match self.inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        let vdom = self.clone();
+        inner.shared.tasks.spawn(async move {
+            future.await;
+            vdom.schedule_render();
+        });
+    }
+
+    /// Schedule a render to occur during the next animation frame, or whenever
+    /// the virtual DOM's `Scheduler` decides.
     ///
     /// If you want a future that resolves after the render has finished, use
     /// `render` instead.
     pub fn schedule_render(&self) {
         debug!("VdomWeak::schedule_render");
 
-        let future = self.render();
+        if let Some(inner) = self.inner.upgrade() {
+            inner.schedule_render();
+        }
+    }
 
-        wasm_bindgen_futures::spawn_local(async move {
-            let _ = future.await;
-        });
+    /// Re-render and apply the changes to the physical DOM right away, rather
+    /// than waiting for the next animation frame.
+    ///
+    /// This is useful in tests, and for imperative code that updates the root
+    /// rendering component and then needs to measure the result. If a render
+    /// was already scheduled, then its future completes as well, and the
+    /// scheduler has nothing left to do for it.
+    ///
+    /// Returns an error if the virtual DOM is busy, such as when this is called
+    /// from within a listener or from `with_component`'s closure. Use
+    /// `schedule_render` in those places instead.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dodrio::VdomWeak;
+    ///
+    /// # struct Counter { count: u32 }
+    /// # impl<'a> dodrio::Render<'a> for Counter {
+    /// #     fn render(&self, _: &mut dodrio::RenderContext<'a>) -> dodrio::Node<'a> { unimplemented!() }
+    /// # }
+    /// async fn increment(vdom: VdomWeak) {
+    ///     vdom.with_component(|root| root.unwrap_mut::<Counter>().count += 1)
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     // The physical DOM reflects the new count as soon as this returns.
+    ///     vdom.render_now().unwrap();
+    /// }
+    /// ```
+    pub fn render_now(&self) -> Result<(), FlushError> {
+        let inner = self.inner.upgrade().ok_or(FlushError::Dropped)?;
+        inner.flush()
     }
 
-    /// Schedule a render to occur during the next animation frame and return a
-    /// future that will complete once the render has finished.
+    /// Schedule a render to occur during the next animation frame, or whenever
+    /// the virtual DOM's `Scheduler` decides, and return a future that will
+    /// complete once the render has finished.
     ///
     /// If you don't want to do more things after the render completes, then use
     /// `schedule_render` instead of `render`.
@@ -437,35 +1188,8 @@ impl VdomWeak {
         async move {
             let inner = inner.ok_or(VdomDroppedError {})?;
 
-            let promise = inner.shared.render_scheduled.take().unwrap_or_else(|| {
-                js_sys::Promise::new(&mut |resolve, reject| {
-                    let vdom = VdomWeak {
-                        inner: Rc::downgrade(&inner),
-                    };
-
-                    with_animation_frame(move || match vdom.inner.upgrade() {
-                        None => {
-                            warn!("VdomWeak::render: vdom unmounted before we could render");
-                            let r = reject.call0(&JsValue::null());
-                            debug_assert!(r.is_ok());
-                        }
-                        Some(inner) => {
-                            let mut exclusive = inner.exclusive.borrow_mut();
-                            exclusive.render();
-
-                            // We did the render, so take the promise away
-                            // and let future `render` calls request new
-                            // animation frames.
-                            let _ = inner.shared.render_scheduled.take();
-
-                            let r = resolve.call0(&JsValue::null());
-                            debug_assert!(r.is_ok());
-                        }
-                    });
-                })
-            });
-
-            inner.shared.render_scheduled.set(Some(promise.clone()));
+            let promise = inner.render_promise();
+            inner.schedule_render();
 
             JsFuture::from(promise)
                 .await
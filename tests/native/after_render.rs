@@ -0,0 +1,91 @@
+use super::{create_element, RenderFn};
+use dodrio::{builder::*, native_dom, Cached, Node, Render, RenderContext, Vdom};
+use std::cell::RefCell;
+
+thread_local! {
+    static LOG: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn log(entry: &str) {
+    LOG.with(|log| log.borrow_mut().push(entry.to_string()));
+}
+
+fn take_log() -> Vec<String> {
+    LOG.with(|log| log.replace(vec![]))
+}
+
+struct Messages {
+    count: usize,
+    container: native_dom::Node,
+}
+
+impl<'a> Render<'a> for Messages {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let count = self.count;
+        let container = self.container.clone();
+        cx.after_render(move |root, _vdom| {
+            // The physical DOM already reflects this render.
+            let list = container.first_child().unwrap();
+            assert_eq!(list.children().len(), count);
+
+            let messages = root.unwrap_mut::<Messages>();
+            log(&format!("scrolled to {}", messages.count));
+        });
+
+        let mut list = ul(&cx);
+        for _ in 0..count {
+            list = list.child(li(&cx).finish());
+        }
+        list.finish()
+    }
+}
+
+#[test]
+fn after_render_callbacks_see_the_new_dom() {
+    take_log();
+    let container = create_element("div");
+    let vdom = Vdom::new(
+        &container,
+        Messages {
+            count: 1,
+            container: container.clone(),
+        },
+    );
+    vdom.immediately_render_and_diff(Messages {
+        count: 3,
+        container: container.clone(),
+    });
+    assert_eq!(take_log(), ["scrolled to 1", "scrolled to 3"]);
+}
+
+#[derive(Default)]
+struct Logs;
+
+impl<'a> Render<'a> for Logs {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        cx.after_render(|_root, _vdom| log("cached"));
+        span(&cx).finish()
+    }
+}
+
+#[test]
+fn after_render_callbacks_run_in_order() {
+    take_log();
+    let container = create_element("div");
+    let vdom = Vdom::new(
+        &container,
+        RenderFn(|cx| {
+            cx.after_render(|_root, _vdom| log("before"));
+            let logs = Cached::new(Logs).render(cx);
+            cx.after_render(|_root, _vdom| log("after"));
+            div(&cx).child(logs).finish()
+        }),
+    );
+    assert_eq!(take_log(), ["before", "cached", "after"]);
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| {
+        cx.after_render(|_root, _vdom| log("again"));
+        div(&cx).finish()
+    }));
+    assert_eq!(take_log(), ["again"]);
+}
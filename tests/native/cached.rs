@@ -0,0 +1,215 @@
+use super::{assert_rendered, before_after, create_element, RenderFn};
+use dodrio::{builder::*, bumpalo, Cached, Node, Render, RenderContext, Vdom};
+use std::cell::Cell;
+use std::rc::Rc;
+
+#[derive(Default)]
+pub struct CountRenders {
+    render_count: Rc<Cell<usize>>,
+}
+
+impl<'a> Render<'a> for CountRenders {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let count = self.render_count.get() + 1;
+        self.render_count.set(count);
+
+        let s = bumpalo::format!(in cx.bump, "{}", count);
+        text(s.into_bump_str())
+    }
+}
+
+#[test]
+fn uses_cached_render() {
+    let render_count = Rc::new(Cell::new(0));
+    let cached = Rc::new(Cached::new(CountRenders {
+        render_count: render_count.clone(),
+    }));
+
+    let container = create_element("div");
+
+    // We render, populate the cache, and get "1".
+    let vdom = Vdom::new(&container, cached.clone());
+    assert_eq!(render_count.get(), 1);
+    assert_rendered(&container, &RenderFn(|_| text("1")));
+
+    // We re-render, re-use the cached node, and get "1" again.
+    vdom.immediately_render_and_diff(cached.clone());
+    assert_eq!(render_count.get(), 1);
+    assert_rendered(&container, &RenderFn(|_| text("1")));
+}
+
+#[derive(Default)]
+struct Item;
+
+impl<'a> Render<'a> for Item {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        li(&cx)
+            .attr("class", "item")
+            .on("click", |_root, _vdom, _event| {})
+            .children([span(&cx).children([text("cached")]).finish()])
+            .finish()
+    }
+}
+
+#[test]
+fn nodes_created_from_templates_get_listeners() {
+    fn list<'a>(cx: &mut RenderContext<'a>, n: usize) -> Node<'a> {
+        let mut list = ul(&cx);
+        for _ in 0..n {
+            list = list.child(Cached::new(Item).render(cx));
+        }
+        list.finish()
+    }
+
+    let container = create_element("div");
+
+    // The first render saves a template, and the rest are created by cloning
+    // it. Clones don't copy listeners, so each must have its own listener
+    // added.
+    let vdom = Vdom::new(&container, RenderFn(|cx| list(cx, 3)));
+    assert_rendered(&container, &RenderFn(|cx| list(cx, 3)));
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| list(cx, 5)));
+    assert_rendered(&container, &RenderFn(|cx| list(cx, 5)));
+    for item in container.first_child().unwrap().children() {
+        assert_eq!(item.listeners(), ["click"]);
+    }
+}
+
+#[derive(Default)]
+struct Id(&'static str);
+
+impl<'a> Render<'a> for Id {
+    fn render(&self, _cx: &mut RenderContext<'a>) -> Node<'a> {
+        text(self.0)
+    }
+}
+
+thread_local! {
+    static WARM_CHEESE: Rc<Cached<Id>> = Rc::new(Cached::new(Id("cheese")));
+    static WARM_CHEESIER: Rc<Cached<Id>> = Rc::new(Cached::new(Id("cheesier")));
+}
+
+fn warm_cheese<'a>(cx: &mut RenderContext<'a>) -> Node<'a> {
+    WARM_CHEESE.with(|c| {
+        let _ = c.render(cx);
+        c.render(cx)
+    })
+}
+
+fn warm_cheesier<'a>(cx: &mut RenderContext<'a>) -> Node<'a> {
+    WARM_CHEESIER.with(|c| {
+        let _ = c.render(cx);
+        c.render(cx)
+    })
+}
+
+before_after! {
+    cold_cache_and_not_cached {
+        before(cx) {
+            Cached::new(Id("cheese")).render(cx)
+        }
+        after(cx) {
+            Id("ravioli").render(cx)
+        }
+    }
+
+    not_cached_and_cold_cache {
+        before(cx) {
+            Id("ravioli").render(cx)
+        }
+        after(cx) {
+            Cached::new(Id("cheese")).render(cx)
+        }
+    }
+
+    cold_cache_and_cold_cache_same {
+        before(cx) {
+            Cached::new(Id("cheese")).render(cx)
+        }
+        after(cx) {
+            Cached::new(Id("cheese")).render(cx)
+        }
+    }
+
+    cold_cache_and_cold_cache_different {
+        before(cx) {
+            Cached::new(Id("cheese")).render(cx)
+        }
+        after(cx) {
+            Cached::new(Id("ravioli")).render(cx)
+        }
+    }
+
+    warm_cache_and_not_cached {
+        before(cx) {
+            warm_cheese(cx)
+        }
+        after(cx) {
+            Id("ravioli").render(cx)
+        }
+    }
+
+    not_cached_and_warm_cache {
+        before(cx) {
+            Id("ravioli").render(cx)
+        }
+        after(cx) {
+            warm_cheese(cx)
+        }
+    }
+
+    warm_cache_and_warm_cache_same {
+        before(cx) {
+            warm_cheese(cx)
+        }
+        after(cx) {
+            warm_cheese(cx)
+        }
+    }
+
+    warm_cache_and_warm_cache_different {
+        before(cx) {
+            warm_cheese(cx)
+        }
+        after(cx) {
+            warm_cheesier(cx)
+        }
+    }
+
+    cold_cache_and_warm_cache_same {
+        before(cx) {
+            Cached::new(Id("cheese")).render(cx)
+        }
+        after(cx) {
+            warm_cheese(cx)
+        }
+    }
+
+    cold_cache_and_warm_cache_different {
+        before(cx) {
+            Cached::new(Id("cheese")).render(cx)
+        }
+        after(cx) {
+            warm_cheesier(cx)
+        }
+    }
+
+    warm_cache_and_cold_cache_same {
+        before(cx) {
+            warm_cheese(cx)
+        }
+        after(cx) {
+            Cached::new(Id("cheese")).render(cx)
+        }
+    }
+
+    warm_cache_and_cold_cache_different {
+        before(cx) {
+            warm_cheesier(cx)
+        }
+        after(cx) {
+            Cached::new(Id("cheese")).render(cx)
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use super::{assert_rendered, before_after, create_element, RenderFn};
+use dodrio::{builder::*, Node, RenderContext, Vdom};
+use std::rc::Rc;
+
+fn todo<'a>(cx: &mut RenderContext<'a>, completed: bool, editing: bool) -> Node<'a> {
+    li(&cx)
+        .class("todo")
+        .class_if("completed", completed)
+        .class_if("editing", editing)
+        .finish()
+}
+
+#[test]
+fn classes_are_added_to_the_class_attribute() {
+    let r = Rc::new(RenderFn(|cx| todo(cx, true, false)));
+
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, r.clone());
+    assert_rendered(&container, &r);
+
+    let li = container.first_child().unwrap();
+    assert_eq!(li.attribute("class").as_deref(), Some("todo completed"));
+}
+
+#[test]
+fn toggling_a_class_only_touches_that_class() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| todo(cx, false, false)));
+    vdom.immediately_render_and_diff(RenderFn(|cx| todo(cx, false, false)));
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| todo(cx, false, true)));
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "add_cached_string 2 \"editing\"",
+            "add_class \"editing\"",
+        ]
+    );
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| todo(cx, true, false)));
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "remove_class \"editing\"",
+            "add_cached_string 3 \"completed\"",
+            "add_class \"completed\"",
+        ]
+    );
+}
+
+#[test]
+fn classes_added_by_others_are_left_alone() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| todo(cx, false, false)));
+
+    // Something other than dodrio adds its own class.
+    let li = container.first_child().unwrap();
+    li.add_class("animating");
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| todo(cx, true, false)));
+    assert_eq!(
+        li.attribute("class").as_deref(),
+        Some("todo animating completed")
+    );
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| todo(cx, false, false)));
+    assert_eq!(li.attribute("class").as_deref(), Some("todo animating"));
+}
+
+#[test]
+fn class_attribute_is_mixed_with_class_names() {
+    let container = create_element("div");
+    let vdom = Vdom::new(
+        &container,
+        RenderFn(|cx| li(&cx).attr("class", "todo big").class("completed").finish()),
+    );
+    vdom.immediately_render_and_diff(RenderFn(|cx| {
+        li(&cx).attr("class", "todo big").class("completed").finish()
+    }));
+    let element = container.first_child().unwrap();
+    assert_eq!(
+        element.attribute("class").as_deref(),
+        Some("todo big completed")
+    );
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| {
+        li(&cx).attr("class", "todo").class("completed").finish()
+    }));
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "add_cached_string 4 \"big\"",
+            "remove_class \"big\"",
+        ]
+    );
+    assert_eq!(element.attribute("class").as_deref(), Some("todo completed"));
+}
+
+before_after! {
+    add_class {
+        before(cx) {
+            div(&cx).finish()
+        }
+        after(cx) {
+            div(&cx).class("a").class("b").finish()
+        }
+    }
+
+    remove_all_classes {
+        before(cx) {
+            div(&cx).class("a").class("b").finish()
+        }
+        after(cx) {
+            div(&cx).finish()
+        }
+    }
+
+    swap_classes {
+        before(cx) {
+            div(&cx).class("a").class("b").finish()
+        }
+        after(cx) {
+            div(&cx).class("b").class("c").finish()
+        }
+    }
+
+    class_attribute_and_class_names {
+        before(cx) {
+            div(&cx).attr("class", "a b").class("c").finish()
+        }
+        after(cx) {
+            div(&cx).attr("class", "b").class("c").class("d").finish()
+        }
+    }
+
+    classes_and_attributes {
+        before(cx) {
+            span(&cx).attr("id", "x").class("a").finish()
+        }
+        after(cx) {
+            span(&cx).attr("id", "y").class("a").class("b").finish()
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use super::{create_element, RenderFn};
+use dodrio::bumpalo::{self, collections::String};
+use dodrio::{Node, RenderContext, Vdom};
+
+fn list<'a>(cx: &mut RenderContext<'a>, keys: &[u32]) -> Node<'a> {
+    use dodrio::builder::*;
+    let children = bumpalo::collections::Vec::from_iter_in(
+        keys.iter().map(|&k| {
+            let label = String::from_str_in(&k.to_string(), cx.bump).into_bump_str();
+            li(&cx).key(k).children([text(label)]).finish()
+        }),
+        cx.bump,
+    );
+    ul(&cx).children(children).finish()
+}
+
+#[test]
+fn attributes_and_children() {
+    let container = create_element("div");
+    let vdom = Vdom::new(
+        &container,
+        RenderFn(|cx| {
+            use dodrio::builder::*;
+            div(&cx).attr("id", "a").children([text("hello")]).finish()
+        }),
+    );
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| {
+        use dodrio::builder::*;
+        div(&cx)
+            .attr("id", "b")
+            .attr("class", "c")
+            .children([text("bye"), span(&cx).finish()])
+            .finish()
+    }));
+    assert_eq!(
+        disassembly.to_string(),
+        r#"push_child 0
+add_cached_string 2 "b"
+set_attribute "id" "b"
+add_cached_string 3 "c"
+add_class "c"
+push_child 0
+set_text "bye"
+pop
+add_cached_string 4 "span"
+create_element "span"
+append_child
+drop_cached_string 1
+"#
+    );
+}
+
+#[test]
+fn keyed_reordering() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| list(cx, &[1, 2, 3])));
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| list(cx, &[3, 1, 2])));
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "save_children_to_temporaries t0..t3 = children 0..3",
+            "push_temporary t0",
+            "push_temporary t2",
+            "insert_before",
+            "drop_cached_string 1",
+            "drop_cached_string 0",
+        ]
+    );
+}
+
+#[test]
+fn no_changes() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| list(cx, &[1, 2])));
+    vdom.immediately_render_and_disassemble(RenderFn(|cx| list(cx, &[1, 2])));
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| list(cx, &[1, 2])));
+    assert_eq!(disassembly.lines(), [] as [String; 0]);
+}
@@ -0,0 +1,103 @@
+use super::{assert_rendered, before_after, create_element, RenderFn};
+use dodrio::{builder::*, Node, RenderContext, Vdom};
+use std::rc::Rc;
+
+fn form<'a>(cx: &mut RenderContext<'a>, show_warning: bool) -> Node<'a> {
+    let warning = if show_warning {
+        Some(p(&cx).children([text("warning!")]).finish())
+    } else {
+        None
+    };
+    div(&cx)
+        .children([warning.into(), input(&cx).finish(), text("done")])
+        .finish()
+}
+
+#[test]
+fn empty_is_rendered_as_a_comment() {
+    let r = Rc::new(RenderFn(|cx| form(cx, false)));
+
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, r.clone());
+    assert_rendered(&container, &r);
+
+    let children = container.first_child().unwrap().children();
+    assert_eq!(children.len(), 3);
+    assert!(children[0].is_comment());
+}
+
+#[test]
+fn toggling_keeps_siblings_in_place() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| form(cx, false)));
+    let input = container.first_child().unwrap().children()[1].clone();
+
+    let shown = Rc::new(RenderFn(|cx| form(cx, true)));
+    vdom.immediately_render_and_diff(shown.clone());
+    assert_rendered(&container, &shown);
+    let children = container.first_child().unwrap().children();
+    assert!(children[1].is_same_node(&input));
+
+    let hidden = Rc::new(RenderFn(|cx| form(cx, false)));
+    vdom.immediately_render_and_diff(hidden.clone());
+    assert_rendered(&container, &hidden);
+    let children = container.first_child().unwrap().children();
+    assert!(children[0].is_comment());
+    assert!(children[1].is_same_node(&input));
+}
+
+#[test]
+fn toggling_only_replaces_the_placeholder() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| form(cx, true)));
+    vdom.immediately_render_and_diff(RenderFn(|cx| form(cx, true)));
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| form(cx, false)));
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "push_child 0",
+            "create_comment",
+            "replace_with",
+        ]
+    );
+}
+
+before_after! {
+    empty_root {
+        before(_cx) {
+            empty()
+        }
+        after(cx) {
+            div(&cx).finish()
+        }
+    }
+
+    empty_to_text {
+        before(cx) {
+            div(&cx).children([empty(), empty()]).finish()
+        }
+        after(cx) {
+            div(&cx).children([empty(), text("shown")]).finish()
+        }
+    }
+
+    text_to_empty {
+        before(cx) {
+            div(&cx).children([text("a"), text("b")]).finish()
+        }
+        after(cx) {
+            div(&cx).children([text("a"), empty()]).finish()
+        }
+    }
+
+    empty_in_fragment {
+        before(cx) {
+            fragment(cx.bump, [empty(), span(&cx).finish()])
+        }
+        after(cx) {
+            fragment(cx.bump, [span(&cx).finish(), span(&cx).finish()])
+        }
+    }
+}
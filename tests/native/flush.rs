@@ -0,0 +1,61 @@
+use super::{create_element, RenderFn};
+use dodrio::{builder::*, bumpalo, FlushError, Vdom};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+#[test]
+fn flush_renders_synchronously() {
+    let count = Rc::new(Cell::new(0));
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, {
+        let count = count.clone();
+        RenderFn(move |cx| {
+            let count = bumpalo::format!(in cx.bump, "{}", count.get());
+            p(&cx).children([text(count.into_bump_str())]).finish()
+        })
+    });
+    assert_eq!(container.first_child().unwrap().text_content(), "0");
+
+    count.set(1);
+    vdom.flush().unwrap();
+    assert_eq!(container.first_child().unwrap().text_content(), "1");
+
+    count.set(2);
+    vdom.weak().render_now().unwrap();
+    assert_eq!(container.first_child().unwrap().text_content(), "2");
+}
+
+#[test]
+fn render_now_is_an_error_while_rendering() {
+    let result = Rc::new(RefCell::new(None));
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, {
+        let result = result.clone();
+        RenderFn(move |cx| {
+            let result = result.clone();
+            cx.after_render(move |_root, vdom| {
+                *result.borrow_mut() = Some(vdom.render_now());
+            });
+            div(&cx).finish()
+        })
+    });
+
+    let result = result.borrow_mut().take();
+    match result {
+        Some(Err(FlushError::Reentrant)) => {}
+        r => panic!("expected a reentrant flush error, found {:?}", r),
+    }
+}
+
+#[test]
+fn render_now_is_an_error_once_dropped() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| div(&cx).finish()));
+    let weak = vdom.weak();
+    drop(vdom);
+
+    match weak.render_now() {
+        Err(FlushError::Dropped) => {}
+        r => panic!("expected a dropped flush error, found {:?}", r),
+    }
+}
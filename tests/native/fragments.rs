@@ -0,0 +1,160 @@
+use super::{assert_rendered, before_after, create_element, RenderFn};
+use dodrio::{builder::*, bumpalo, Cached, Node, Render, RenderContext, Vdom};
+use std::rc::Rc;
+
+fn items<'a>(cx: &mut RenderContext<'a>, labels: &[&'static str]) -> Node<'a> {
+    let children = bumpalo::collections::Vec::from_iter_in(
+        labels
+            .iter()
+            .map(|&label| li(&cx).children([text(label)]).finish()),
+        cx.bump,
+    );
+    fragment(cx.bump, children)
+}
+
+fn keyed_items<'a>(cx: &mut RenderContext<'a>, keys: &[u32]) -> Node<'a> {
+    let children = bumpalo::collections::Vec::from_iter_in(
+        keys.iter().map(|&k| {
+            let id = bumpalo::format!(in cx.bump, "{}", k).into_bump_str();
+            li(&cx).key(k).attr("id", id).finish()
+        }),
+        cx.bump,
+    );
+    fragment(cx.bump, children)
+}
+
+#[test]
+fn render_initial_root_fragment() {
+    let r = Rc::new(RenderFn(|cx| {
+        fragment(cx.bump, [text("hello"), span(&cx).finish(), text("world")])
+    }));
+
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, r.clone());
+    assert_eq!(container.children().len(), 3);
+    assert_rendered(&container, &r);
+}
+
+#[test]
+fn empty_root_fragment_is_emptied_upon_drop() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| fragment(cx.bump, [])));
+    assert!(container.first_child().is_none());
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| {
+        fragment(cx.bump, [div(&cx).finish(), div(&cx).finish()])
+    }));
+    assert_eq!(container.children().len(), 2);
+
+    drop(vdom);
+    assert!(container.first_child().is_none());
+}
+
+#[derive(Default)]
+struct Cells;
+
+impl<'a> Render<'a> for Cells {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        fragment(
+            cx.bump,
+            [
+                td(&cx).children([text("a")]).finish(),
+                td(&cx).children([text("cached")]).finish(),
+            ],
+        )
+    }
+}
+
+#[test]
+fn cached_fragment() {
+    let cells = Rc::new(Cached::new(Cells));
+    let r = Rc::new(RenderFn(move |cx| {
+        tr(&cx)
+            .children([td(&cx).finish(), cells.render(cx), td(&cx).finish()])
+            .finish()
+    }));
+
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, r.clone());
+    assert_rendered(&container, &r);
+
+    vdom.immediately_render_and_diff(r.clone());
+    assert_rendered(&container, &r);
+    assert_eq!(container.first_child().unwrap().children().len(), 4);
+}
+
+before_after! {
+    root_fragment_to_element {
+        before(cx) {
+            fragment(cx.bump, [div(&cx).finish(), span(&cx).finish()])
+        }
+        after(cx) {
+            div(&cx).finish()
+        }
+    }
+
+    root_element_to_fragment {
+        before(cx) {
+            div(&cx).attr("id", "a").finish()
+        }
+        after(cx) {
+            fragment(cx.bump, [div(&cx).attr("id", "b").finish(), text("c")])
+        }
+    }
+
+    root_fragment_grows {
+        before(cx) {
+            fragment(cx.bump, [text("a")])
+        }
+        after(cx) {
+            fragment(cx.bump, [text("a"), text("b"), p(&cx).finish()])
+        }
+    }
+
+    root_fragment_shrinks {
+        before(cx) {
+            fragment(cx.bump, [text("a"), text("b"), p(&cx).finish()])
+        }
+        after(cx) {
+            fragment(cx.bump, [p(&cx).finish()])
+        }
+    }
+
+    nested_fragments_in_children {
+        before(cx) {
+            let first = items(cx, &["a", "b"]);
+            let second = items(cx, &["c"]);
+            ul(&cx).children([first, second]).finish()
+        }
+        after(cx) {
+            let first = items(cx, &["a"]);
+            let second = items(cx, &["b", "c", "d"]);
+            let nested = fragment(cx.bump, [first, li(&cx).finish(), second]);
+            ul(&cx).children([nested]).finish()
+        }
+    }
+
+    fragment_becomes_empty {
+        before(cx) {
+            let list = items(cx, &["a", "b"]);
+            ol(&cx).children([list]).finish()
+        }
+        after(cx) {
+            let list = items(cx, &[]);
+            ol(&cx).children([list]).finish()
+        }
+    }
+
+    keyed_children_in_fragments {
+        before(cx) {
+            let first = keyed_items(cx, &[1, 2]);
+            let second = keyed_items(cx, &[3, 4]);
+            ul(&cx).children([first, second]).finish()
+        }
+        after(cx) {
+            let first = keyed_items(cx, &[4, 1]);
+            let second = keyed_items(cx, &[5, 3, 2]);
+            ul(&cx).children([first, second]).finish()
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use super::{assert_rendered, create_element};
+use dodrio::{
+    builder::*, bumpalo, native_dom, scheduler::Manual, Node, Render, RenderBudget, RenderContext,
+    Vdom,
+};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// A list of keyed items, each with some nested nodes to diff.
+#[derive(Clone)]
+struct List {
+    keys: Rc<RefCell<Vec<u32>>>,
+    label: Rc<Cell<&'static str>>,
+}
+
+impl List {
+    fn new(keys: Vec<u32>, label: &'static str) -> List {
+        List {
+            keys: Rc::new(RefCell::new(keys)),
+            label: Rc::new(Cell::new(label)),
+        }
+    }
+
+    fn snapshot(&self) -> List {
+        List::new(self.keys.borrow().clone(), self.label.get())
+    }
+}
+
+impl<'a> Render<'a> for List {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let mut list = ul(&cx);
+        for &key in self.keys.borrow().iter() {
+            let key_text = bumpalo::format!(in cx.bump, "{}", key).into_bump_str();
+            list = list.child(
+                li(&cx)
+                    .key(key)
+                    .children([
+                        span(&cx).children([text(key_text)]).finish(),
+                        span(&cx).children([text(self.label.get())]).finish(),
+                    ])
+                    .finish(),
+            );
+        }
+        div(&cx)
+            .children([
+                h1(&cx).children([text(self.label.get())]).finish(),
+                list.finish(),
+            ])
+            .finish()
+    }
+}
+
+fn mount(list: &List, budget: usize) -> (Vdom, Manual, native_dom::Node) {
+    let manual = Manual::new();
+    let container = create_element("div");
+    let vdom = Vdom::builder()
+        .scheduler(manual.clone())
+        .render_budget(Some(RenderBudget::Nodes(budget)))
+        .mount(&container, list.clone());
+    (vdom, manual, container)
+}
+
+/// Run the manual scheduler until the render is finished, checking that the
+/// physical DOM shows `before` until then, and return how many slices it took.
+fn run_to_completion(manual: &Manual, container: &native_dom::Node, before: &List) -> usize {
+    let mut slices = 0;
+    while manual.is_pending() {
+        assert_rendered(container, before);
+        manual.run();
+        slices += 1;
+    }
+    slices
+}
+
+#[test]
+fn incremental_render_applies_changes_once_finished() {
+    let list = List::new((0..20).collect(), "before");
+    let (vdom, manual, container) = mount(&list, 5);
+    assert_rendered(&container, &list);
+
+    let before = list.snapshot();
+    list.label.set("after");
+    vdom.weak().schedule_render();
+
+    let slices = run_to_completion(&manual, &container, &before);
+    assert!(slices > 1, "expected several slices, found {}", slices);
+    assert_rendered(&container, &list);
+}
+
+#[test]
+fn incremental_render_of_moved_keyed_children() {
+    let list = List::new((0..20).collect(), "before");
+    let (vdom, manual, container) = mount(&list, 3);
+
+    let before = list.snapshot();
+    *list.keys.borrow_mut() = vec![0, 1, 2, 15, 3, 30, 7, 6, 5, 4, 31, 17, 18, 19];
+    list.label.set("after");
+    vdom.weak().schedule_render();
+
+    let slices = run_to_completion(&manual, &container, &before);
+    assert!(slices > 1, "expected several slices, found {}", slices);
+    assert_rendered(&container, &list);
+
+    // Appending many new children is also done a few at a time.
+    let before = list.snapshot();
+    list.keys.borrow_mut().extend(100..120);
+    vdom.weak().schedule_render();
+
+    let slices = run_to_completion(&manual, &container, &before);
+    assert!(slices > 1, "expected several slices, found {}", slices);
+    assert_rendered(&container, &list);
+}
+
+#[test]
+fn time_budgeted_render_finishes() {
+    let list = List::new((0..20).collect(), "before");
+    let manual = Manual::new();
+    let container = create_element("div");
+    // Without any time to spend, each slice diffs a single node.
+    let vdom = Vdom::builder()
+        .scheduler(manual.clone())
+        .render_budget(Some(RenderBudget::Time(Duration::from_secs(0))))
+        .mount(&container, list.clone());
+    assert_rendered(&container, &list);
+
+    let before = list.snapshot();
+    list.label.set("after");
+    vdom.weak().schedule_render();
+
+    let slices = run_to_completion(&manual, &container, &before);
+    assert!(slices > 1, "expected several slices, found {}", slices);
+    assert_rendered(&container, &list);
+}
+
+#[test]
+fn flush_finishes_incremental_render() {
+    let list = List::new((0..20).collect(), "before");
+    let (vdom, manual, container) = mount(&list, 5);
+
+    list.label.set("during");
+    vdom.weak().schedule_render();
+    manual.run();
+    assert!(manual.is_pending());
+
+    list.label.set("after");
+    vdom.flush().unwrap();
+    assert_rendered(&container, &list);
+
+    // The continuation of the finished render has nothing left to do.
+    manual.run();
+    assert!(!manual.is_pending());
+    assert_rendered(&container, &list);
+}
+
+#[test]
+fn renders_scheduled_during_incremental_render_happen_after_it() {
+    let list = List::new((0..20).collect(), "before");
+    let (vdom, manual, container) = mount(&list, 5);
+
+    list.label.set("during");
+    vdom.weak().schedule_render();
+    manual.run();
+
+    list.label.set("after");
+    vdom.weak().schedule_render();
+    while manual.is_pending() {
+        manual.run();
+    }
+    assert_rendered(&container, &list);
+}
+
+#[test]
+fn after_render_runs_once_incremental_render_finishes() {
+    let finished = Rc::new(Cell::new(0));
+    let list = List::new((0..20).collect(), "before");
+    let manual = Manual::new();
+    let container = create_element("div");
+    let vdom = Vdom::builder()
+        .scheduler(manual.clone())
+        .render_budget(Some(RenderBudget::Nodes(5)))
+        .mount(&container, {
+            let finished = finished.clone();
+            let list = list.clone();
+            super::RenderFn(move |cx| {
+                let finished = finished.clone();
+                cx.after_render(move |_root, _vdom| finished.set(finished.get() + 1));
+                list.render(cx)
+            })
+        });
+    assert_eq!(finished.get(), 1);
+
+    list.label.set("after");
+    vdom.weak().schedule_render();
+    manual.run();
+    assert!(manual.is_pending());
+    assert_eq!(finished.get(), 1);
+
+    while manual.is_pending() {
+        manual.run();
+    }
+    assert_eq!(finished.get(), 2);
+}
+
+#[test]
+fn incremental_render_one_node_at_a_time() {
+    let cases: &[(&[u32], &[u32])] = &[
+        (&[], &[0, 1, 2]),
+        (&[0, 1, 2], &[]),
+        (&[0, 1, 2], &[0, 1, 2, 3, 4]),
+        (&[0, 1, 2, 3, 4], &[0, 1, 2]),
+        (&[0, 1, 2, 3, 4], &[4, 3, 2, 1, 0]),
+        (&[0, 1, 2, 3, 4], &[0, 5, 2, 6, 4]),
+        (&[0, 1, 2, 3, 4], &[1, 2, 3, 4, 0]),
+        (&[0, 1, 2, 3, 4], &[7, 8, 9]),
+    ];
+
+    for &(before, after) in cases {
+        let list = List::new(before.to_vec(), "before");
+        let (vdom, manual, container) = mount(&list, 1);
+
+        let snapshot = list.snapshot();
+        *list.keys.borrow_mut() = after.to_vec();
+        list.label.set("after");
+        vdom.weak().schedule_render();
+
+        run_to_completion(&manual, &container, &snapshot);
+        assert_rendered(&container, &list);
+    }
+}
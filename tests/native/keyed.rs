@@ -0,0 +1,368 @@
+use crate::{assert_rendered, create_element};
+use dodrio::{builder::*, bumpalo, native_dom, Node, Render, RenderContext, Vdom};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+struct Keyed(u16);
+
+impl<'a> Render<'a> for Keyed {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let key = bumpalo::format!(in cx.bump, "{}", self.0).into_bump_str();
+        div(&cx)
+            .attr("class", "keyed")
+            .attr("id", key)
+            .key(self.0 as u32)
+            .finish()
+    }
+}
+
+fn keyed<'a, Keys>(cx: &mut RenderContext<'a>, keys: Keys) -> Node<'a>
+where
+    Keys: AsRef<[u16]>,
+{
+    let mut parent = div(&cx).attr("class", "parent");
+
+    for &k in keys.as_ref() {
+        parent = parent.child(Keyed(k).render(cx));
+    }
+
+    parent.finish()
+}
+
+/// Like `keyed`, but keyed by the hashes of string slugs instead of `u32`s.
+fn slugged<'a, Slugs>(cx: &mut RenderContext<'a>, slugs: Slugs) -> Node<'a>
+where
+    Slugs: AsRef<[&'static str]>,
+{
+    let mut parent = div(&cx).attr("class", "parent");
+
+    for &slug in slugs.as_ref() {
+        parent = parent.child(
+            div(&cx)
+                .attr("class", "keyed")
+                .attr("id", slug)
+                .hashed_key(slug)
+                .finish(),
+        );
+    }
+
+    parent.finish()
+}
+
+/// Collect all of the keyed elements beneath `node`, by their `id`.
+fn keyed_elements(node: &native_dom::Node, saved: &mut HashMap<String, native_dom::Node>) {
+    if node.attribute("class").as_deref() == Some("keyed") {
+        saved.insert(node.attribute("id").unwrap(), node.clone());
+    }
+    for child in node.children() {
+        keyed_elements(&child, saved);
+    }
+}
+
+fn assert_keyed<Before, After>(before: Before, after: After)
+where
+    Before: 'static + for<'a> Render<'a>,
+    After: 'static + for<'a> Render<'a>,
+{
+    let container = create_element("div");
+
+    let before = Rc::new(before);
+    let after = Rc::new(after);
+
+    let vdom = Vdom::new(&container, before.clone());
+    let mut saved = HashMap::new();
+    keyed_elements(&container, &mut saved);
+    assert_rendered(&container, &before);
+
+    vdom.immediately_render_and_diff(after.clone());
+    assert_rendered(&container, &after);
+
+    // Elements whose keys survived the diff must have been moved rather than
+    // re-created.
+    let mut current = HashMap::new();
+    keyed_elements(&container, &mut current);
+    for (id, node) in &current {
+        if let Some(original) = saved.get(id) {
+            assert!(
+                original.is_same_node(node),
+                "did not preserve child with key={}",
+                id
+            );
+        }
+    }
+}
+
+macro_rules! keyed_tests {
+    ( $(
+        $name:ident {
+            before($before_cx:ident) {
+                $( $before:tt )*
+            }
+            after($after_cx:ident) {
+                $( $after:tt )*
+            }
+        }
+    )* ) => {
+        $(
+            #[test]
+            fn $name() {
+                use crate::RenderFn;
+                assert_keyed(
+                    RenderFn(|$before_cx| { $( $before )* }),
+                    RenderFn(|$after_cx| { $( $after )* }),
+                );
+            }
+        )*
+    }
+}
+
+keyed_tests! {
+    same_order {
+        before(cx) {
+            keyed(cx, [1, 2, 3])
+        }
+        after(cx) {
+            keyed(cx, [1, 2, 3])
+        }
+    }
+
+    same_order_append {
+        before(cx) {
+            keyed(cx, [1])
+        }
+        after(cx) {
+            keyed(cx, [1, 2])
+        }
+    }
+
+    same_order_delete {
+        before(cx) {
+            keyed(cx, [1, 2, 3])
+        }
+        after(cx) {
+            keyed(cx, [1, 2])
+        }
+    }
+
+    same_suffix {
+        before(cx) {
+            keyed(cx, [1, 2, 3])
+        }
+        after(cx) {
+            keyed(cx, [4, 2, 3])
+        }
+    }
+
+    same_prefix_and_suffix_reorder_middle {
+        before(cx) {
+            keyed(cx, [1, 2, 3, 4])
+        }
+        after(cx) {
+            keyed(cx, [1, 3, 2, 4])
+        }
+    }
+
+    same_prefix_and_suffix_new_middle {
+        before(cx) {
+            keyed(cx, [2, 3, 4, 5])
+        }
+        after(cx) {
+            keyed(cx, [2, 7, 8, 5])
+        }
+    }
+
+    reverse_order {
+        before(cx) {
+            keyed(cx, [1, 2, 3])
+        }
+        after(cx) {
+            keyed(cx, [3, 2, 1])
+        }
+    }
+
+    no_shared_keys {
+        before(cx) {
+            keyed(cx, [1, 2])
+        }
+        after(cx) {
+            keyed(cx, [3, 4])
+        }
+    }
+
+    new_keys_in_middle {
+        before(cx) {
+            keyed(cx, [1, 2])
+        }
+        after(cx) {
+            keyed(cx, [1, 3, 4, 2])
+        }
+    }
+
+    new_keys_at_start_and_end {
+        before(cx) {
+            keyed(cx, [1, 2, 3])
+        }
+        after(cx) {
+            keyed(cx, [4, 1, 2, 3, 5])
+        }
+    }
+
+    delete_prefix {
+        before(cx) {
+            keyed(cx, [1, 2, 3])
+        }
+        after(cx) {
+            keyed(cx, [2, 3])
+        }
+    }
+
+    delete_suffix {
+        before(cx) {
+            keyed(cx, [1, 2, 3])
+        }
+        after(cx) {
+            keyed(cx, [1, 2])
+        }
+    }
+
+    delete_middle {
+        before(cx) {
+            keyed(cx, [1, 2, 3])
+        }
+        after(cx) {
+            keyed(cx, [1, 3])
+        }
+    }
+
+    nested_keyed_children {
+        before(cx) {
+            ul(&cx)
+                .children([
+                    li(&cx)
+                        .key(1)
+                        .children([
+                            keyed(cx, [2, 3, 4])
+                        ])
+                        .finish(),
+                    li(&cx)
+                        .key(5)
+                        .children([
+                            keyed(cx, [6, 7, 8])
+                        ])
+                        .finish(),
+                    li(&cx)
+                        .key(9)
+                        .children([
+                            keyed(cx, [10, 11, 12])
+                        ])
+                        .finish(),
+                ])
+                .finish()
+        }
+        after(cx) {
+            ul(&cx)
+                .children([
+                    li(&cx)
+                        .key(9)
+                        .children([
+                            keyed(cx, [12, 11, 10])
+                        ])
+                        .finish(),
+                    li(&cx)
+                        .key(5)
+                        .children([
+                            keyed(cx, [8, 7, 6])
+                        ])
+                        .finish(),
+                    li(&cx)
+                        .key(1)
+                        .children([
+                            keyed(cx, [4, 3, 2])
+                        ])
+                        .finish(),
+                ])
+                .finish()
+        }
+    }
+
+    hashed_keys_reorder {
+        before(cx) {
+            slugged(cx, ["intro", "setup", "usage", "faq"])
+        }
+        after(cx) {
+            slugged(cx, ["faq", "usage", "intro", "changelog", "setup"])
+        }
+    }
+
+    hashed_keys_delete_and_insert {
+        before(cx) {
+            slugged(cx, ["a", "b", "c"])
+        }
+        after(cx) {
+            slugged(cx, ["b", "d", "a"])
+        }
+    }
+
+    hashed_and_u32_keys_do_not_collide {
+        before(cx) {
+            ul(&cx)
+                .children([
+                    li(&cx).key(1).attr("class", "keyed").attr("id", "one").finish(),
+                    li(&cx).hashed_key(&1u32).attr("class", "keyed").attr("id", "hashed-one").finish(),
+                ])
+                .finish()
+        }
+        after(cx) {
+            ul(&cx)
+                .children([
+                    li(&cx).hashed_key(&1u32).attr("class", "keyed").attr("id", "hashed-one").finish(),
+                    li(&cx).key(1).attr("class", "keyed").attr("id", "one").finish(),
+                ])
+                .finish()
+        }
+    }
+}
+
+/// A key whose every value hashes the same, to force hashed keys to collide.
+#[derive(PartialEq, Eq)]
+struct Collide(u32);
+
+impl std::hash::Hash for Collide {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        0u32.hash(state);
+    }
+}
+
+fn colliding<'a, Ids>(cx: &mut RenderContext<'a>, ids: Ids) -> Node<'a>
+where
+    Ids: AsRef<[u32]>,
+{
+    let mut parent = div(&cx).attr("class", "parent");
+
+    for &id in ids.as_ref() {
+        let id_text = bumpalo::format!(in cx.bump, "{}", id).into_bump_str();
+        parent = parent.child(
+            div(&cx)
+                .hashed_key(&Collide(id))
+                .children([text(id_text)])
+                .finish(),
+        );
+    }
+
+    parent.finish()
+}
+
+#[test]
+fn colliding_hashed_keys_are_diffed_as_non_keyed() {
+    use crate::RenderFn;
+
+    let container = create_element("div");
+    let before = Rc::new(RenderFn(|cx| colliding(cx, [1, 2, 3])));
+    let after = Rc::new(RenderFn(|cx| colliding(cx, [3, 1])));
+
+    let vdom = Vdom::new(&container, before.clone());
+    assert_rendered(&container, &before);
+
+    vdom.immediately_render_and_diff(after.clone());
+    assert_rendered(&container, &after);
+}
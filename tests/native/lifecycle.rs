@@ -0,0 +1,224 @@
+use super::{create_element, RenderFn};
+use dodrio::{builder::*, bumpalo, Cached, Node, Render, RenderContext, Vdom};
+use std::cell::RefCell;
+
+thread_local! {
+    static LOG: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn take_log() -> Vec<String> {
+    LOG.with(|log| log.replace(vec![]))
+}
+
+// An `<li>` with the given id that logs when it is mounted and unmounted.
+fn item<'a>(cx: &mut RenderContext<'a>, id: u32) -> Node<'a> {
+    let id = bumpalo::format!(in cx.bump, "{}", id).into_bump_str();
+    li(&cx)
+        .attr("id", id)
+        .on_mount(|_root, _vdom, element| {
+            // The element is in place by the time it is mounted.
+            assert!(element.parent().is_some());
+            let id = element.attribute("id").unwrap();
+            LOG.with(|log| log.borrow_mut().push(format!("mount {}", id)));
+        })
+        .on_unmount(|_root, _vdom, element| {
+            assert!(element.parent().is_none());
+            let id = element.attribute("id").unwrap();
+            LOG.with(|log| log.borrow_mut().push(format!("unmount {}", id)));
+        })
+        .finish()
+}
+
+fn list<'a>(cx: &mut RenderContext<'a>, ids: &[u32]) -> Node<'a> {
+    let mut list = ul(&cx);
+    for &id in ids {
+        list = list.child(item(cx, id));
+    }
+    list.finish()
+}
+
+fn keyed_list<'a>(cx: &mut RenderContext<'a>, ids: &[u32]) -> Node<'a> {
+    let mut list = ul(&cx);
+    for &id in ids {
+        let item = div(&cx).key(id).child(item(cx, id)).finish();
+        list = list.child(item);
+    }
+    list.finish()
+}
+
+#[test]
+fn mounted_once_and_unmounted_once() {
+    take_log();
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| list(cx, &[1, 2])));
+    assert_eq!(take_log(), ["mount 1", "mount 2"]);
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| list(cx, &[1, 2])));
+    vdom.immediately_render_and_diff(RenderFn(|cx| list(cx, &[1, 2])));
+    assert_eq!(take_log(), [] as [String; 0]);
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| list(cx, &[])));
+    assert_eq!(take_log(), ["unmount 1", "unmount 2"]);
+}
+
+#[test]
+fn unmounted_with_next_siblings() {
+    take_log();
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| list(cx, &[1, 2, 3])));
+    take_log();
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| list(cx, &[1])));
+    assert_eq!(take_log(), ["unmount 2", "unmount 3"]);
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| list(cx, &[1, 4])));
+    assert_eq!(take_log(), ["mount 4"]);
+}
+
+#[test]
+fn keyed_moves_are_not_remounts() {
+    take_log();
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| keyed_list(cx, &[1, 2, 3])));
+    take_log();
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| keyed_list(cx, &[3, 1, 2])));
+    assert_eq!(take_log(), [] as [String; 0]);
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| keyed_list(cx, &[1, 4, 2])));
+    assert_eq!(take_log(), ["unmount 3", "mount 4"]);
+}
+
+#[test]
+fn descendants_of_replaced_nodes_are_unmounted() {
+    take_log();
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| list(cx, &[1, 2])));
+    take_log();
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| ol(&cx).children([item(cx, 3)]).finish()));
+    assert_eq!(take_log(), ["unmount 1", "unmount 2", "mount 3"]);
+
+    vdom.immediately_render_and_diff(RenderFn(|_cx| text("nothing")));
+    assert_eq!(take_log(), ["unmount 3"]);
+}
+
+#[test]
+fn callbacks_added_to_existing_elements() {
+    take_log();
+    let container = create_element("div");
+
+    let render = |with_callbacks: bool| {
+        RenderFn(move |cx| {
+            if with_callbacks {
+                item(cx, 1)
+            } else {
+                li(&cx).attr("id", "1").finish()
+            }
+        })
+    };
+
+    let vdom = Vdom::new(&container, render(false));
+    vdom.immediately_render_and_diff(render(true));
+    // The element was already mounted...
+    assert_eq!(take_log(), [] as [String; 0]);
+
+    // ...but is still unmounted.
+    vdom.immediately_render_and_diff(RenderFn(|cx| p(&cx).finish()));
+    assert_eq!(take_log(), ["unmount 1"]);
+}
+
+#[derive(Default)]
+struct Item(u32);
+
+impl<'a> Render<'a> for Item {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        item(cx, self.0)
+    }
+}
+
+#[derive(Default)]
+struct Other;
+
+impl<'a> Render<'a> for Other {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        span(&cx).finish()
+    }
+}
+
+#[test]
+fn cached_nodes() {
+    take_log();
+    let container = create_element("div");
+
+    // Each cached node is created from the same template, whose own lifecycle
+    // is never mounted.
+    let render = |ids: &'static [u32]| {
+        RenderFn(move |cx| {
+            let mut list = ul(&cx);
+            for &id in ids {
+                list = list.child(Cached::new(Item(id)).render(cx));
+            }
+            list.finish()
+        })
+    };
+    let vdom = Vdom::new(&container, render(&[1, 2]));
+    assert_eq!(take_log(), ["mount 1", "mount 2"]);
+
+    // Cached nodes with the same template are diffed, not replaced.
+    vdom.immediately_render_and_diff(render(&[1]));
+    assert_eq!(take_log(), ["unmount 2"]);
+
+    // Replacing a cached node unmounts its contents.
+    vdom.immediately_render_and_diff(RenderFn(|cx| {
+        ul(&cx).child(Cached::new(Other).render(cx)).finish()
+    }));
+    assert_eq!(take_log(), ["unmount 1"]);
+}
+
+#[test]
+fn cached_nodes_rendered_twice() {
+    take_log();
+    let container = create_element("div");
+
+    let render = |count: usize| {
+        let cached = Cached::new(Item(1));
+        RenderFn(move |cx| {
+            let mut list = ul(&cx);
+            for _ in 0..count {
+                list = list.child(cached.render(cx));
+            }
+            list.finish()
+        })
+    };
+    let vdom = Vdom::new(&container, render(2));
+    assert_eq!(take_log(), ["mount 1", "mount 1"]);
+
+    // Both of the cached node's elements are unmounted, not just the first.
+    vdom.immediately_render_and_diff(RenderFn(|cx| ul(&cx).finish()));
+    assert_eq!(take_log(), ["unmount 1", "unmount 1"]);
+}
+
+#[test]
+fn dropping_the_vdom_unmounts_everything() {
+    take_log();
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| list(cx, &[1, 2])));
+    take_log();
+
+    drop(vdom);
+    let mut log = take_log();
+    log.sort();
+    assert_eq!(log, ["unmount 1", "unmount 2"]);
+}
+
+#[test]
+fn unmounting_the_vdom_unmounts_everything() {
+    take_log();
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| keyed_list(cx, &[1])));
+    take_log();
+
+    let _component = vdom.unmount();
+    assert_eq!(take_log(), ["unmount 1"]);
+}
@@ -0,0 +1,214 @@
+use super::{create_element, RenderFn};
+use dodrio::{
+    builder::*, bumpalo, ListenerOptions, Node, RenderContext, RootRender, Vdom, VdomWeak,
+};
+use web_sys::Event;
+
+fn scroller<'a>(cx: &mut RenderContext<'a>, passive: bool) -> Node<'a> {
+    let options = ListenerOptions {
+        passive,
+        ..Default::default()
+    };
+    div(&cx)
+        .on_with_options("touchstart", options, |_root, _vdom, _event| {})
+        .on("click", |_root, _vdom, _event| {})
+        .finish()
+}
+
+#[test]
+fn listeners_are_added_with_their_options() {
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, RenderFn(|cx| scroller(cx, true)));
+
+    let div = container.first_child().unwrap();
+    assert_eq!(div.listeners(), ["touchstart", "click"]);
+    assert_eq!(
+        div.listener_options("touchstart"),
+        Some(ListenerOptions {
+            passive: true,
+            ..Default::default()
+        })
+    );
+    assert_eq!(
+        div.listener_options("click"),
+        Some(ListenerOptions::default())
+    );
+}
+
+#[test]
+fn changing_options_re_adds_the_listener() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| scroller(cx, true)));
+    vdom.immediately_render_and_diff(RenderFn(|cx| scroller(cx, true)));
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| scroller(cx, true)));
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "update_event_listener \"touchstart\" passive",
+            "update_event_listener \"click\"",
+        ]
+    );
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| scroller(cx, false)));
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "remove_event_listener \"touchstart\" passive",
+            "new_event_listener \"touchstart\"",
+            "update_event_listener \"click\"",
+        ]
+    );
+
+    let div = container.first_child().unwrap();
+    assert_eq!(
+        div.listener_options("touchstart"),
+        Some(ListenerOptions::default())
+    );
+}
+
+#[test]
+fn capture_and_bubble_listeners_are_distinct() {
+    let container = create_element("div");
+    let capture = ListenerOptions {
+        capture: true,
+        ..Default::default()
+    };
+    let vdom = Vdom::new(
+        &container,
+        RenderFn(move |cx| {
+            div(&cx)
+                .on_with_options("focus", capture, |_root, _vdom, _event| {})
+                .finish()
+        }),
+    );
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| {
+        div(&cx).on("focus", |_root, _vdom, _event| {}).finish()
+    }));
+    let div = container.first_child().unwrap();
+    assert_eq!(div.listeners(), ["focus"]);
+    assert_eq!(
+        div.listener_options("focus"),
+        Some(ListenerOptions::default())
+    );
+}
+
+fn rows<'a>(cx: &mut RenderContext<'a>, n: usize) -> Node<'a> {
+    let mut table = table(&cx);
+    for _ in 0..n {
+        table = table.child(
+            tr(&cx)
+                .on("click", |_root, _vdom, _event| {})
+                .on("mouseenter", |_root, _vdom, _event| {})
+                .finish(),
+        );
+    }
+    table.finish()
+}
+
+#[test]
+fn delegated_listeners_are_added_to_the_container_once() {
+    let container = create_element("div");
+    let vdom = Vdom::builder()
+        .delegate_events(true)
+        .mount(&container, RenderFn(|cx| rows(cx, 3)));
+    assert_eq!(container.listeners(), ["click", "mouseenter"]);
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| rows(cx, 10)));
+    assert_eq!(container.listeners(), ["click", "mouseenter"]);
+
+    drop(vdom);
+    assert!(container.listeners().is_empty());
+}
+
+#[test]
+fn listeners_with_options_are_not_delegated() {
+    let container = create_element("div");
+    let _vdom = Vdom::builder()
+        .delegate_events(true)
+        .mount(&container, RenderFn(|cx| scroller(cx, true)));
+    assert_eq!(container.listeners(), ["click"]);
+
+    let div = container.first_child().unwrap();
+    assert_eq!(div.listeners(), ["touchstart", "click"]);
+}
+
+#[test]
+fn listeners_are_not_delegated_by_default() {
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, RenderFn(|cx| rows(cx, 3)));
+    assert!(container.listeners().is_empty());
+}
+
+#[test]
+fn typed_listeners_listen_for_their_event() {
+    let container = create_element("div");
+    let _vdom = Vdom::new(
+        &container,
+        RenderFn(|cx| {
+            form(&cx)
+                .on_click(|_root, _vdom, _event| {})
+                .on_pointermove(|_root, _vdom, _event| {})
+                .on_keydown(|_root, _vdom, _event| {})
+                .on_input(|_root, _vdom, _event, _value| {})
+                .on_change(|_root, _vdom, _event, _value| {})
+                .on_submit(|_root, _vdom, _event| {})
+                .finish()
+        }),
+    );
+
+    let form = container.first_child().unwrap();
+    assert_eq!(
+        form.listeners(),
+        [
+            "click",
+            "pointermove",
+            "keydown",
+            "input",
+            "change",
+            "submit"
+        ]
+    );
+}
+
+#[test]
+fn listeners_with_data_can_borrow_from_the_bump() {
+    fn on_row_click(_root: &mut dyn RootRender, _vdom: VdomWeak, _event: Event, _label: &str) {}
+
+    let container = create_element("div");
+    let _vdom = Vdom::new(
+        &container,
+        RenderFn(|cx| {
+            let mut list = ul(&cx);
+            for i in 0..3 {
+                let label = bumpalo::format!(in cx.bump, "row {}", i).into_bump_str();
+                list = list.child(li(&cx).on_with("click", label, on_row_click).finish());
+            }
+            list.finish()
+        }),
+    );
+
+    let list = container.first_child().unwrap();
+    for row in list.children() {
+        assert_eq!(row.listeners(), ["click"]);
+    }
+}
+
+#[test]
+fn async_listeners_are_added_like_other_listeners() {
+    let container = create_element("div");
+    let _vdom = Vdom::new(
+        &container,
+        RenderFn(|cx| {
+            button(&cx)
+                .on_async("click", |_root, _vdom, _event| async {})
+                .finish()
+        }),
+    );
+
+    let button = container.first_child().unwrap();
+    assert_eq!(button.listeners(), ["click"]);
+}
@@ -0,0 +1,300 @@
+//! Test suite for the in-memory DOM that change lists are applied to on native
+//! targets.
+//!
+//! These mirror the tests in `tests/web`, but run with a plain `cargo test`
+//! instead of in a headless browser.
+
+#![cfg(all(
+    feature = "xxx-unstable-internal-use-only",
+    not(target_arch = "wasm32")
+))]
+
+use bumpalo::Bump;
+use dodrio::{
+    native_dom, Attribute, CachedSet, ElementNode, FragmentNode, Node, NodeKind, PortalNode,
+    Property, RawHtmlNode, Render, RenderContext, TextNode, Vdom,
+};
+use fxhash::FxHashMap;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub mod after_render;
+pub mod cached;
+pub mod classes;
+pub mod disassemble;
+pub mod empty;
+pub mod flush;
+pub mod fragments;
+pub mod incremental;
+pub mod keyed;
+pub mod lifecycle;
+pub mod listeners;
+pub mod node_refs;
+pub mod portals;
+pub mod properties;
+pub mod raw_html;
+pub mod remote;
+pub mod render;
+pub mod scheduler;
+pub mod styles;
+
+pub fn create_element(tag: &str) -> native_dom::Node {
+    native_dom::Node::create_element(tag)
+}
+
+/// Assert that the `container` contains the in-memory DOM tree that matches
+/// `r`'s rendered virtual DOM.
+pub fn assert_rendered<R: for<'a> Render<'a>>(container: &native_dom::Node, r: &R) {
+    let cached_set = &RefCell::new(CachedSet::default());
+    let bump = &Bump::new();
+    let templates = &mut FxHashMap::default();
+    let cx = &mut RenderContext::new(bump, cached_set, templates);
+    let node = r.render(cx);
+
+    // The root may be a fragment, so compare the container's children with the
+    // flattened root, just like any other element's children.
+    let cached_set = cached_set.borrow();
+    check_children(
+        &cached_set,
+        &container.children(),
+        std::slice::from_ref(&node),
+    );
+
+    fn check_node(cached_set: &CachedSet, actual: &native_dom::Node, expected: &Node) {
+        match expected.kind {
+            NodeKind::Text(TextNode { text }) => {
+                assert!(actual.is_text(), "expected text node, found {:?}", actual);
+                assert_eq!(
+                    actual.text_content(),
+                    text,
+                    "actual.text_content() == expected.text()"
+                );
+            }
+            NodeKind::Element(&ElementNode {
+                tag_name,
+                listeners,
+                attributes,
+                properties,
+                styles,
+                classes,
+                children,
+                namespace,
+                ..
+            }) => {
+                assert_eq!(
+                    actual.tag_name().as_deref(),
+                    Some(tag_name),
+                    "actual.tag_name() == expected.tag_name()"
+                );
+                assert_eq!(
+                    actual.namespace().as_deref(),
+                    namespace,
+                    "actual.namespace() == expected.namespace()"
+                );
+                assert_eq!(
+                    actual.listeners().len(),
+                    listeners.len(),
+                    "actual's number of listeners == expected's number of listeners"
+                );
+                check_attributes(actual, attributes);
+                check_classes(actual, attributes, classes);
+                check_properties(actual, properties);
+                check_styles(actual, styles);
+                check_children(cached_set, &actual.children(), children);
+            }
+            NodeKind::Cached(ref c) => {
+                let (expected, _template) = cached_set.get(c.id);
+                check_node(cached_set, actual, expected);
+            }
+            NodeKind::Empty => {
+                assert!(actual.is_comment(), "expected comment, found {:?}", actual);
+            }
+            NodeKind::RawHtml(RawHtmlNode { html }) => {
+                // The markup is kept in a wrapper.
+                assert_eq!(actual.tag_name().as_deref(), Some("div"));
+                let children = actual.children();
+                assert_eq!(children.len(), 1, "raw HTML wrapper's children");
+                assert_eq!(
+                    children[0].raw_html().as_deref(),
+                    Some(html),
+                    "actual.raw_html() == expected.html()"
+                );
+            }
+            NodeKind::Portal(PortalNode {
+                target, children, ..
+            }) => {
+                assert!(actual.is_comment(), "expected comment, found {:?}", actual);
+                // The portal's children are between a pair of comment markers
+                // in the target, which must not have any other contents.
+                let nodes = target.element.children();
+                assert!(
+                    nodes.len() >= 2
+                        && nodes[0].is_comment()
+                        && nodes[nodes.len() - 1].is_comment(),
+                    "portal target should hold only the portal's markers and children, found {:?}",
+                    nodes
+                );
+                check_children(cached_set, &nodes[1..nodes.len() - 1], children);
+            }
+            NodeKind::Fragment(_) => unreachable!("fragments are flattened"),
+        }
+    }
+
+    fn check_attributes(actual: &native_dom::Node, expected: &[Attribute]) {
+        // Class list tokens live in the `class` attribute, which may also be
+        // left behind empty after removing the last token, so it is checked by
+        // `check_classes` instead.
+        let has_class_attr = actual.attribute("class").is_some()
+            && expected.iter().all(|attr| attr.name() != "class");
+        assert_eq!(
+            actual.attributes().len() - has_class_attr as usize,
+            expected.len(),
+            "actual's number of attributes == expected's number of attributes"
+        );
+        for attr in expected {
+            assert_eq!(
+                actual.attribute(attr.name()).as_deref(),
+                Some(attr.value()),
+                "actual attr value == expected attr value for attr \"{}\"",
+                attr.name()
+            );
+        }
+    }
+
+    fn check_properties(actual: &native_dom::Node, expected: &[Property]) {
+        assert_eq!(
+            actual.properties().len(),
+            expected.len(),
+            "actual's number of properties == expected's number of properties"
+        );
+        for prop in expected {
+            assert_eq!(
+                actual.property(prop.name()),
+                Some(prop.value().into()),
+                "actual prop value == expected prop value for prop \"{}\"",
+                prop.name()
+            );
+        }
+    }
+
+    fn check_classes(actual: &native_dom::Node, attributes: &[Attribute], expected: &[&str]) {
+        if attributes.iter().any(|attr| attr.name() == "class") {
+            return;
+        }
+        let actual = actual.attribute("class").unwrap_or_default();
+        let mut actual: Vec<_> = actual.split_whitespace().collect();
+        actual.sort_unstable();
+        let mut expected = expected.to_vec();
+        expected.sort_unstable();
+        expected.dedup();
+        assert_eq!(actual, expected, "actual classes == expected classes");
+    }
+
+    fn check_styles(actual: &native_dom::Node, expected: &[Attribute]) {
+        assert_eq!(
+            actual.styles().len(),
+            expected.len(),
+            "actual's number of styles == expected's number of styles"
+        );
+        for style in expected {
+            assert_eq!(
+                actual.style(style.name()).as_deref(),
+                Some(style.value()),
+                "actual style value == expected style value for style \"{}\"",
+                style.name()
+            );
+        }
+    }
+
+    fn check_children(cached_set: &CachedSet, actual: &[native_dom::Node], expected: &[Node]) {
+        let mut flattened = vec![];
+        flatten(cached_set, expected, &mut flattened);
+        let expected = flattened;
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "actual children length == expected children length"
+        );
+        for (actual_child, child) in actual.iter().zip(expected) {
+            check_node(cached_set, actual_child, child);
+        }
+    }
+
+    // Flatten fragments, including cached ones, into their parent's children.
+    fn flatten<'a>(
+        cached_set: &'a CachedSet,
+        children: &'a [Node<'a>],
+        out: &mut Vec<&'a Node<'a>>,
+    ) {
+        for child in children {
+            let resolved = match child.kind {
+                NodeKind::Cached(ref c) => cached_set.get(c.id).0,
+                _ => child,
+            };
+            match resolved.kind {
+                NodeKind::Fragment(FragmentNode { children }) => flatten(cached_set, children, out),
+                _ => out.push(child),
+            }
+        }
+    }
+}
+
+/// Use the function `F` to render.
+pub struct RenderFn<F>(F)
+where
+    F: for<'a> Fn(&mut RenderContext<'a>) -> Node<'a>;
+
+impl<'a, F> Render<'a> for RenderFn<F>
+where
+    F: for<'b> Fn(&mut RenderContext<'b>) -> Node<'b>,
+{
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        (self.0)(cx)
+    }
+}
+
+/// Assert that if we start by rendering the `before` virtual DOM tree into an
+/// in-memory DOM tree, and then diff it with the `after` virtual DOM tree, then
+/// the in-memory DOM tree correctly matches `after`.
+pub fn assert_before_after<R, S>(before: R, after: S)
+where
+    R: 'static + for<'a> Render<'a>,
+    S: 'static + for<'a> Render<'a>,
+{
+    let container = create_element("div");
+
+    let before = Rc::new(before);
+    let after = Rc::new(after);
+
+    let vdom = Vdom::new(&container, before.clone());
+    assert_rendered(&container, &before);
+
+    vdom.immediately_render_and_diff(after.clone());
+    assert_rendered(&container, &after);
+}
+
+/// A helper macro for declaring a bunch of `assert_before_after` tests.
+#[macro_export]
+macro_rules! before_after {
+    ( $(
+        $name:ident {
+            before($before_cx:ident) {
+                $( $before:tt )*
+            }
+            after($after_cx:ident) {
+                $( $after:tt )*
+            }
+        }
+    )* ) => {
+        $(
+            #[test]
+            fn $name() {
+                use $crate::{assert_before_after, RenderFn};
+                assert_before_after(
+                    RenderFn(|$before_cx| { $( $before )* }),
+                    RenderFn(|$after_cx| { $( $after )* }),
+                );
+            }
+        )*
+    }
+}
@@ -0,0 +1,126 @@
+use super::{create_element, RenderFn};
+use dodrio::{builder::*, Cached, Node, NodeRef, Render, RenderContext, Vdom};
+
+fn input_with_ref<'a>(cx: &mut RenderContext<'a>, node_ref: &NodeRef) -> Node<'a> {
+    div(&cx)
+        .children([input(&cx).node_ref(node_ref).finish()])
+        .finish()
+}
+
+#[test]
+fn node_ref_is_filled_in_when_created() {
+    let node_ref = NodeRef::new();
+    assert!(node_ref.get().is_none());
+
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, {
+        let node_ref = node_ref.clone();
+        RenderFn(move |cx| input_with_ref(cx, &node_ref))
+    });
+
+    let input = container.first_child().unwrap().first_child().unwrap();
+    assert!(node_ref.get().unwrap().is_same_node(&input));
+}
+
+#[test]
+fn node_ref_stays_valid_across_renders() {
+    let node_ref = NodeRef::new();
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, {
+        let node_ref = node_ref.clone();
+        RenderFn(move |cx| input_with_ref(cx, &node_ref))
+    });
+    vdom.immediately_render_and_diff({
+        let node_ref = node_ref.clone();
+        RenderFn(move |cx| input_with_ref(cx, &node_ref))
+    });
+    let input = node_ref.get().unwrap();
+
+    let disassembly = vdom.immediately_render_and_disassemble({
+        let node_ref = node_ref.clone();
+        RenderFn(move |cx| input_with_ref(cx, &node_ref))
+    });
+    assert_eq!(disassembly.lines(), [] as [String; 0]);
+    assert!(node_ref.get().unwrap().is_same_node(&input));
+}
+
+#[test]
+fn node_ref_is_cleared_when_removed() {
+    let node_ref = NodeRef::new();
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, {
+        let node_ref = node_ref.clone();
+        RenderFn(move |cx| input_with_ref(cx, &node_ref))
+    });
+    assert!(node_ref.get().is_some());
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| div(&cx).finish()));
+    assert!(node_ref.get().is_none());
+}
+
+#[test]
+fn node_ref_follows_its_element() {
+    let node_ref = NodeRef::new();
+    let container = create_element("div");
+
+    let render = |first: bool| {
+        let node_ref = node_ref.clone();
+        RenderFn(move |cx| {
+            let mut a = span(&cx);
+            let mut b = em(&cx);
+            if first {
+                a = a.node_ref(&node_ref);
+            } else {
+                b = b.node_ref(&node_ref);
+            }
+            div(&cx).children([a.finish(), b.finish()]).finish()
+        })
+    };
+
+    let vdom = Vdom::new(&container, render(true));
+    vdom.immediately_render_and_diff(render(true));
+    assert_eq!(node_ref.get().unwrap().tag_name().as_deref(), Some("span"));
+
+    let disassembly = vdom.immediately_render_and_disassemble(render(false));
+    assert_eq!(
+        disassembly.lines(),
+        ["push_child 0", "push_child 1", "set_node_ref"]
+    );
+    assert_eq!(node_ref.get().unwrap().tag_name().as_deref(), Some("em"));
+
+    vdom.immediately_render_and_diff(render(true));
+    assert_eq!(node_ref.get().unwrap().tag_name().as_deref(), Some("span"));
+}
+
+#[derive(Default)]
+struct Canvas(NodeRef);
+
+impl<'a> Render<'a> for Canvas {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        canvas(&cx).node_ref(&self.0).finish()
+    }
+}
+
+#[test]
+fn node_ref_is_filled_in_for_template_clones() {
+    let node_ref = NodeRef::new();
+    let container = create_element("div");
+
+    let render = |n: usize| {
+        let node_ref = node_ref.clone();
+        RenderFn(move |cx| {
+            let mut list = div(&cx);
+            for _ in 0..n {
+                list = list.child(Cached::new(Canvas(node_ref.clone())).render(cx));
+            }
+            list.finish()
+        })
+    };
+
+    let vdom = Vdom::new(&container, render(1));
+    vdom.immediately_render_and_diff(render(2));
+
+    // The node ref points at the most recently created clone.
+    let canvases = container.first_child().unwrap().children();
+    assert!(node_ref.get().unwrap().is_same_node(&canvases[1]));
+}
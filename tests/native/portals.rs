@@ -0,0 +1,402 @@
+use super::{assert_rendered, create_element, RenderFn};
+use dodrio::{builder::*, bumpalo, native_dom, Cached, Node, Render, RenderContext, Vdom};
+use std::rc::Rc;
+
+struct Modal {
+    target: native_dom::Node,
+    items: Vec<&'static str>,
+}
+
+impl Modal {
+    fn new(target: &native_dom::Node, items: &[&'static str]) -> Modal {
+        Modal {
+            target: target.clone(),
+            items: items.to_vec(),
+        }
+    }
+}
+
+impl<'a> Render<'a> for Modal {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let items = bumpalo::collections::Vec::from_iter_in(
+            self.items
+                .iter()
+                .map(|item| li(&cx).children([text(item)]).finish()),
+            cx.bump,
+        );
+        let list = ul(&cx).children(items).finish();
+        div(&cx)
+            .children([text("page"), portal(cx, &self.target, [list])])
+            .finish()
+    }
+}
+
+struct Toggle<R> {
+    open: bool,
+    inner: R,
+}
+
+impl<'a, R: Render<'a>> Render<'a> for Toggle<R> {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        if self.open {
+            self.inner.render(cx)
+        } else {
+            div(&cx).children([text("page"), empty()]).finish()
+        }
+    }
+}
+
+#[test]
+fn children_are_rendered_into_the_target() {
+    let container = create_element("div");
+    let target = create_element("div");
+    let modal = Rc::new(Modal::new(&target, &["a", "b"]));
+    let _vdom = Vdom::new(&container, modal.clone());
+    assert_rendered(&container, &modal);
+
+    let page = container.first_child().unwrap();
+    assert_eq!(page.text_content(), "page");
+    assert!(page.children()[1].is_comment());
+    assert_eq!(target.text_content(), "ab");
+}
+
+#[test]
+fn children_are_diffed_in_place() {
+    let container = create_element("div");
+    let target = create_element("div");
+    let vdom = Vdom::new(&container, Modal::new(&target, &["a", "b"]));
+    let list = target.children()[1].clone();
+
+    let modal = Rc::new(Modal::new(&target, &["a", "c", "d"]));
+    vdom.immediately_render_and_diff(modal.clone());
+    assert_rendered(&container, &modal);
+    assert_eq!(target.text_content(), "acd");
+    assert!(target.children()[1].is_same_node(&list));
+}
+
+#[test]
+fn children_are_removed_with_the_portal() {
+    let container = create_element("div");
+    let target = create_element("div");
+    let vdom = Vdom::new(
+        &container,
+        Toggle {
+            open: true,
+            inner: Modal::new(&target, &["a"]),
+        },
+    );
+    assert_eq!(target.text_content(), "a");
+
+    vdom.immediately_render_and_diff(Toggle {
+        open: false,
+        inner: Modal::new(&target, &["a"]),
+    });
+    assert!(target.children().is_empty());
+
+    let modal = Rc::new(Toggle {
+        open: true,
+        inner: Modal::new(&target, &["b"]),
+    });
+    vdom.immediately_render_and_diff(modal.clone());
+    assert_rendered(&container, &modal);
+    assert_eq!(target.text_content(), "b");
+}
+
+#[test]
+fn children_move_to_a_new_target() {
+    let container = create_element("div");
+    let first = create_element("div");
+    let second = create_element("div");
+    let vdom = Vdom::new(&container, Modal::new(&first, &["a"]));
+    assert_eq!(first.text_content(), "a");
+
+    let modal = Rc::new(Modal::new(&second, &["a"]));
+    vdom.immediately_render_and_diff(modal.clone());
+    assert_rendered(&container, &modal);
+    assert!(first.children().is_empty());
+    assert_eq!(second.text_content(), "a");
+}
+
+struct Nested {
+    outer: native_dom::Node,
+    inner: native_dom::Node,
+}
+
+impl<'a> Render<'a> for Nested {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let inner = portal(cx, &self.inner, [text("inner")]);
+        let outer = portal(cx, &self.outer, [text("outer"), inner]);
+        div(&cx).children([outer]).finish()
+    }
+}
+
+#[test]
+fn nested_portals_are_removed() {
+    let container = create_element("div");
+    let outer = create_element("div");
+    let inner = create_element("div");
+    let nested = Rc::new(Nested {
+        outer: outer.clone(),
+        inner: inner.clone(),
+    });
+    let vdom = Vdom::new(
+        &container,
+        Toggle {
+            open: true,
+            inner: nested.clone(),
+        },
+    );
+    assert_rendered(&container, &nested);
+    assert_eq!(outer.text_content(), "outer");
+    assert_eq!(inner.text_content(), "inner");
+
+    vdom.immediately_render_and_diff(Toggle {
+        open: false,
+        inner: nested,
+    });
+    assert!(outer.children().is_empty());
+    assert!(inner.children().is_empty());
+}
+
+thread_local! {
+    static TOOLTIP_ROOT: native_dom::Node = create_element("div");
+}
+
+#[derive(Default)]
+struct Tooltip;
+
+impl<'a> Render<'a> for Tooltip {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let root = TOOLTIP_ROOT.with(|root| root.clone());
+        let tip = p(&cx).children([text("tip")]).finish();
+        span(&cx).children([portal(cx, &root, [tip])]).finish()
+    }
+}
+
+#[test]
+fn nodes_created_from_templates_get_portal_children() {
+    fn tooltips<'a>(cx: &mut RenderContext<'a>, n: usize) -> Node<'a> {
+        let mut list = div(&cx);
+        for _ in 0..n {
+            list = list.child(Cached::new(Tooltip).render(cx));
+        }
+        list.finish()
+    }
+
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| tooltips(cx, 1)));
+    let root = TOOLTIP_ROOT.with(|root| root.clone());
+    assert_eq!(root.text_content(), "tip");
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| tooltips(cx, 0)));
+    assert!(root.children().is_empty());
+}
+
+#[test]
+fn delegated_listeners_are_added_to_the_target() {
+    struct Button {
+        target: native_dom::Node,
+    }
+
+    impl<'a> Render<'a> for Button {
+        fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+            let b = button(&cx).on("click", |_root, _vdom, _event| {}).finish();
+            portal(cx, &self.target, [b])
+        }
+    }
+
+    let container = create_element("div");
+    let target = create_element("div");
+    let vdom = Vdom::builder().delegate_events(true).mount(
+        &container,
+        Button {
+            target: target.clone(),
+        },
+    );
+    assert_eq!(container.listeners(), ["click"]);
+    assert_eq!(target.listeners(), ["click"]);
+
+    drop(vdom);
+    assert!(target.listeners().is_empty());
+}
+
+#[test]
+fn children_are_removed_when_the_vdom_is_dropped() {
+    let container = create_element("div");
+    let target = create_element("div");
+    let vdom = Vdom::new(&container, Modal::new(&target, &["a"]));
+    assert_eq!(target.text_content(), "a");
+
+    drop(vdom);
+    assert!(target.children().is_empty());
+}
+
+#[test]
+fn other_contents_of_the_target_are_left_alone() {
+    // The target is another virtual DOM's container.
+    let target = create_element("div");
+    let _page = Vdom::new(&target, RenderFn(|_cx| text("page")));
+
+    let container = create_element("div");
+    let vdom = Vdom::new(
+        &container,
+        Toggle {
+            open: true,
+            inner: Modal::new(&target, &["a"]),
+        },
+    );
+    assert_eq!(target.text_content(), "pagea");
+
+    vdom.immediately_render_and_diff(Toggle {
+        open: false,
+        inner: Modal::new(&target, &["a"]),
+    });
+    assert_eq!(target.text_content(), "page");
+
+    vdom.immediately_render_and_diff(Toggle {
+        open: true,
+        inner: Modal::new(&target, &["b"]),
+    });
+    assert_eq!(target.text_content(), "pageb");
+
+    drop(vdom);
+    assert_eq!(target.text_content(), "page");
+}
+
+struct Shared {
+    target: native_dom::Node,
+    first: bool,
+}
+
+impl<'a> Render<'a> for Shared {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let first = if self.first {
+            portal(cx, &self.target, [text("first")])
+        } else {
+            empty()
+        };
+        let second = portal(cx, &self.target, [text("second")]);
+        div(&cx).children([first, second]).finish()
+    }
+}
+
+#[test]
+fn portals_can_share_a_target() {
+    let container = create_element("div");
+    let target = create_element("div");
+    let vdom = Vdom::new(
+        &container,
+        Shared {
+            target: target.clone(),
+            first: true,
+        },
+    );
+    assert_eq!(target.text_content(), "firstsecond");
+
+    vdom.immediately_render_and_diff(Shared {
+        target: target.clone(),
+        first: false,
+    });
+    assert_eq!(target.text_content(), "second");
+
+    vdom.immediately_render_and_diff(Shared {
+        target: target.clone(),
+        first: true,
+    });
+    assert_eq!(target.text_content(), "secondfirst");
+}
+
+struct Lists {
+    target: native_dom::Node,
+    first: Vec<&'static str>,
+}
+
+impl<'a> Render<'a> for Lists {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let items = bumpalo::collections::Vec::from_iter_in(
+            self.first.iter().map(|item| text(item)),
+            cx.bump,
+        );
+        let first = portal(cx, &self.target, items);
+        let second = portal(cx, &self.target, [text("|second")]);
+        div(&cx).children([first, second]).finish()
+    }
+}
+
+#[test]
+fn children_stay_between_their_markers() {
+    // The target is another virtual DOM's container.
+    let target = create_element("div");
+    let _page = Vdom::new(&target, RenderFn(|_cx| text("page|")));
+
+    let container = create_element("div");
+    let lists = |first: &[&'static str]| Lists {
+        target: target.clone(),
+        first: first.to_vec(),
+    };
+    let vdom = Vdom::new(&container, lists(&["a", "b", "c"]));
+    assert_eq!(target.text_content(), "page|abc|second");
+
+    // Removes the trailing children.
+    vdom.immediately_render_and_diff(lists(&["a"]));
+    assert_eq!(target.text_content(), "page|a|second");
+
+    // Replaces all children with a single text node.
+    vdom.immediately_render_and_diff(lists(&["x"]));
+    assert_eq!(target.text_content(), "page|x|second");
+
+    // Removes all children.
+    vdom.immediately_render_and_diff(lists(&[]));
+    assert_eq!(target.text_content(), "page||second");
+
+    // Appends children.
+    vdom.immediately_render_and_diff(lists(&["d", "e"]));
+    assert_eq!(target.text_content(), "page|de|second");
+}
+
+#[test]
+fn unused_targets_are_released() {
+    struct Button {
+        target: native_dom::Node,
+    }
+
+    impl<'a> Render<'a> for Button {
+        fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+            let b = button(&cx).on("click", |_root, _vdom, _event| {}).finish();
+            div(&cx).children([portal(cx, &self.target, [b])]).finish()
+        }
+    }
+
+    let container = create_element("div");
+    let target = create_element("div");
+    let vdom = Vdom::builder().delegate_events(true).mount(
+        &container,
+        Toggle {
+            open: true,
+            inner: Button {
+                target: target.clone(),
+            },
+        },
+    );
+    assert_eq!(target.listeners(), ["click"]);
+
+    vdom.immediately_render_and_diff(Toggle {
+        open: false,
+        inner: Button {
+            target: target.clone(),
+        },
+    });
+    assert!(target.children().is_empty());
+    assert!(target.listeners().is_empty());
+    assert_eq!(container.listeners(), ["click"]);
+
+    let button = Rc::new(Toggle {
+        open: true,
+        inner: Button {
+            target: target.clone(),
+        },
+    });
+    vdom.immediately_render_and_diff(button.clone());
+    assert_rendered(&container, &button);
+    assert_eq!(target.listeners(), ["click"]);
+}
@@ -0,0 +1,173 @@
+use super::{assert_rendered, before_after, create_element, RenderFn};
+use dodrio::{builder::*, native_dom::PropertyValue, Cached, Node, Render, RenderContext, Vdom};
+use std::rc::Rc;
+
+fn checkbox<'a>(cx: &mut RenderContext<'a>, indeterminate: bool) -> Node<'a> {
+    input(&cx)
+        .attr("type", "checkbox")
+        .prop("indeterminate", indeterminate)
+        .finish()
+}
+
+#[test]
+fn properties_are_not_attributes() {
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, RenderFn(|cx| checkbox(cx, true)));
+
+    let input = container.first_child().unwrap();
+    assert_eq!(input.attribute("indeterminate"), None);
+    assert_eq!(
+        input.property("indeterminate"),
+        Some(PropertyValue::Bool(true))
+    );
+}
+
+#[test]
+fn unchanged_properties_are_not_set_again() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| checkbox(cx, true)));
+    vdom.immediately_render_and_diff(RenderFn(|cx| checkbox(cx, true)));
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| checkbox(cx, true)));
+    assert!(disassembly.lines().is_empty());
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| checkbox(cx, false)));
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "add_cached_string 4 \"indeterminate\"",
+            "set_property_bool \"indeterminate\" false",
+        ]
+    );
+}
+
+#[test]
+fn property_value_types() {
+    let r = Rc::new(RenderFn(|cx| {
+        video(&cx)
+            .prop("muted", true)
+            .prop("volume", 0.5)
+            .prop("currentTime", 42)
+            .prop("title", "intro")
+            .finish()
+    }));
+
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, r.clone());
+    assert_rendered(&container, &r);
+
+    let video = container.first_child().unwrap();
+    assert_eq!(
+        video.properties(),
+        [
+            ("muted".to_string(), PropertyValue::Bool(true)),
+            ("volume".to_string(), PropertyValue::Number(0.5)),
+            ("currentTime".to_string(), PropertyValue::Number(42.0)),
+            ("title".to_string(), PropertyValue::String("intro".into())),
+        ]
+    );
+}
+
+#[derive(Default)]
+struct Toggle;
+
+impl<'a> Render<'a> for Toggle {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        li(&cx).children([checkbox(cx, true)]).finish()
+    }
+}
+
+#[test]
+fn nodes_created_from_templates_get_properties() {
+    fn list<'a>(cx: &mut RenderContext<'a>, n: usize) -> Node<'a> {
+        let mut list = ul(&cx);
+        for _ in 0..n {
+            list = list.child(Cached::new(Toggle).render(cx));
+        }
+        list.finish()
+    }
+
+    // Like listeners, properties aren't copied when cloning a template, so
+    // each clone must have its own properties set.
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| list(cx, 2)));
+    assert_rendered(&container, &RenderFn(|cx| list(cx, 2)));
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| list(cx, 4)));
+    assert_rendered(&container, &RenderFn(|cx| list(cx, 4)));
+}
+
+#[test]
+fn removed_properties_get_their_defaults_back() {
+    fn controls<'a>(cx: &mut RenderContext<'a>, set: bool) -> Node<'a> {
+        let mut text_input = input(&cx).attr("value", "markup");
+        let mut checkbox = input(&cx).attr("type", "checkbox");
+        let mut widget = ElementBuilder::new(cx.bump, "my-widget");
+        if set {
+            text_input = text_input.prop("value", "typed").prop("tabIndex", 3);
+            checkbox = checkbox.prop("checked", true);
+            widget = widget.prop("config", "dark");
+        }
+        form(&cx)
+            .children([text_input.finish(), checkbox.finish(), widget.finish()])
+            .finish()
+    }
+
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| controls(cx, true)));
+    let children = container.first_child().unwrap().children();
+    assert_eq!(
+        children[0].property("value"),
+        Some(PropertyValue::String("typed".into()))
+    );
+    assert_eq!(
+        children[2].property("config"),
+        Some(PropertyValue::String("dark".into()))
+    );
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| controls(cx, false)));
+    assert_eq!(
+        children[0].property("value"),
+        Some(PropertyValue::String("markup".into()))
+    );
+    assert_eq!(
+        children[0].property("tabIndex"),
+        Some(PropertyValue::Number(0.0))
+    );
+    assert_eq!(
+        children[1].property("checked"),
+        Some(PropertyValue::Bool(false))
+    );
+    assert_eq!(children[2].property("config"), None);
+    assert!(children.iter().all(|child| child.properties().is_empty()));
+}
+
+before_after! {
+    add_property {
+        before(cx) {
+            input(&cx).finish()
+        }
+        after(cx) {
+            input(&cx).prop("value", "hello").finish()
+        }
+    }
+
+    change_property_type {
+        before(cx) {
+            div(&cx).prop("scrollTop", 10).finish()
+        }
+        after(cx) {
+            div(&cx).prop("scrollTop", "10").finish()
+        }
+    }
+
+    remove_property {
+        before(cx) {
+            video(&cx).prop("muted", true).prop("volume", 0.25).finish()
+        }
+        after(cx) {
+            video(&cx).prop("volume", 0.25).finish()
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use super::{assert_rendered, before_after, create_element, RenderFn};
+use dodrio::{builder::*, Node, RenderContext, Vdom};
+use std::rc::Rc;
+
+fn post<'a>(cx: &mut RenderContext<'a>) -> Node<'a> {
+    div(&cx)
+        .children([raw_html("<p>Hello, <em>world</em>!</p>")])
+        .finish()
+}
+
+#[test]
+fn raw_html_is_not_diffed_when_unchanged() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(post));
+    vdom.immediately_render_and_diff(RenderFn(post));
+    let markup = container.first_child().unwrap().first_child().unwrap();
+    assert_eq!(
+        markup.first_child().unwrap().raw_html().as_deref(),
+        Some("<p>Hello, <em>world</em>!</p>")
+    );
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(post));
+    assert!(disassembly.lines().is_empty());
+    let after = container.first_child().unwrap().first_child().unwrap();
+    assert!(after.is_same_node(&markup));
+}
+
+#[test]
+fn changed_raw_html_is_replaced() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|_cx| raw_html("<b>old</b>")));
+    vdom.immediately_render_and_diff(RenderFn(|_cx| raw_html("<b>old</b>")));
+
+    let new = Rc::new(RenderFn(|_cx| raw_html("<i>new</i>")));
+    let disassembly = vdom.immediately_render_and_disassemble(new.clone());
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "create_raw_html \"<i>new</i>\"",
+            "replace_with"
+        ]
+    );
+    assert_rendered(&container, &new);
+}
+
+#[test]
+fn raw_html_may_have_several_top_level_nodes() {
+    // Rendered markdown, with a trailing newline.
+    const MARKDOWN: &str =
+        "<h1>Title</h1>\n<p>Hello, <em>world</em>!</p>\n<ul>\n<li>a</li>\n</ul>\n";
+
+    let container = create_element("div");
+    let before = Rc::new(RenderFn(|cx| {
+        div(&cx)
+            .children([raw_html(MARKDOWN), text("after")])
+            .finish()
+    }));
+    let vdom = Vdom::new(&container, before.clone());
+    assert_rendered(&container, &before);
+
+    let after = Rc::new(RenderFn(|cx| {
+        div(&cx)
+            .children([raw_html("<p>a</p><p>b</p>  "), text("after")])
+            .finish()
+    }));
+    let disassembly = vdom.immediately_render_and_disassemble(after.clone());
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "push_child 0",
+            "create_raw_html \"<p>a</p><p>b</p>  \"",
+            "replace_with"
+        ]
+    );
+    assert_rendered(&container, &after);
+
+    let removed = Rc::new(RenderFn(|cx| div(&cx).children([text("after")]).finish()));
+    vdom.immediately_render_and_diff(removed.clone());
+    assert_rendered(&container, &removed);
+}
+
+before_after! {
+    raw_html_to_element {
+        before(cx) {
+            div(&cx).children([raw_html("<hr>"), text("a")]).finish()
+        }
+        after(cx) {
+            div(&cx).children([span(&cx).finish(), text("a")]).finish()
+        }
+    }
+
+    element_to_raw_html {
+        before(cx) {
+            div(&cx).children([span(&cx).finish(), empty()]).finish()
+        }
+        after(cx) {
+            div(&cx).children([raw_html("<hr>"), raw_html("<br>")]).finish()
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use super::{assert_rendered, create_element, RenderFn};
+use dodrio::remote::{Applier, Frame, ListenerId};
+use dodrio::{scheduler::Manual, FlushError, Node, RenderContext, Vdom};
+use std::cell::{Cell, RefCell};
+use wasm_bindgen::{JsCast, JsValue};
+use std::rc::Rc;
+
+fn greeting<'a>(cx: &mut RenderContext<'a>, name: &'a str) -> Node<'a> {
+    use dodrio::builder::*;
+    div(&cx)
+        .attr("class", "greeting")
+        .children([text("Hello, "), strong(&cx).children([text(name)]).finish()])
+        .finish()
+}
+
+/// Create a remote `Vdom` whose frames are serialized and queued up, rather
+/// than applied immediately.
+fn remote<R>(component: R) -> (Vdom, Rc<RefCell<Vec<Vec<u8>>>>)
+where
+    R: 'static + for<'a> dodrio::Render<'a>,
+{
+    let queue = Rc::new(RefCell::new(vec![]));
+    let sender = queue.clone();
+    let vdom = Vdom::with_transport(component, move |frame: Frame| {
+        sender.borrow_mut().push(frame.to_bytes());
+    });
+    (vdom, queue)
+}
+
+fn apply_queued(applier: &mut Applier, queue: &RefCell<Vec<Vec<u8>>>) {
+    for bytes in queue.borrow_mut().drain(..) {
+        let frame = Frame::from_bytes(&bytes).unwrap();
+        applier.apply(&frame);
+    }
+}
+
+#[test]
+fn applies_frames_in_another_container() {
+    let before = Rc::new(RenderFn(|cx| greeting(cx, "world")));
+    let after = Rc::new(RenderFn(|cx| greeting(cx, "remote")));
+
+    let (vdom, queue) = remote(before.clone());
+    assert_eq!(queue.borrow().len(), 1);
+
+    let container = create_element("div");
+    let mut applier = Applier::new(&container, |_listener, _event| {});
+    apply_queued(&mut applier, &queue);
+    assert_rendered(&container, &before);
+
+    vdom.immediately_render_and_diff(after.clone());
+    apply_queued(&mut applier, &queue);
+    assert_rendered(&container, &after);
+}
+
+#[test]
+fn does_not_send_empty_frames() {
+    let component = Rc::new(RenderFn(|cx| greeting(cx, "world")));
+    let (vdom, queue) = remote(component.clone());
+
+    // The first re-render drops strings that were only used to create the
+    // initial DOM from the cache.
+    vdom.immediately_render_and_diff(component.clone());
+    queue.borrow_mut().clear();
+
+    vdom.immediately_render_and_diff(component);
+    assert!(queue.borrow().is_empty());
+}
+
+#[test]
+fn empties_container_when_dropped() {
+    let (_vdom, queue) = remote(RenderFn(|cx| greeting(cx, "world")));
+
+    let container = create_element("div");
+    let mut applier = Applier::new(&container, |_listener, _event| {});
+    apply_queued(&mut applier, &queue);
+    assert_eq!(container.children().len(), 1);
+
+    drop(applier);
+    assert!(container.children().is_empty());
+}
+
+#[test]
+fn builder_configures_remote_vdoms() {
+    let manual = Manual::new();
+    let queue = Rc::new(RefCell::new(vec![]));
+    let sender = queue.clone();
+    let vdom = Vdom::builder()
+        .scheduler(manual.clone())
+        .mount_with_transport(RenderFn(|cx| greeting(cx, "world")), move |frame: Frame| {
+            sender.borrow_mut().push(frame.to_bytes());
+        });
+    assert_eq!(queue.borrow().len(), 1);
+
+    // Scheduled renders wait for the configured scheduler.
+    vdom.weak().schedule_render();
+    assert!(manual.is_pending());
+    assert_eq!(manual.run(), 1);
+}
+
+#[test]
+fn renders_only_the_placeholders_of_portals() {
+    use dodrio::builder::*;
+
+    let target = create_element("div");
+    let portal_target = target.clone();
+    let component = RenderFn(move |cx| {
+        div(&cx)
+            .children([text("page"), portal(cx, &portal_target, [text("modal")])])
+            .finish()
+    });
+    let (vdom, queue) = remote(component);
+
+    let container = create_element("div");
+    let mut applier = Applier::new(&container, |_listener, _event| {});
+    apply_queued(&mut applier, &queue);
+    let page = container.first_child().unwrap();
+    assert_eq!(page.text_content(), "page");
+    assert!(page.children()[1].is_comment());
+    assert!(target.children().is_empty());
+
+    vdom.immediately_render_and_diff(RenderFn(|_cx| text("gone")));
+    apply_queued(&mut applier, &queue);
+    assert_eq!(container.text_content(), "gone");
+    assert!(target.children().is_empty());
+}
+
+#[test]
+fn dispatching_events_while_rendering_fails() {
+    let reentrant = Rc::new(Cell::new(false));
+    let component = {
+        let reentrant = reentrant.clone();
+        RenderFn(move |cx| {
+            let reentrant = reentrant.clone();
+            cx.after_render(move |_root, vdom| {
+                let event = JsValue::NULL.unchecked_into();
+                let result = vdom.dispatch_event(ListenerId::from_parts(0, 0), event);
+                reentrant.set(matches!(result, Err(FlushError::Reentrant)));
+            });
+            greeting(cx, "world")
+        })
+    };
+    let (_vdom, _queue) = remote(component);
+    assert!(reentrant.get());
+}
@@ -0,0 +1,90 @@
+use super::{create_element, RenderFn};
+use dodrio::{builder::*, scheduler::Manual, Vdom};
+use std::cell::Cell;
+use std::rc::Rc;
+
+fn counting_vdom(manual: &Manual) -> (Vdom, Rc<Cell<usize>>) {
+    let renders = Rc::new(Cell::new(0));
+    let container = create_element("div");
+    let vdom = Vdom::builder()
+        .scheduler(manual.clone())
+        .mount(&container, {
+            let renders = renders.clone();
+            RenderFn(move |cx| {
+                renders.set(renders.get() + 1);
+                div(&cx).finish()
+            })
+        });
+    (vdom, renders)
+}
+
+#[test]
+fn manual_scheduler_renders_when_run() {
+    let manual = Manual::new();
+    let (vdom, renders) = counting_vdom(&manual);
+    assert_eq!(renders.get(), 1);
+    assert!(!manual.is_pending());
+
+    // Renders that are scheduled before the next render are coalesced.
+    vdom.weak().schedule_render();
+    vdom.weak().schedule_render();
+    assert!(manual.is_pending());
+    assert_eq!(renders.get(), 1);
+
+    assert_eq!(manual.run(), 1);
+    assert!(!manual.is_pending());
+    assert_eq!(renders.get(), 2);
+
+    vdom.weak().schedule_render();
+    assert_eq!(manual.run(), 1);
+    assert_eq!(renders.get(), 3);
+}
+
+#[test]
+fn manual_scheduler_skips_flushed_renders() {
+    let manual = Manual::new();
+    let (vdom, renders) = counting_vdom(&manual);
+
+    vdom.weak().schedule_render();
+    vdom.flush().unwrap();
+    assert_eq!(renders.get(), 2);
+
+    manual.run();
+    assert_eq!(renders.get(), 2);
+}
+
+#[test]
+fn manual_scheduler_retries_while_busy() {
+    let manual = Manual::new();
+    let renders = Rc::new(Cell::new(0));
+    let container = create_element("div");
+    let vdom = Vdom::builder()
+        .scheduler(manual.clone())
+        .mount(&container, {
+            let renders = renders.clone();
+            let manual = manual.clone();
+            RenderFn(move |cx| {
+                renders.set(renders.get() + 1);
+                if renders.get() == 2 {
+                    let manual = manual.clone();
+                    cx.after_render(move |_root, vdom| {
+                        // The virtual DOM is still busy, so the render that is
+                        // run here is put back to wait for the next `run`.
+                        vdom.schedule_render();
+                        assert_eq!(manual.run(), 1);
+                        assert!(manual.is_pending());
+                    });
+                }
+                div(&cx).finish()
+            })
+        });
+
+    vdom.weak().schedule_render();
+    manual.run();
+    assert_eq!(renders.get(), 2);
+    assert!(manual.is_pending());
+
+    manual.run();
+    assert_eq!(renders.get(), 3);
+    assert!(!manual.is_pending());
+}
@@ -0,0 +1,118 @@
+use super::{assert_rendered, before_after, create_element, RenderFn};
+use dodrio::{builder::*, bumpalo, Node, RenderContext, Vdom};
+use std::rc::Rc;
+
+fn dot<'a>(cx: &mut RenderContext<'a>, x: u32) -> Node<'a> {
+    let left = bumpalo::format!(in cx.bump, "{}px", x).into_bump_str();
+    div(&cx)
+        .style("position", "absolute")
+        .style("left", left)
+        .style("background", "red")
+        .finish()
+}
+
+#[test]
+fn styles_are_not_attributes() {
+    let r = Rc::new(RenderFn(|cx| dot(cx, 0)));
+
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, r.clone());
+    assert_rendered(&container, &r);
+
+    let dot = container.first_child().unwrap();
+    assert!(dot.attributes().is_empty());
+    assert_eq!(dot.style("left").as_deref(), Some("0px"));
+}
+
+#[test]
+fn only_changed_styles_are_set() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| dot(cx, 0)));
+    vdom.immediately_render_and_diff(RenderFn(|cx| dot(cx, 0)));
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| dot(cx, 10)));
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "add_cached_string 6 \"left\"",
+            "add_cached_string 7 \"10px\"",
+            "set_style \"left\" \"10px\"",
+        ]
+    );
+}
+
+#[test]
+fn styles_are_set_again_when_the_style_attribute_changes() {
+    let container = create_element("div");
+    let vdom = Vdom::new(
+        &container,
+        RenderFn(|cx| div(&cx).attr("style", "color: red").style("left", "0px").finish()),
+    );
+    let element = container.first_child().unwrap();
+    assert_eq!(element.attribute("style").as_deref(), Some("color: red"));
+    assert_eq!(element.style("left").as_deref(), Some("0px"));
+
+    let disassembly = vdom.immediately_render_and_disassemble(RenderFn(|cx| {
+        div(&cx).attr("style", "color: blue").style("left", "0px").finish()
+    }));
+    assert_eq!(
+        disassembly.lines(),
+        [
+            "push_child 0",
+            "add_cached_string 4 \"color: blue\"",
+            "set_attribute \"style\" \"color: blue\"",
+            "set_style \"left\" \"0px\"",
+            "drop_cached_string 1",
+        ]
+    );
+    assert_eq!(element.attribute("style").as_deref(), Some("color: blue"));
+    assert_eq!(element.style("left").as_deref(), Some("0px"));
+}
+
+before_after! {
+    add_style {
+        before(cx) {
+            div(&cx).finish()
+        }
+        after(cx) {
+            div(&cx).style("color", "red").finish()
+        }
+    }
+
+    remove_style {
+        before(cx) {
+            div(&cx).style("color", "red").style("width", "10px").finish()
+        }
+        after(cx) {
+            div(&cx).style("width", "10px").finish()
+        }
+    }
+
+    reorder_styles {
+        before(cx) {
+            div(&cx).style("color", "red").style("width", "10px").finish()
+        }
+        after(cx) {
+            div(&cx).style("width", "20px").style("color", "red").finish()
+        }
+    }
+
+    styles_and_attributes {
+        before(cx) {
+            span(&cx).attr("class", "a").style("color", "red").finish()
+        }
+        after(cx) {
+            span(&cx).attr("class", "b").style("color", "blue").finish()
+        }
+    }
+
+    style_attribute_and_styles {
+        before(cx) {
+            div(&cx).attr("style", "color: red").style("width", "10px").finish()
+        }
+        after(cx) {
+            div(&cx).attr("style", "color: blue").style("width", "10px").finish()
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use super::{assert_rendered, before_after, create_element, RenderFn};
+use dodrio::{builder::*, Node, RenderContext, Vdom};
+use std::rc::Rc;
+use wasm_bindgen_test::*;
+
+fn todo<'a>(cx: &mut RenderContext<'a>, completed: bool) -> Node<'a> {
+    li(&cx)
+        .class("todo")
+        .class_if("completed", completed)
+        .finish()
+}
+
+#[wasm_bindgen_test]
+fn render_classes() {
+    let r = Rc::new(RenderFn(|cx| todo(cx, true)));
+
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, r.clone());
+    assert_rendered(&container, &r);
+}
+
+#[wasm_bindgen_test]
+fn classes_added_by_others_are_left_alone() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, RenderFn(|cx| todo(cx, false)));
+
+    let li = container.first_element_child().unwrap();
+    li.set_attribute("class", "todo animating").unwrap();
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| todo(cx, true)));
+    assert_eq!(
+        li.get_attribute("class").as_deref(),
+        Some("todo animating completed")
+    );
+
+    vdom.immediately_render_and_diff(RenderFn(|cx| todo(cx, false)));
+    assert_eq!(li.get_attribute("class").as_deref(), Some("todo animating"));
+}
+
+before_after! {
+    add_class {
+        before(cx) {
+            div(&cx).finish()
+        }
+        after(cx) {
+            div(&cx).class("a").class("b").finish()
+        }
+    }
+
+    remove_all_classes {
+        before(cx) {
+            div(&cx).class("a").class("b").finish()
+        }
+        after(cx) {
+            div(&cx).finish()
+        }
+    }
+
+    swap_classes {
+        before(cx) {
+            div(&cx).class("a").class("b").finish()
+        }
+        after(cx) {
+            div(&cx).class("b").class("c").finish()
+        }
+    }
+}
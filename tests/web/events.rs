@@ -1,5 +1,5 @@
 use super::create_element;
-use dodrio::{Node, Render, RenderContext, Vdom};
+use dodrio::{bumpalo, ListenerOptions, Node, Render, RenderContext, RootRender, Vdom, VdomWeak};
 use futures::future::{select, Either};
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
@@ -248,3 +248,612 @@ async fn event_does_not_fire_after_unmounting() {
         Either::Right((Err(_), outer)) => assert_eq!(outer.await, Ok("outer")),
     }
 }
+
+// Records the order in which its listeners are invoked: a capturing listener on
+// the outer element, and a bubbling listener on the inner `#target` element.
+struct CaptureContainer {
+    calls: Rc<RefCell<Vec<&'static str>>>,
+    once: bool,
+}
+
+impl<'a> Render<'a> for CaptureContainer {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+
+        let capture = ListenerOptions {
+            capture: true,
+            ..Default::default()
+        };
+        let target = ListenerOptions {
+            once: self.once,
+            ..Default::default()
+        };
+        div(&cx)
+            .on_with_options("click", capture, |root, _scheduler, _event| {
+                let root = root.unwrap_mut::<CaptureContainer>();
+                root.calls.borrow_mut().push("outer");
+            })
+            .children([div(&cx)
+                .attr("id", "target")
+                .on_with_options("click", target, |root, _scheduler, _event| {
+                    let root = root.unwrap_mut::<CaptureContainer>();
+                    root.calls.borrow_mut().push("target");
+                })
+                .finish()])
+            .finish()
+    }
+}
+
+#[wasm_bindgen_test]
+fn capture_listener_is_called_first() {
+    let container = create_element("div");
+    let calls = Rc::new(RefCell::new(vec![]));
+
+    let _vdom = Vdom::new(
+        &container,
+        CaptureContainer {
+            calls: calls.clone(),
+            once: false,
+        },
+    );
+
+    target(&container).click();
+    assert_eq!(*calls.borrow(), ["outer", "target"]);
+}
+
+#[wasm_bindgen_test]
+async fn once_listener_is_called_once() {
+    let container = create_element("div");
+    let calls = Rc::new(RefCell::new(vec![]));
+
+    let vdom = Vdom::new(
+        &container,
+        CaptureContainer {
+            calls: calls.clone(),
+            once: true,
+        },
+    );
+
+    target(&container).click();
+    target(&container).click();
+    assert_eq!(*calls.borrow(), ["outer", "target", "outer"]);
+
+    // Changing the options re-adds the listener.
+    vdom.weak()
+        .set_component(Box::new(CaptureContainer {
+            calls: calls.clone(),
+            once: false,
+        }))
+        .await
+        .unwrap();
+    calls.borrow_mut().clear();
+
+    target(&container).click();
+    target(&container).click();
+    assert_eq!(*calls.borrow(), ["outer", "target", "outer", "target"]);
+}
+
+// Records the order in which its listeners are invoked: one on the outer
+// element, and one on the inner `#target` element, which may stop the event's
+// propagation.
+struct BubbleContainer {
+    calls: Rc<RefCell<Vec<String>>>,
+    stop_propagation: bool,
+}
+
+impl<'a> Render<'a> for BubbleContainer {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+
+        div(&cx)
+            .attr("id", "outer")
+            .on("click", |root, _scheduler, event| {
+                let root = root.unwrap_mut::<BubbleContainer>();
+                let current_target: web_sys::Element =
+                    event.current_target().unwrap_throw().unchecked_into();
+                root.calls.borrow_mut().push(current_target.id());
+            })
+            .children([div(&cx)
+                .children([span(&cx)
+                    .attr("id", "target")
+                    .on("click", |root, _scheduler, event| {
+                        let root = root.unwrap_mut::<BubbleContainer>();
+                        let current_target: web_sys::Element =
+                            event.current_target().unwrap_throw().unchecked_into();
+                        root.calls.borrow_mut().push(current_target.id());
+                        if root.stop_propagation {
+                            event.stop_propagation();
+                        }
+                    })
+                    .finish()])
+                .finish()])
+            .finish()
+    }
+}
+
+fn delegated_clicks(stop_propagation: bool) -> (Vec<String>, bool) {
+    let container = create_element("div");
+    let calls = Rc::new(RefCell::new(vec![]));
+
+    // Listen outside of the container, to see whether the event keeps
+    // propagating past it.
+    let reached_parent = Rc::new(Cell::new(false));
+    let parent = create_element("div");
+    parent.append_child(&container).unwrap();
+    let on_parent_click = {
+        let reached_parent = reached_parent.clone();
+        Closure::wrap(Box::new(move |_| reached_parent.set(true)) as Box<dyn FnMut(web_sys::Event)>)
+    };
+    parent
+        .add_event_listener_with_callback("click", on_parent_click.as_ref().unchecked_ref())
+        .unwrap();
+
+    let _vdom = Vdom::builder().delegate_events(true).mount(
+        &container,
+        BubbleContainer {
+            calls: calls.clone(),
+            stop_propagation,
+        },
+    );
+
+    target(&container).click();
+    let calls = calls.borrow().clone();
+    (calls, reached_parent.get())
+}
+
+#[wasm_bindgen_test]
+fn delegated_listeners_are_called_while_bubbling() {
+    let (calls, reached_parent) = delegated_clicks(false);
+    assert_eq!(calls, ["target", "outer"]);
+    assert!(reached_parent);
+}
+
+#[wasm_bindgen_test]
+fn delegated_listeners_respect_stop_propagation() {
+    let (calls, reached_parent) = delegated_clicks(true);
+    assert_eq!(calls, ["target"]);
+    assert!(!reached_parent);
+}
+
+// Records the order in which its listeners are invoked: a passive listener on
+// the outer element, and a listener with the default options on the inner
+// `#target` element.
+struct PassiveContainer {
+    calls: Rc<RefCell<Vec<&'static str>>>,
+}
+
+impl<'a> Render<'a> for PassiveContainer {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+
+        let passive = ListenerOptions {
+            passive: true,
+            ..Default::default()
+        };
+        div(&cx)
+            .on_with_options("click", passive, |root, _scheduler, _event| {
+                let root = root.unwrap_mut::<PassiveContainer>();
+                root.calls.borrow_mut().push("outer");
+            })
+            .children([div(&cx)
+                .attr("id", "target")
+                .on("click", |root, _scheduler, _event| {
+                    let root = root.unwrap_mut::<PassiveContainer>();
+                    root.calls.borrow_mut().push("target");
+                })
+                .finish()])
+            .finish()
+    }
+}
+
+#[wasm_bindgen_test]
+fn listeners_with_options_are_called_before_delegated_listeners() {
+    let container = create_element("div");
+    let calls = Rc::new(RefCell::new(vec![]));
+    let _vdom = Vdom::new(
+        &container,
+        PassiveContainer {
+            calls: calls.clone(),
+        },
+    );
+    target(&container).click();
+    assert_eq!(*calls.borrow(), ["target", "outer"]);
+
+    // The passive listener is still added to its element, so it is called while
+    // the event bubbles up to the container, before the delegated listener on
+    // the inner element. This is a known limitation of delegation.
+    let container = create_element("div");
+    let calls = Rc::new(RefCell::new(vec![]));
+    let _vdom = Vdom::builder().delegate_events(true).mount(
+        &container,
+        PassiveContainer {
+            calls: calls.clone(),
+        },
+    );
+    target(&container).click();
+    assert_eq!(*calls.borrow(), ["outer", "target"]);
+}
+
+#[wasm_bindgen_test]
+async fn delegated_listener_is_not_called_after_removal() {
+    let container = create_element("div");
+    let calls = Rc::new(Cell::new(0));
+
+    let vdom = Vdom::builder().delegate_events(true).mount(
+        &container,
+        ListensOnlyOnFirstRender::new({
+            let calls = calls.clone();
+            move || calls.set(calls.get() + 1)
+        }),
+    );
+
+    target(&container).click();
+    assert_eq!(calls.get(), 1);
+
+    // Re-render, so we aren't listening anymore.
+    vdom.weak().render().await.unwrap();
+
+    target(&container).click();
+    assert_eq!(calls.get(), 1);
+}
+
+// Records the values that its `#target` input's typed listeners are given.
+struct TypedInput {
+    values: Rc<RefCell<Vec<String>>>,
+}
+
+impl<'a> Render<'a> for TypedInput {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+
+        input(&cx)
+            .attr("id", "target")
+            .on_input(|root, _scheduler, _event, value| {
+                let root = root.unwrap_mut::<TypedInput>();
+                root.values.borrow_mut().push(value);
+            })
+            .on_click(|root, _scheduler, event| {
+                let root = root.unwrap_mut::<TypedInput>();
+                root.values
+                    .borrow_mut()
+                    .push(format!("click {}", event.button()));
+            })
+            .finish()
+    }
+}
+
+#[wasm_bindgen_test]
+fn typed_listeners_get_their_event_and_value() {
+    let container = create_element("div");
+    let values = Rc::new(RefCell::new(vec![]));
+
+    let _vdom = Vdom::new(
+        &container,
+        TypedInput {
+            values: values.clone(),
+        },
+    );
+
+    let input = target(&container);
+    js_sys::Reflect::set(&input, &"value".into(), &"hello".into()).unwrap();
+    input
+        .dispatch_event(&web_sys::Event::new("input").unwrap())
+        .unwrap();
+    input.click();
+    // A plain `Event` isn't a `MouseEvent`, so the `click` listener skips it.
+    input
+        .dispatch_event(&web_sys::Event::new("click").unwrap())
+        .unwrap();
+
+    assert_eq!(*values.borrow(), ["hello", "click 0"]);
+}
+
+// A list of buttons whose listeners are all the same zero-sized closure.
+struct Buttons {
+    len: usize,
+    clicks: Rc<Cell<usize>>,
+}
+
+impl<'a> Render<'a> for Buttons {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+
+        let mut list = div(&cx);
+        for i in 0..self.len {
+            let id = if i == 0 { "target" } else { "other" };
+            list = list.child(
+                button(&cx)
+                    .attr("id", id)
+                    .on("click", |root, _scheduler, _event| {
+                        let root = root.unwrap_mut::<Buttons>();
+                        root.clicks.set(root.clicks.get() + 1);
+                    })
+                    .finish(),
+            );
+        }
+        list.finish()
+    }
+}
+
+#[wasm_bindgen_test]
+async fn identical_listeners_on_many_elements() {
+    let container = create_element("div");
+    let clicks = Rc::new(Cell::new(0));
+
+    let vdom = Vdom::new(
+        &container,
+        Buttons {
+            len: 3,
+            clicks: clicks.clone(),
+        },
+    );
+
+    target(&container).click();
+    assert_eq!(clicks.get(), 1);
+
+    // Removing the other buttons' listeners must not unregister the first
+    // button's listener.
+    vdom.weak()
+        .set_component(Box::new(Buttons {
+            len: 1,
+            clicks: clicks.clone(),
+        }))
+        .await
+        .unwrap();
+
+    target(&container).click();
+    assert_eq!(clicks.get(), 2);
+}
+
+// Rows that share one plain function as their listener, each passing its own
+// index.
+struct Rows {
+    clicked: Rc<RefCell<Vec<usize>>>,
+}
+
+fn on_row_click(root: &mut dyn RootRender, _vdom: VdomWeak, _event: web_sys::Event, i: usize) {
+    root.unwrap_mut::<Rows>().clicked.borrow_mut().push(i);
+}
+
+impl<'a> Render<'a> for Rows {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+
+        let mut list = ul(&cx);
+        for i in 0..5 {
+            let id = if i == 3 { "target" } else { "other" };
+            list = list.child(
+                li(&cx)
+                    .attr("id", id)
+                    .on_with("click", i, on_row_click)
+                    .finish(),
+            );
+        }
+        list.finish()
+    }
+}
+
+#[wasm_bindgen_test]
+fn listener_is_called_with_its_data() {
+    let container = create_element("div");
+    let clicked = Rc::new(RefCell::new(vec![]));
+
+    let _vdom = Vdom::new(
+        &container,
+        Rows {
+            clicked: clicked.clone(),
+        },
+    );
+
+    target(&container).click();
+    assert_eq!(*clicked.borrow(), [3]);
+}
+
+// A button whose async listener waits for `release` to be signalled, and then
+// increments the count.
+struct AsyncCounter {
+    count: usize,
+    release: Rc<RefCell<Option<futures::channel::oneshot::Receiver<()>>>>,
+    dropped: Rc<Cell<bool>>,
+}
+
+// Sets its flag when dropped.
+struct DropFlag(Rc<Cell<bool>>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+impl<'a> Render<'a> for AsyncCounter {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+
+        let count = bumpalo::format!(in cx.bump, "{}", self.count).into_bump_str();
+        button(&cx)
+            .attr("id", "target")
+            .on_async("click", |root, vdom, _event| {
+                let counter = root.unwrap_mut::<AsyncCounter>();
+                let release = counter.release.borrow_mut().take().unwrap_throw();
+                let flag = DropFlag(counter.dropped.clone());
+                async move {
+                    let _flag = flag;
+                    let _ = release.await;
+                    vdom.with_component(|root| root.unwrap_mut::<AsyncCounter>().count += 1)
+                        .await
+                        .unwrap_throw();
+                }
+            })
+            .children([text(count)])
+            .finish()
+    }
+}
+
+fn async_counter() -> (
+    AsyncCounter,
+    futures::channel::oneshot::Sender<()>,
+    Rc<Cell<bool>>,
+) {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    let dropped = Rc::new(Cell::new(false));
+    let counter = AsyncCounter {
+        count: 0,
+        release: Rc::new(RefCell::new(Some(receiver))),
+        dropped: dropped.clone(),
+    };
+    (counter, sender, dropped)
+}
+
+#[wasm_bindgen_test]
+async fn async_listener_renders_when_it_completes() {
+    let container = create_element("div");
+    let (counter, release, dropped) = async_counter();
+    let vdom = Vdom::new(&container, counter);
+
+    target(&container).click();
+    release.send(()).unwrap();
+
+    // Wait for the future to complete, and then for the render that it
+    // scheduled.
+    while !dropped.get() {
+        vdom.weak().with_component(|_| ()).await.unwrap();
+    }
+    vdom.weak().render().await.unwrap();
+
+    assert_eq!(target(&container).text_content().as_deref(), Some("1"));
+}
+
+#[wasm_bindgen_test]
+fn async_listener_is_cancelled_when_the_vdom_is_dropped() {
+    let container = create_element("div");
+    let (counter, _release, dropped) = async_counter();
+    let vdom = Vdom::new(&container, counter);
+
+    target(&container).click();
+    assert!(!dropped.get());
+
+    drop(vdom);
+    assert!(dropped.get());
+}
+
+// Like `Rows`, but each row passes data that is too big to be stored inline in
+// its listener.
+struct LabeledRows {
+    clicked: Rc<RefCell<Vec<String>>>,
+}
+
+fn on_labeled_row_click(
+    root: &mut dyn RootRender,
+    _vdom: VdomWeak,
+    _event: web_sys::Event,
+    (i, label): (usize, &str),
+) {
+    root.unwrap_mut::<LabeledRows>()
+        .clicked
+        .borrow_mut()
+        .push(format!("{} {}", i, label));
+}
+
+impl<'a> Render<'a> for LabeledRows {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+
+        let mut list = ul(&cx);
+        for i in 0..5 {
+            let id = if i == 3 { "target" } else { "other" };
+            let label = bumpalo::format!(in cx.bump, "row {}", i).into_bump_str();
+            list = list.child(
+                li(&cx)
+                    .attr("id", id)
+                    .on_with("click", (i, label), on_labeled_row_click)
+                    .finish(),
+            );
+        }
+        list.finish()
+    }
+}
+
+#[wasm_bindgen_test]
+fn listener_is_called_with_data_larger_than_a_word() {
+    let container = create_element("div");
+    let clicked = Rc::new(RefCell::new(vec![]));
+
+    let _vdom = Vdom::new(
+        &container,
+        LabeledRows {
+            clicked: clicked.clone(),
+        },
+    );
+
+    target(&container).click();
+    assert_eq!(*clicked.borrow(), ["3 row 3"]);
+}
+
+// A button that counts its clicks, followed by a long list that makes renders
+// take many slices.
+struct SlowCounter {
+    clicks: Rc<Cell<usize>>,
+    label: Rc<Cell<&'static str>>,
+}
+
+impl<'a> Render<'a> for SlowCounter {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+
+        let mut list = ul(&cx);
+        for _ in 0..50 {
+            list = list.child(li(&cx).children([text(self.label.get())]).finish());
+        }
+        div(&cx)
+            .children([
+                button(&cx)
+                    .attr("id", "target")
+                    .on("click", |root, _vdom, _event| {
+                        let counter = root.unwrap_mut::<SlowCounter>();
+                        counter.clicks.set(counter.clicks.get() + 1);
+                    })
+                    .finish(),
+                list.finish(),
+            ])
+            .finish()
+    }
+}
+
+#[wasm_bindgen_test]
+fn event_during_incremental_render() {
+    use dodrio::{scheduler::Manual, RenderBudget};
+
+    let container = create_element("div");
+    let clicks = Rc::new(Cell::new(0));
+    let label = Rc::new(Cell::new("before"));
+    let manual = Manual::new();
+    let vdom = Vdom::builder()
+        .scheduler(manual.clone())
+        .render_budget(Some(RenderBudget::Nodes(1)))
+        .mount(
+            &container,
+            SlowCounter {
+                clicks: clicks.clone(),
+                label: label.clone(),
+            },
+        );
+
+    // Start a render, but only diff a little of it.
+    label.set("after");
+    vdom.weak().schedule_render();
+    manual.run();
+    assert!(manual.is_pending());
+
+    // The button's listener is replaced by the render, but the physical DOM
+    // still has the old one, which must still be invoked. Doing so finishes
+    // the render first.
+    target(&container).click();
+    assert_eq!(clicks.get(), 1);
+    assert!(container.text_content().unwrap().ends_with("after"));
+
+    // The render has nothing left to do, and the new listener works.
+    manual.run();
+    target(&container).click();
+    assert_eq!(clicks.get(), 2);
+}
@@ -0,0 +1,189 @@
+use super::{assert_rendered, create_element, RenderFn};
+use dodrio::{ssr, Node, Render, RenderContext, Vdom};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+
+/// Server-render `server` into a new container, hydrate it with `client`, and
+/// assert that the physical DOM matches `client` afterwards.
+fn hydrate<R, S>(server: &R, client: S)
+where
+    R: for<'a> Render<'a>,
+    S: 'static + for<'a> Render<'a>,
+{
+    let container = create_element("div");
+    container.set_inner_html(&ssr::render_to_string(server));
+
+    let client = Rc::new(client);
+    let _vdom = Vdom::hydrate(&container, client.clone());
+    assert_rendered(&container, &client);
+}
+
+#[wasm_bindgen_test]
+fn reuses_server_rendered_nodes() {
+    let component = RenderFn(|cx| {
+        use dodrio::builder::*;
+        div(&cx)
+            .attr("class", "app")
+            .children([
+                h1(&cx).children([text("Hello")]).finish(),
+                p(&cx).children([text("world")]).finish(),
+            ])
+            .finish()
+    });
+
+    let container = create_element("div");
+    container.set_inner_html(&ssr::render_to_string(&component));
+    let root = container.first_child().unwrap();
+    let heading = container.query_selector("h1").unwrap().unwrap();
+
+    let _vdom = Vdom::hydrate(&container, component);
+
+    assert!(root.is_same_node(container.first_child().as_ref()));
+    let heading_after: &web_sys::Node = &container.query_selector("h1").unwrap().unwrap();
+    assert!(heading.is_same_node(Some(heading_after)));
+}
+
+#[wasm_bindgen_test]
+fn ignores_whitespace_around_the_root() {
+    let container = create_element("div");
+    container.set_inner_html("\n  <!-- app -->\n  <span>hi</span>\n");
+
+    let component = Rc::new(RenderFn(|cx| {
+        use dodrio::builder::*;
+        span(&cx).children([text("hi")]).finish()
+    }));
+    let _vdom = Vdom::hydrate(&container, component.clone());
+    assert_rendered(&container, &component);
+    assert_eq!(container.child_nodes().length(), 1);
+}
+
+#[wasm_bindgen_test]
+fn hydrates_root_fragments() {
+    fn rows<'a>(cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+        fragment(
+            cx.bump,
+            [
+                h1(&cx).children([text("title")]).finish(),
+                fragment(cx.bump, [p(&cx).finish(), p(&cx).finish()]),
+            ],
+        )
+    }
+    hydrate(&RenderFn(rows), RenderFn(rows));
+}
+
+#[wasm_bindgen_test]
+fn adopts_empty_placeholders() {
+    let container = create_element("div");
+    container.set_inner_html("<!-- app --><!----><p></p>");
+
+    let component = Rc::new(RenderFn(|cx| {
+        use dodrio::builder::*;
+        fragment(cx.bump, [empty(), p(&cx).finish()])
+    }));
+    let placeholder = container.child_nodes().item(1).unwrap();
+    let _vdom = Vdom::hydrate(&container, component.clone());
+    assert_rendered(&container, &component);
+    assert!(placeholder.is_same_node(container.first_child().as_ref()));
+}
+
+#[wasm_bindgen_test]
+fn splits_adjacent_text_nodes() {
+    fn paragraph<'a>(cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+        p(&cx)
+            .children([text("one"), text(""), text("two"), text("three")])
+            .finish()
+    }
+    hydrate(&RenderFn(paragraph), RenderFn(paragraph));
+}
+
+#[wasm_bindgen_test]
+fn patches_mismatches() {
+    let server = RenderFn(|cx| {
+        use dodrio::builder::*;
+        ul(&cx)
+            .attr("class", "old")
+            .attr("data-stale", "yes")
+            .children([
+                li(&cx).children([text("a")]).finish(),
+                span(&cx).finish(),
+                li(&cx).children([text("c")]).finish(),
+                li(&cx).children([text("d")]).finish(),
+            ])
+            .finish()
+    });
+    let client = RenderFn(|cx| {
+        use dodrio::builder::*;
+        ul(&cx)
+            .attr("class", "new")
+            .children([
+                li(&cx).children([text("a")]).finish(),
+                li(&cx).children([text("b")]).finish(),
+                li(&cx).children([text("changed")]).finish(),
+            ])
+            .finish()
+    });
+    hydrate(&server, client);
+}
+
+#[wasm_bindgen_test]
+fn creates_missing_children() {
+    let server = RenderFn(|cx| {
+        use dodrio::builder::*;
+        div(&cx).finish()
+    });
+    let client = RenderFn(|cx| {
+        use dodrio::builder::*;
+        div(&cx)
+            .children([text("hello"), br(&cx).finish()])
+            .finish()
+    });
+    hydrate(&server, client);
+}
+
+struct Clickable {
+    on_click: Box<dyn FnMut()>,
+}
+
+impl<'a> Render<'a> for Clickable {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+        div(&cx)
+            .children([button(&cx)
+                .attr("id", "target")
+                .on("click", |root, _vdom, _event| {
+                    (root.unwrap_mut::<Clickable>().on_click)();
+                })
+                .children([text("Click me")])
+                .finish()])
+            .finish()
+    }
+}
+
+#[wasm_bindgen_test]
+async fn attaches_listeners() {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    let mut sender = Some(sender);
+
+    let clickable = Clickable {
+        on_click: Box::new(move || {
+            sender
+                .take()
+                .expect_throw("should only call listener once")
+                .send(())
+                .expect_throw("should not have dropped the receiver");
+        }),
+    };
+
+    let container = create_element("div");
+    container.set_inner_html(&ssr::render_to_string(&clickable));
+    let button = container.query_selector("#target").unwrap().unwrap();
+
+    let _vdom = Vdom::hydrate(&container, clickable);
+
+    button.unchecked_ref::<web_sys::HtmlElement>().click();
+    receiver.await.unwrap();
+}
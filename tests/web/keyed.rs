@@ -31,6 +31,26 @@ where
     parent.finish()
 }
 
+/// Like `keyed`, but keyed by the hashes of string slugs instead of `u32`s.
+fn slugged<'a, Slugs>(cx: &mut RenderContext<'a>, slugs: Slugs) -> Node<'a>
+where
+    Slugs: AsRef<[&'static str]>,
+{
+    let mut parent = div(&cx).attr("class", "parent");
+
+    for &slug in slugs.as_ref() {
+        parent = parent.child(
+            div(&cx)
+                .attr("class", "keyed")
+                .attr("id", slug)
+                .hashed_key(slug)
+                .finish(),
+        );
+    }
+
+    parent.finish()
+}
+
 async fn assert_keyed<Before, After>(before: Before, after: After) -> Result<(), JsValue>
 where
     Before: 'static + for<'a> Render<'a>,
@@ -266,4 +286,13 @@ keyed_tests! {
                 .finish()
         }
     }
+
+    hashed_keys_reorder {
+        before(cx) {
+            slugged(cx, ["intro", "setup", "usage", "faq"])
+        }
+        after(cx) {
+            slugged(cx, ["faq", "usage", "intro", "changelog", "setup"])
+        }
+    }
 }
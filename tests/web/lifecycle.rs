@@ -0,0 +1,55 @@
+use super::create_element;
+use dodrio::{builder::*, Node, Render, RenderContext, RootRender, Vdom};
+use std::cell::RefCell;
+use wasm_bindgen_test::*;
+
+thread_local! {
+    static LOG: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Chart {
+    show: bool,
+}
+
+impl<'a> Render<'a> for Chart {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let mut container = div(&cx);
+        if self.show {
+            let chart = canvas(&cx)
+                .on_mount(|root, _vdom, element| {
+                    assert!(root.unwrap_mut::<Chart>().show);
+                    assert!(element.parent_node().is_some());
+                    LOG.with(|log| {
+                        log.borrow_mut()
+                            .push(format!("mount {}", element.tag_name()))
+                    });
+                })
+                .on_unmount(|_root, _vdom, element| {
+                    assert!(element.parent_node().is_none());
+                    LOG.with(|log| {
+                        log.borrow_mut()
+                            .push(format!("unmount {}", element.tag_name()))
+                    });
+                })
+                .finish();
+            container = container.child(chart);
+        }
+        container.finish()
+    }
+}
+
+#[wasm_bindgen_test]
+async fn mount_and_unmount() {
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, Chart { show: true });
+    assert_eq!(LOG.with(|log| log.replace(vec![])), ["mount CANVAS"]);
+
+    vdom.weak()
+        .with_component(|root: &mut dyn RootRender| {
+            root.unwrap_mut::<Chart>().show = false;
+        })
+        .await
+        .unwrap();
+    vdom.weak().render().await.unwrap();
+    assert_eq!(LOG.with(|log| log.replace(vec![])), ["unmount CANVAS"]);
+}
@@ -4,7 +4,8 @@
 
 use bumpalo::Bump;
 use dodrio::{
-    Attribute, CachedSet, ElementNode, Node, NodeKind, Render, RenderContext, TextNode, Vdom,
+    Attribute, CachedSet, ElementNode, FragmentNode, Node, NodeKind, PortalNode, Property,
+    PropertyValue, RawHtmlNode, Render, RenderContext, TextNode, Vdom,
 };
 use fxhash::FxHashMap;
 use log::*;
@@ -17,10 +18,19 @@ use wasm_bindgen_test::*;
 wasm_bindgen_test_configure!(run_in_browser);
 
 pub mod cached;
+pub mod classes;
 pub mod events;
+pub mod hydrate;
 pub mod js_api;
 pub mod keyed;
+pub mod lifecycle;
+pub mod node_refs;
+pub mod portals;
+pub mod properties;
+pub mod remote;
 pub mod render;
+pub mod scheduler;
+pub mod styles;
 
 pub fn window() -> web_sys::Window {
     web_sys::window().expect("no global `window` exists")
@@ -58,12 +68,15 @@ pub fn assert_rendered<R: for<'a> Render<'a>>(container: &web_sys::Element, r: &
     let templates = &mut FxHashMap::default();
     let cx = &mut RenderContext::new(bump, cached_set, templates);
     let node = r.render(cx);
-    let child = container
-        .first_child()
-        .expect("container does not have anything rendered into it?");
 
+    // The root may be a fragment, so compare the container's children with the
+    // flattened root, just like any other element's children.
     let cached_set = cached_set.borrow();
-    check_node(&cached_set, &child, &node);
+    check_children(
+        &cached_set,
+        &child_nodes(container),
+        std::slice::from_ref(&node),
+    );
 
     fn stringify_actual_node(n: &web_sys::Node) -> String {
         if let Some(el) = n.dyn_ref::<web_sys::Element>() {
@@ -93,6 +106,9 @@ pub fn assert_rendered<R: for<'a> Render<'a>>(container: &web_sys::Element, r: &
             NodeKind::Element(&ElementNode {
                 tag_name,
                 attributes,
+                properties,
+                styles,
+                classes,
                 children,
                 namespace,
                 ..
@@ -106,7 +122,10 @@ pub fn assert_rendered<R: for<'a> Render<'a>>(container: &web_sys::Element, r: &
                     .dyn_ref::<web_sys::Element>()
                     .expect("`actual` should be an `Element`");
                 check_attributes(actual.attributes(), attributes);
-                check_children(cached_set, actual.child_nodes(), children);
+                check_classes(actual, attributes, classes);
+                check_properties(actual, properties);
+                check_styles(actual, styles);
+                check_children(cached_set, &child_nodes(actual), children);
                 if let Some(namespace) = namespace {
                     assert_eq!(actual.namespace_uri(), Some(namespace.into()))
                 }
@@ -115,12 +134,55 @@ pub fn assert_rendered<R: for<'a> Render<'a>>(container: &web_sys::Element, r: &
                 let (expected, _template) = cached_set.get(c.id);
                 check_node(cached_set, actual, &expected);
             }
+            NodeKind::Empty => {
+                assert_eq!(
+                    actual.node_type(),
+                    web_sys::Node::COMMENT_NODE,
+                    "actual.node_type() == COMMENT_NODE"
+                );
+            }
+            NodeKind::RawHtml(RawHtmlNode { html }) => {
+                // The markup is kept in a wrapper.
+                let actual = actual
+                    .dyn_ref::<web_sys::Element>()
+                    .expect("`actual` should be an `Element`");
+                assert_eq!(actual.local_name(), "div", "actual.local_name() == \"div\"");
+                assert_eq!(actual.inner_html(), html, "actual.inner_html() == expected.html()");
+            }
+            NodeKind::Portal(PortalNode {
+                target, children, ..
+            }) => {
+                assert_eq!(
+                    actual.node_type(),
+                    web_sys::Node::COMMENT_NODE,
+                    "actual.node_type() == COMMENT_NODE"
+                );
+                // The portal's children are between a pair of comment markers
+                // in the target, which must not have any other contents.
+                let nodes = child_nodes(&target.element);
+                let is_comment = |node: &web_sys::Node| node.node_type() == web_sys::Node::COMMENT_NODE;
+                assert!(
+                    nodes.len() >= 2 && is_comment(&nodes[0]) && is_comment(&nodes[nodes.len() - 1]),
+                    "portal target should hold only the portal's markers and children, found {:?}",
+                    target.element.inner_html()
+                );
+                check_children(cached_set, &nodes[1..nodes.len() - 1], children);
+            }
+            NodeKind::Fragment(_) => unreachable!("fragments are flattened"),
         }
     }
 
     fn check_attributes(actual: web_sys::NamedNodeMap, expected: &[Attribute]) {
+        // Inline styles live in the `style` attribute, which may also be left
+        // behind empty after removing the last style, so it is checked by
+        // `check_styles` instead.
+        let has_style_attr = actual.get_named_item("style").is_some()
+            && expected.iter().all(|attr| attr.name() != "style");
+        // Likewise for class list tokens and `check_classes`.
+        let has_class_attr = actual.get_named_item("class").is_some()
+            && expected.iter().all(|attr| attr.name() != "class");
         assert_eq!(
-            actual.length(),
+            actual.length() - has_style_attr as u32 - has_class_attr as u32,
             expected.len() as u32,
             "actual's number of attributes == expected's number of attributes"
         );
@@ -137,15 +199,90 @@ pub fn assert_rendered<R: for<'a> Render<'a>>(container: &web_sys::Element, r: &
         }
     }
 
-    fn check_children(cached_set: &CachedSet, actual: web_sys::NodeList, expected: &[Node]) {
+    fn check_properties(actual: &web_sys::Element, expected: &[Property]) {
+        for prop in expected {
+            let actual_value = js_sys::Reflect::get(actual, &prop.name().into())
+                .expect(&format!("should have property \"{}\"", prop.name()));
+            let expected_value: JsValue = match prop.value() {
+                PropertyValue::Bool(b) => b.into(),
+                PropertyValue::Number(n) => n.into(),
+                PropertyValue::String(s) => s.into(),
+            };
+            assert_eq!(
+                actual_value,
+                expected_value,
+                "actual prop value == expected prop value for prop \"{}\"",
+                prop.name()
+            );
+        }
+    }
+
+    fn check_classes(actual: &web_sys::Element, attributes: &[Attribute], expected: &[&str]) {
+        if attributes.iter().any(|attr| attr.name() == "class") {
+            return;
+        }
+        let actual = actual.get_attribute("class").unwrap_or_default();
+        let mut actual: Vec<_> = actual.split_whitespace().collect();
+        actual.sort_unstable();
+        let mut expected = expected.to_vec();
+        expected.sort_unstable();
+        expected.dedup();
+        assert_eq!(actual, expected, "actual classes == expected classes");
+    }
+
+    fn check_styles(actual: &web_sys::Element, expected: &[Attribute]) {
+        let style = js_sys::Reflect::get(actual, &"style".into()).unwrap();
+        let length = js_sys::Reflect::get(&style, &"length".into()).unwrap();
         assert_eq!(
-            actual.length(),
-            expected.len() as u32,
+            length.as_f64(),
+            Some(expected.len() as f64),
+            "actual's number of styles == expected's number of styles"
+        );
+        for s in expected {
+            let actual_value = js_sys::Reflect::get(&style, &s.name().into()).unwrap();
+            assert_eq!(
+                actual_value.as_string().as_deref(),
+                Some(s.value()),
+                "actual style value == expected style value for style \"{}\"",
+                s.name()
+            );
+        }
+    }
+
+    fn check_children(cached_set: &CachedSet, actual: &[web_sys::Node], expected: &[Node]) {
+        let mut flattened = vec![];
+        flatten(cached_set, expected, &mut flattened);
+        let expected = flattened;
+        assert_eq!(
+            actual.len(),
+            expected.len(),
             "actual children length == expected children length"
         );
-        for (i, child) in expected.iter().enumerate() {
-            let actual_child = actual.item(i as u32).unwrap();
-            check_node(cached_set, &actual_child, child);
+        for (actual_child, child) in actual.iter().zip(expected) {
+            check_node(cached_set, actual_child, child);
+        }
+    }
+
+    fn child_nodes(node: &web_sys::Node) -> Vec<web_sys::Node> {
+        let nodes = node.child_nodes();
+        (0..nodes.length()).filter_map(|i| nodes.item(i)).collect()
+    }
+
+    // Flatten fragments, including cached ones, into their parent's children.
+    fn flatten<'a>(
+        cached_set: &'a CachedSet,
+        children: &'a [Node<'a>],
+        out: &mut Vec<&'a Node<'a>>,
+    ) {
+        for child in children {
+            let resolved = match child.kind {
+                NodeKind::Cached(ref c) => cached_set.get(c.id).0,
+                _ => child,
+            };
+            match resolved.kind {
+                NodeKind::Fragment(FragmentNode { children }) => flatten(cached_set, children, out),
+                _ => out.push(child),
+            }
         }
     }
 }
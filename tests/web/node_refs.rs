@@ -0,0 +1,55 @@
+use super::create_element;
+use dodrio::{builder::*, Node, NodeRef, Render, RenderContext, RootRender, Vdom};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+
+struct Search {
+    show: bool,
+    input: NodeRef,
+}
+
+impl<'a> Render<'a> for Search {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let mut form = form(&cx);
+        if self.show {
+            form = form.child(
+                input(&cx)
+                    .attr("type", "search")
+                    .node_ref(&self.input)
+                    .finish(),
+            );
+        }
+        form.finish()
+    }
+}
+
+#[wasm_bindgen_test]
+async fn node_ref_points_at_rendered_element() {
+    let input = NodeRef::new();
+    let container = create_element("div");
+    let vdom = Vdom::new(
+        &container,
+        Search {
+            show: true,
+            input: input.clone(),
+        },
+    );
+
+    let physical = container.query_selector("input").unwrap().unwrap();
+    assert!(input.get().unwrap().is_same_node(Some(&physical)));
+
+    // The element can be used like any other, e.g. to focus it.
+    let html_input: web_sys::HtmlElement = input.get().unwrap().unchecked_into();
+    html_input.focus().unwrap();
+
+    vdom.weak()
+        .with_component(|root: &mut dyn RootRender| {
+            root.unwrap_mut::<Search>().show = false;
+        })
+        .await
+        .unwrap();
+    vdom.weak().render().await.unwrap();
+
+    assert!(container.query_selector("input").unwrap().is_none());
+    assert!(input.get().is_none());
+}
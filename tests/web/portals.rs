@@ -0,0 +1,77 @@
+use super::{assert_rendered, create_element};
+use dodrio::{builder::*, Node, Render, RenderContext, RootRender, Vdom};
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+
+struct Modal {
+    target: web_sys::Element,
+    open: bool,
+    clicks: u32,
+}
+
+impl<'a> Render<'a> for Modal {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        let modal = if self.open {
+            let close = button(&cx)
+                .on("click", |root, _vdom, _event| {
+                    root.unwrap_mut::<Modal>().clicks += 1;
+                })
+                .children([text("close")])
+                .finish();
+            portal(cx, &self.target, [close])
+        } else {
+            empty()
+        };
+        div(&cx).children([text("page"), modal]).finish()
+    }
+}
+
+#[wasm_bindgen_test]
+async fn clicks_in_portals_reach_the_root_component() {
+    for &delegate_events in &[false, true] {
+        let container = create_element("div");
+        let target = create_element("div");
+        let vdom = Vdom::builder().delegate_events(delegate_events).mount(
+            &container,
+            Modal {
+                target: target.clone(),
+                open: true,
+                clicks: 0,
+            },
+        );
+        assert_eq!(target.text_content().unwrap(), "close");
+
+        let close: web_sys::HtmlElement = target
+            .query_selector("button")
+            .unwrap()
+            .unwrap()
+            .unchecked_into();
+        close.click();
+
+        vdom.weak()
+            .with_component(|root: &mut dyn RootRender| {
+                let modal = root.unwrap_mut::<Modal>();
+                assert_eq!(modal.clicks, 1);
+                modal.open = false;
+            })
+            .await
+            .unwrap();
+        vdom.weak().render().await.unwrap();
+        assert!(target.first_child().is_none());
+    }
+}
+
+#[wasm_bindgen_test]
+fn portal_children_are_rendered_into_the_target() {
+    let container = create_element("div");
+    let target = create_element("div");
+    let modal = Rc::new(Modal {
+        target: target.clone(),
+        open: true,
+        clicks: 0,
+    });
+    let _vdom = Vdom::new(&container, modal.clone());
+    assert_rendered(&container, &modal);
+    assert_eq!(container.text_content().unwrap(), "page");
+}
@@ -0,0 +1,52 @@
+use super::{assert_rendered, before_after, create_element, RenderFn};
+use dodrio::{builder::*, Vdom};
+use std::rc::Rc;
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+fn set_indeterminate() {
+    let r = Rc::new(RenderFn(|cx| {
+        input(&cx)
+            .attr("type", "checkbox")
+            .prop("indeterminate", true)
+            .finish()
+    }));
+
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, r.clone());
+    assert_rendered(&container, &r);
+
+    let input = container.first_element_child().unwrap();
+    let indeterminate = js_sys::Reflect::get(&input, &"indeterminate".into()).unwrap();
+    assert_eq!(indeterminate.as_bool(), Some(true));
+    assert!(!input.has_attribute("indeterminate"));
+}
+
+before_after! {
+    add_property {
+        before(cx) {
+            video(&cx).finish()
+        }
+        after(cx) {
+            video(&cx).prop("muted", true).finish()
+        }
+    }
+
+    update_property {
+        before(cx) {
+            video(&cx).prop("volume", 0.5).finish()
+        }
+        after(cx) {
+            video(&cx).prop("volume", 0.25).finish()
+        }
+    }
+
+    remove_property {
+        before(cx) {
+            div(&cx).prop("dodrioCustom", "a").prop("title", "b").finish()
+        }
+        after(cx) {
+            div(&cx).prop("title", "b").finish()
+        }
+    }
+}
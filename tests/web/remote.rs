@@ -0,0 +1,87 @@
+use super::{assert_rendered, create_element, RenderFn};
+use dodrio::remote::{Applier, Frame};
+use dodrio::{Node, Render, RenderContext, Vdom, VdomWeak};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+fn applies_frames_in_another_container() {
+    let component = Rc::new(RenderFn(|cx| {
+        use dodrio::builder::*;
+        ul(&cx)
+            .children([
+                li(&cx).children([text("a")]).finish(),
+                li(&cx).children([text("b")]).finish(),
+            ])
+            .finish()
+    }));
+
+    let frames = Rc::new(RefCell::new(vec![]));
+    let sender = frames.clone();
+    let _vdom = Vdom::with_transport(component.clone(), move |frame: Frame| {
+        sender.borrow_mut().push(frame.to_bytes());
+    });
+
+    let container = create_element("div");
+    let mut applier = Applier::new(&container, |_listener, _event| {});
+    for bytes in frames.borrow_mut().drain(..) {
+        applier.apply(&Frame::from_bytes(&bytes).unwrap());
+    }
+    assert_rendered(&container, &component);
+}
+
+struct Clickable {
+    on_click: Box<dyn FnMut()>,
+}
+
+impl<'a> Render<'a> for Clickable {
+    fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+        use dodrio::builder::*;
+        button(&cx)
+            .attr("id", "remote-target")
+            .on("click", |root, _vdom, _event| {
+                (root.unwrap_mut::<Clickable>().on_click)();
+            })
+            .children([text("Click me")])
+            .finish()
+    }
+}
+
+#[wasm_bindgen_test]
+async fn routes_events_back() {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    let mut sender = Some(sender);
+
+    let clickable = Clickable {
+        on_click: Box::new(move || {
+            sender
+                .take()
+                .expect_throw("should only call listener once")
+                .send(())
+                .expect_throw("should not have dropped the receiver");
+        }),
+    };
+
+    let frames = Rc::new(RefCell::new(vec![]));
+    let frame_sender = frames.clone();
+    let vdom = Vdom::with_transport(clickable, move |frame: Frame| {
+        frame_sender.borrow_mut().push(frame);
+    });
+
+    // Stand in for a real transport by handing the event straight back.
+    let weak: VdomWeak = vdom.weak();
+    let container = create_element("div");
+    let mut applier = Applier::new(&container, move |listener, event| {
+        weak.dispatch_event(listener, event).unwrap_throw();
+    });
+    for frame in frames.borrow_mut().drain(..) {
+        applier.apply(&frame);
+    }
+
+    let button = container.first_child().unwrap_throw();
+    button.unchecked_ref::<web_sys::HtmlElement>().click();
+    receiver.await.unwrap_throw();
+}
@@ -341,3 +341,104 @@ before_after! {
         }
     }
 }
+
+#[wasm_bindgen_test]
+fn render_root_fragment() {
+    let r = Rc::new(RenderFn(|cx| {
+        fragment(cx.bump, [text("hello"), span(&cx).finish(), text("world")])
+    }));
+
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, r.clone());
+    assert_eq!(container.child_nodes().length(), 3);
+    assert_rendered(&container, &r);
+}
+
+before_after! {
+    raw_html_is_replaced_when_changed {
+        before(cx) {
+            div(&cx).children([raw_html("<p>Hello, <em>world</em>!</p>")]).finish()
+        }
+        after(cx) {
+            div(&cx).children([raw_html("<p>Goodbye!</p>")]).finish()
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+async fn after_render_runs_once_rendered() {
+    struct Chat {
+        messages: usize,
+        container: web_sys::Element,
+        scrolled_to: usize,
+    }
+
+    impl<'a> Render<'a> for Chat {
+        fn render(&self, cx: &mut RenderContext<'a>) -> Node<'a> {
+            let messages = self.messages;
+            let container = self.container.clone();
+            cx.after_render(move |root, _vdom| {
+                let list = container.first_element_child().unwrap();
+                assert_eq!(list.child_element_count() as usize, messages);
+                root.unwrap_mut::<Chat>().scrolled_to = messages;
+            });
+
+            let mut list = ul(&cx);
+            for _ in 0..messages {
+                list = list.child(li(&cx).finish());
+            }
+            list.finish()
+        }
+    }
+
+    let container = create_element("div");
+    let vdom = Vdom::new(
+        &container,
+        Chat {
+            messages: 1,
+            container: container.clone(),
+            scrolled_to: 0,
+        },
+    );
+
+    let weak = vdom.weak();
+    weak.with_component(|root| {
+        let chat = root.unwrap_mut::<Chat>();
+        assert_eq!(chat.scrolled_to, 1);
+        chat.messages = 2;
+    })
+    .await
+    .unwrap();
+    weak.render().await.unwrap();
+
+    let scrolled_to = weak
+        .with_component(|root| root.unwrap_mut::<Chat>().scrolled_to)
+        .await
+        .unwrap();
+    assert_eq!(scrolled_to, 2);
+}
+
+#[wasm_bindgen_test(async)]
+async fn render_now_completes_scheduled_render() {
+    use std::cell::Cell;
+
+    let count = Rc::new(Cell::new(0));
+    let container = create_element("div");
+    let vdom = Vdom::new(&container, {
+        let count = count.clone();
+        RenderFn(move |cx| {
+            let count = dodrio::bumpalo::format!(in cx.bump, "{}", count.get());
+            p(&cx).children([text(count.into_bump_str())]).finish()
+        })
+    });
+    let weak = vdom.weak();
+
+    count.set(1);
+    let scheduled = weak.render();
+    weak.render_now().unwrap();
+    assert_eq!(container.text_content().unwrap(), "1");
+
+    // Scheduled renders still complete afterwards.
+    scheduled.await.unwrap();
+    assert_eq!(container.text_content().unwrap(), "1");
+}
@@ -0,0 +1,74 @@
+use super::{create_element, RenderFn};
+use dodrio::{builder::*, scheduler, Vdom};
+use std::cell::Cell;
+use std::rc::Rc;
+use wasm_bindgen_test::*;
+
+async fn renders_with<S>(scheduler: S)
+where
+    S: 'static + scheduler::Scheduler,
+{
+    let count = Rc::new(Cell::new(0));
+    let container = create_element("div");
+    let vdom = Vdom::builder().scheduler(scheduler).mount(&container, {
+        let count = count.clone();
+        RenderFn(move |cx| {
+            let count = dodrio::bumpalo::format!(in cx.bump, "{}", count.get());
+            p(&cx).children([text(count.into_bump_str())]).finish()
+        })
+    });
+    assert_eq!(container.text_content().unwrap(), "0");
+
+    count.set(1);
+    vdom.weak().render().await.unwrap();
+    assert_eq!(container.text_content().unwrap(), "1");
+}
+
+#[wasm_bindgen_test(async)]
+async fn animation_frame_scheduler() {
+    renders_with(scheduler::AnimationFrame).await;
+}
+
+#[wasm_bindgen_test(async)]
+async fn microtask_scheduler() {
+    renders_with(scheduler::Microtask).await;
+}
+
+#[wasm_bindgen_test(async)]
+async fn timeout_scheduler() {
+    renders_with(scheduler::Timeout).await;
+}
+
+#[wasm_bindgen_test(async)]
+async fn idle_scheduler() {
+    renders_with(scheduler::Idle).await;
+}
+
+#[wasm_bindgen_test(async)]
+async fn render_budget_renders_incrementally() {
+    use dodrio::RenderBudget;
+    use std::time::Duration;
+
+    let count = Rc::new(Cell::new(0));
+    let container = create_element("div");
+    let vdom = Vdom::builder()
+        .render_budget(Some(RenderBudget::Time(Duration::from_millis(1))))
+        .mount(&container, {
+            let count = count.clone();
+            RenderFn(move |cx| {
+                let count = dodrio::bumpalo::format!(in cx.bump, "{}", count.get());
+                let count = count.into_bump_str();
+                let mut list = ul(&cx);
+                for _ in 0..1000 {
+                    list = list.child(li(&cx).children([text(count)]).finish());
+                }
+                list.finish()
+            })
+        });
+
+    count.set(1);
+    vdom.weak().render().await.unwrap();
+    let list = container.first_element_child().unwrap();
+    assert_eq!(list.child_element_count(), 1000);
+    assert_eq!(list.text_content().unwrap(), "1".repeat(1000));
+}
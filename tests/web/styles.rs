@@ -0,0 +1,60 @@
+use super::{assert_rendered, before_after, create_element, RenderFn};
+use dodrio::{builder::*, Vdom};
+use std::rc::Rc;
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+fn render_styles() {
+    let r = Rc::new(RenderFn(|cx| {
+        div(&cx)
+            .attr("id", "styled")
+            .style("color", "red")
+            .style("margin-left", "10px")
+            .finish()
+    }));
+
+    let container = create_element("div");
+    let _vdom = Vdom::new(&container, r.clone());
+    assert_rendered(&container, &r);
+}
+
+// Only longhand properties are used here, since `assert_rendered` compares the
+// number of styles with the physical `style.length`, which counts shorthands
+// as all of their longhands.
+before_after! {
+    add_style {
+        before(cx) {
+            div(&cx).finish()
+        }
+        after(cx) {
+            div(&cx).style("color", "red").finish()
+        }
+    }
+
+    change_style {
+        before(cx) {
+            div(&cx).style("color", "red").style("width", "10px").finish()
+        }
+        after(cx) {
+            div(&cx).style("color", "blue").style("width", "10px").finish()
+        }
+    }
+
+    remove_style {
+        before(cx) {
+            div(&cx).style("color", "red").style("width", "10px").finish()
+        }
+        after(cx) {
+            div(&cx).style("width", "10px").finish()
+        }
+    }
+
+    remove_all_styles {
+        before(cx) {
+            div(&cx).style("color", "red").finish()
+        }
+        after(cx) {
+            div(&cx).attr("class", "plain").finish()
+        }
+    }
+}